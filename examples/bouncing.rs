@@ -0,0 +1,64 @@
+use anis::{color::Color, config::AppConfig, run_with};
+
+struct Ball {
+    pos: [f32; 2],
+    velocity: [f32; 2],
+    size: f32,
+    color: Color,
+}
+
+// A few rectangles bouncing off the edges of the window, with the state
+// and the per-frame logic passed to run_with.
+fn main() {
+    env_logger::init();
+
+    run_with(
+        AppConfig::default(),
+        |_context| {
+            (0..5)
+                .map(|i| Ball {
+                    pos: [40.0 + i as f32 * 90.0, 30.0 + i as f32 * 50.0],
+                    velocity: [
+                        120.0 + i as f32 * 40.0,
+                        180.0 - i as f32 * 25.0,
+                    ],
+                    size: 30.0 + i as f32 * 8.0,
+                    color: Color::rgb(
+                        1.0 - i as f32 * 0.2,
+                        0.4,
+                        i as f32 * 0.2,
+                    ),
+                })
+                .collect::<Vec<_>>()
+        },
+        |context, balls, dt| {
+            let (width, height) = context.viewport_size();
+
+            context.set_rectangles(vec![]);
+
+            for ball in balls.iter_mut() {
+                for axis in 0..2 {
+                    let max = [width, height][axis] - ball.size;
+                    ball.pos[axis] += ball.velocity[axis] * dt;
+
+                    // turn around at the edges, pointing back inside
+                    if ball.pos[axis] < 0.0 {
+                        ball.velocity[axis] = ball.velocity[axis].abs();
+                    } else if ball.pos[axis] > max {
+                        ball.velocity[axis] = -ball.velocity[axis].abs();
+                    }
+                    ball.pos[axis] = ball.pos[axis].clamp(0.0, max.max(0.0));
+                }
+
+                context.draw_rounded_rect(
+                    ball.pos[0],
+                    ball.pos[1],
+                    ball.size,
+                    ball.size,
+                    6.0,
+                    ball.color,
+                );
+            }
+        },
+    );
+}
@@ -0,0 +1,61 @@
+use anis::{config::AppConfig, context::Context, create_window};
+use winit::{
+    application::ApplicationHandler,
+    event::{StartCause, WindowEvent},
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+// Drives the context from an ApplicationHandler owned by the application
+// instead of using anis::run, so it can handle events itself.
+struct App<'a> {
+    context: Context<'a>,
+    frames: u64,
+}
+
+impl<'a> ApplicationHandler for App<'a> {
+    fn resumed(&mut self, _event_loop: &ActiveEventLoop) {}
+
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        self.context.handle_new_events(cause);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        match &event {
+            WindowEvent::RedrawRequested => self.frames += 1,
+            WindowEvent::CursorMoved { position, .. } => {
+                log::info!("Cursor at {:?}", position);
+            }
+            _ => {}
+        }
+
+        // resizing, drawing and closing is still left to the context
+        self.context.handle_window_event(event_loop, event);
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        println!("Drew {} frames", self.frames);
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let config = AppConfig::default();
+
+    let event_loop = EventLoop::new().unwrap();
+    let window = create_window(&event_loop, &config);
+
+    let mut app = App {
+        context: Context::new(&window, &config),
+        frames: 0,
+    };
+    app.context.set_target_fps(60);
+
+    event_loop.run_app(&mut app).unwrap();
+}
@@ -0,0 +1,52 @@
+use std::time::Instant;
+
+use anis::{config::AppConfig, context::Context, create_window};
+use image::{DynamicImage, Rgba, RgbaImage};
+use winit::event_loop::EventLoop;
+
+// Creates a few hundred small textures at startup and prints how long that
+// took, then draws all of them in a grid.
+const TEXTURE_COUNT: usize = 400;
+const TEXTURE_SIZE: u32 = 16;
+
+fn main() {
+    env_logger::init();
+
+    let config = AppConfig::default();
+
+    let event_loop = EventLoop::new().unwrap();
+    let window = create_window(&event_loop, &config);
+
+    let mut context = Context::new(&window, &config);
+
+    let images: Vec<DynamicImage> = (0..TEXTURE_COUNT)
+        .map(|i| {
+            let shade = (i * 255 / TEXTURE_COUNT) as u8;
+            RgbaImage::from_pixel(
+                TEXTURE_SIZE,
+                TEXTURE_SIZE,
+                Rgba([shade, 255 - shade, 128, 255]),
+            )
+            .into()
+        })
+        .collect();
+
+    let start = Instant::now();
+    let handles = context.create_textures_batch(&images).unwrap();
+    println!(
+        "Created {} textures in {:?}",
+        handles.len(),
+        start.elapsed()
+    );
+
+    let columns = 20;
+    for (i, handle) in handles.into_iter().enumerate() {
+        let pos = [
+            (i % columns) as f32 * (TEXTURE_SIZE + 4) as f32,
+            (i / columns) as f32 * (TEXTURE_SIZE + 4) as f32,
+        ];
+        context.draw_sprite(handle, pos, 1.0).unwrap();
+    }
+
+    event_loop.run_app(&mut context).unwrap();
+}
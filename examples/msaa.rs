@@ -0,0 +1,92 @@
+use anis::{config::AppConfig, context::Context, create_window};
+use winit::{
+    application::ApplicationHandler,
+    event::{ElementState, KeyEvent, StartCause, WindowEvent},
+    event_loop::{ActiveEventLoop, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
+    window::WindowId,
+};
+
+// Press M to switch between 4x multisampling and none. The thin bars are
+// placed at fractional positions, so their edges show the difference.
+struct App<'a> {
+    context: Context<'a>,
+}
+
+impl<'a> ApplicationHandler for App<'a> {
+    fn resumed(&mut self, _event_loop: &ActiveEventLoop) {}
+
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        self.context.handle_new_events(cause);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        if let WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    state: ElementState::Pressed,
+                    physical_key: PhysicalKey::Code(KeyCode::KeyM),
+                    repeat: false,
+                    ..
+                },
+            ..
+        } = event
+        {
+            let requested = if self.context.sample_count() > 1 {
+                1
+            } else {
+                4
+            };
+            let sample_count = self.context.set_sample_count(requested);
+            println!("Sample count: {}", sample_count);
+
+            self.context.request_redraw();
+        }
+
+        self.context.handle_window_event(event_loop, event);
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let config = AppConfig {
+        sample_count: 4,
+        ..Default::default()
+    };
+
+    let event_loop = EventLoop::new().unwrap();
+    let window = create_window(&event_loop, &config);
+
+    let mut context = Context::new(&window, &config);
+    println!("Sample count: {}", context.sample_count());
+
+    for i in 0..20 {
+        let offset = i as f32 * 0.3;
+        context.draw_rounded_rect(
+            50.0 + i as f32 * 20.0 + offset,
+            100.0 + offset,
+            2.5,
+            200.0,
+            0.0,
+            [1.0, 1.0, 1.0],
+        );
+    }
+    context.draw_rounded_rect(
+        500.0,
+        100.0,
+        150.0,
+        150.0,
+        75.0,
+        [0.9, 0.4, 0.2],
+    );
+
+    let mut app = App { context };
+
+    event_loop.run_app(&mut app).unwrap();
+}
@@ -0,0 +1,29 @@
+use anis::{color::Color, config::AppConfig, context::Context, create_window};
+use winit::{event_loop::EventLoop, window::Window};
+
+// A second window drawn with the same context, like a detached tool panel.
+// Closing it leaves the main window open.
+fn main() {
+    env_logger::init();
+
+    let config = AppConfig::default();
+
+    let event_loop = EventLoop::new().unwrap();
+    let window = create_window(&event_loop, &config);
+    #[allow(deprecated)]
+    let panel = event_loop
+        .create_window(Window::default_attributes().with_title("Panel"))
+        .unwrap();
+
+    let mut context = Context::new(&window, &config);
+    context.draw_rounded_rect(100.0, 100.0, 200.0, 150.0, 12.0, Color::RED);
+
+    let panel = context.add_window(&panel).unwrap();
+    context.set_window_clear_color(panel, Color::BLACK).unwrap();
+    context.begin_window(panel).unwrap();
+    context.draw_rounded_rect(20.0, 20.0, 100.0, 100.0, 12.0, Color::CYAN);
+    context.draw_rounded_rect(140.0, 20.0, 100.0, 100.0, 50.0, Color::YELLOW);
+    context.end_window().unwrap();
+
+    event_loop.run_app(&mut context).unwrap();
+}
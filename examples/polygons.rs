@@ -0,0 +1,48 @@
+use std::f32::consts::TAU;
+
+use anis::{config::AppConfig, context::Context, create_window};
+use winit::event_loop::EventLoop;
+
+// Fills a convex hexagon and two concave shapes, a star and an L.
+fn main() {
+    env_logger::init();
+
+    let config = AppConfig::default();
+
+    let event_loop = EventLoop::new().unwrap();
+    let window = create_window(&event_loop, &config);
+
+    let mut context = Context::new(&window, &config);
+
+    let hexagon: Vec<[f32; 2]> = (0..6)
+        .map(|i| {
+            let angle = i as f32 / 6.0 * TAU;
+            [150.0 + 80.0 * angle.cos(), 200.0 + 80.0 * angle.sin()]
+        })
+        .collect();
+    context.draw_polygon(&hexagon, [0.2, 0.8, 0.4, 1.0]);
+
+    // every other point is on the inner circle
+    let star: Vec<[f32; 2]> = (0..10)
+        .map(|i| {
+            let angle = i as f32 / 10.0 * TAU - TAU / 4.0;
+            let radius = if i % 2 == 0 { 90.0 } else { 35.0 };
+            [380.0 + radius * angle.cos(), 200.0 + radius * angle.sin()]
+        })
+        .collect();
+    context.draw_polygon(&star, [1.0, 0.8, 0.1, 1.0]);
+
+    context.draw_polygon(
+        &[
+            [520.0, 120.0],
+            [570.0, 120.0],
+            [570.0, 230.0],
+            [660.0, 230.0],
+            [660.0, 280.0],
+            [520.0, 280.0],
+        ],
+        [0.3, 0.5, 1.0, 0.8],
+    );
+
+    event_loop.run_app(&mut context).unwrap();
+}
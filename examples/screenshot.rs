@@ -0,0 +1,23 @@
+use anis::{config::AppConfig, context::Context};
+use winit::{event_loop::EventLoop, window::WindowAttributes};
+
+// Opens a window, draws a single frame into screenshot.png and exits.
+fn main() {
+    env_logger::init();
+
+    let event_loop = EventLoop::new().unwrap();
+
+    #[allow(deprecated)]
+    let window = event_loop
+        .create_window(WindowAttributes::default().with_visible(false))
+        .unwrap();
+
+    let mut context = Context::new(&window, &AppConfig::default());
+    let texture = context.create_texture_from_path("res/one.png").unwrap();
+    context.draw_texture_at(texture, [50.0, 50.0]).unwrap();
+
+    context.update(0.0);
+    context.save_screenshot("screenshot.png").unwrap();
+
+    println!("Saved screenshot.png");
+}
@@ -0,0 +1,43 @@
+use anis::{config::AppConfig, context::Context, create_window};
+use winit::event_loop::EventLoop;
+
+// Draws 100,000 small rectangles, way more than the rectangles buffer
+// starts out with, to check that it grows instead of cutting them off. The
+// rectangles stay in the list, so they're drawn every frame.
+const RECTANGLE_COUNT: usize = 100_000;
+
+fn main() {
+    env_logger::init();
+
+    let config = AppConfig::default();
+
+    let event_loop = EventLoop::new().unwrap();
+    let window = create_window(&event_loop, &config);
+
+    let mut context = Context::new(&window, &config);
+
+    // a grid that fills the window
+    let columns = (RECTANGLE_COUNT as f32).sqrt().ceil() as usize;
+    let (width, height) =
+        (context.size.width as f32, context.size.height as f32);
+    let cell = [width / columns as f32, height / columns as f32];
+
+    for i in 0..RECTANGLE_COUNT {
+        let (column, row) = (i % columns, i / columns);
+
+        context.draw_rounded_rect(
+            column as f32 * cell[0],
+            row as f32 * cell[1],
+            cell[0],
+            cell[1],
+            0.0,
+            [
+                column as f32 / columns as f32,
+                row as f32 / columns as f32,
+                0.5,
+            ],
+        );
+    }
+
+    event_loop.run_app(&mut context).unwrap();
+}
@@ -0,0 +1,68 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anis::{config::AppConfig, context::Context};
+use image::{DynamicImage, RgbImage, RgbaImage};
+use winit::{event_loop::EventLoop, window::WindowAttributes};
+
+// Uploads a big RGBA and a big RGB image and prints the most memory that
+// was allocated at once while doing it, on top of the image itself. The
+// RGBA image is uploaded straight from its own buffer and the RGB one is
+// converted in small chunks, so neither should come close to the 64MB of
+// a second copy (what the GPU backend allocates for staging is counted
+// too though).
+const SIZE: u32 = 4096;
+
+struct CountingAllocator;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current =
+            CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn measure(context: &mut Context, name: &str, image: &DynamicImage) {
+    let before = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+
+    context.create_texture_from_raw_data(image).unwrap();
+
+    let peak = PEAK.load(Ordering::Relaxed) - before;
+    println!("{}: {:.1}MB allocated at most", name, peak as f64 / 1e6);
+}
+
+fn main() {
+    env_logger::init();
+
+    let event_loop = EventLoop::new().unwrap();
+
+    #[allow(deprecated)]
+    let window = event_loop
+        .create_window(WindowAttributes::default().with_visible(false))
+        .unwrap();
+
+    let mut context = Context::new(&window, &AppConfig::default());
+
+    let rgba = DynamicImage::ImageRgba8(RgbaImage::new(SIZE, SIZE));
+    measure(&mut context, "RGBA", &rgba);
+    drop(rgba);
+
+    let rgb = DynamicImage::ImageRgb8(RgbImage::new(SIZE, SIZE));
+    measure(&mut context, "RGB", &rgb);
+}
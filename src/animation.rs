@@ -0,0 +1,274 @@
+use std::{fs::File, io::BufReader};
+
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder},
+    error::{DecodingError, ImageFormatHint},
+    AnimationDecoder, DynamicImage, Frames, GenericImage, ImageError,
+    ImageFormat, RgbaImage,
+};
+
+use crate::{
+    atlas::SpriteHandle,
+    context::{Context, TextureError, TextureHandle},
+    geometry::Vec2,
+    loader::load_image_from_path,
+};
+
+pub type AnimationHandle = usize;
+
+// Animations are decoded completely up front, so really big ones are
+// refused instead of eating all the memory. This is about 256MB of RGBA.
+pub const MAX_ANIMATION_PIXELS: u64 = 64 * 1024 * 1024;
+
+// browsers treat frames without a delay like this, so GIFs made for them
+// expect it
+const DEFAULT_FRAME_DELAY: f32 = 0.1;
+
+#[derive(Clone, Debug)]
+pub struct Animation {
+    // all frames are packed into one texture
+    pub frames: Vec<SpriteHandle>,
+    // how long each frame is shown, in seconds
+    pub delays: Vec<f32>,
+    pub duration: f32,
+    // size of one frame in pixels
+    pub frame_size: [f32; 2],
+}
+
+impl Animation {
+    /// An animation from a sprite sheet that's already loaded, with
+    /// `frame_count` frames of `frame_size` pixels each, played at `fps`.
+    /// The frames are read row by row from the top left, as many per row as
+    /// fit into the texture. `texture_size` is what
+    /// `Context::texture_size` returns for it.
+    pub fn from_sprite_sheet(
+        texture: TextureHandle,
+        texture_size: (u32, u32),
+        frame_size: [u32; 2],
+        frame_count: u32,
+        fps: f32,
+    ) -> Animation {
+        let (width, height) = texture_size;
+        let columns = (width / frame_size[0].max(1)).max(1);
+        let uv_size = [
+            frame_size[0] as f32 / width as f32,
+            frame_size[1] as f32 / height as f32,
+        ];
+
+        let frames = (0..frame_count.max(1))
+            .map(|i| SpriteHandle {
+                texture,
+                uv_pos: [
+                    (i % columns) as f32 * uv_size[0],
+                    (i / columns) as f32 * uv_size[1],
+                ],
+                uv_size,
+            })
+            .collect::<Vec<_>>();
+
+        // fps of 0 shows the first frame forever
+        let delay = if fps > 0.0 { 1.0 / fps } else { 0.0 };
+
+        Animation {
+            delays: vec![delay; frames.len()],
+            duration: delay * frames.len() as f32,
+            frames,
+            frame_size: [frame_size[0] as f32, frame_size[1] as f32],
+        }
+    }
+
+    // the frame shown `elapsed_seconds` after the animation started,
+    // looping forever
+    pub fn frame_at(&self, elapsed_seconds: f32) -> SpriteHandle {
+        if self.duration <= 0.0 {
+            return self.frames[0];
+        }
+
+        let mut time = elapsed_seconds.rem_euclid(self.duration);
+        for (frame, delay) in self.frames.iter().zip(self.delays.iter()) {
+            if time < *delay {
+                return *frame;
+            }
+            time -= delay;
+        }
+
+        // only reachable through float rounding
+        *self.frames.last().unwrap()
+    }
+}
+
+fn decode_frames(
+    frames: Frames,
+) -> Result<Vec<(RgbaImage, f32)>, TextureError> {
+    let mut decoded = vec![];
+    let mut pixels = 0;
+
+    for frame in frames {
+        let frame = frame.map_err(TextureError::CouldNotDecode)?;
+
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let mut delay = numerator as f32 / denominator as f32 / 1000.0;
+        if delay <= 0.0 {
+            delay = DEFAULT_FRAME_DELAY;
+        }
+
+        let buffer = frame.into_buffer();
+
+        // check while decoding, not after everything is already in memory
+        pixels += buffer.width() as u64 * buffer.height() as u64;
+        if pixels > MAX_ANIMATION_PIXELS {
+            return Err(TextureError::AnimationTooLarge);
+        }
+
+        decoded.push((buffer, delay));
+    }
+
+    Ok(decoded)
+}
+
+// every frame of the file with its delay, or just the image if it isn't
+// animated
+fn load_frames_from_path(
+    path: &str,
+) -> Result<Vec<(RgbaImage, f32)>, TextureError> {
+    let format = ImageFormat::from_path(path).ok();
+
+    let open = || {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(TextureError::CouldNotOpenFile)
+    };
+
+    match format {
+        Some(ImageFormat::Gif) => {
+            let decoder = GifDecoder::new(open()?)
+                .map_err(TextureError::CouldNotDecode)?;
+
+            decode_frames(decoder.into_frames())
+        }
+
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(open()?)
+                .map_err(TextureError::CouldNotDecode)?;
+
+            if decoder.is_apng().map_err(TextureError::CouldNotDecode)? {
+                let decoder =
+                    decoder.apng().map_err(TextureError::CouldNotDecode)?;
+
+                decode_frames(decoder.into_frames())
+            } else {
+                let image = DynamicImage::from_decoder(decoder)
+                    .map_err(TextureError::CouldNotDecode)?;
+
+                Ok(vec![(image.to_rgba8(), 0.0)])
+            }
+        }
+
+        _ => Ok(vec![(load_image_from_path(path)?.to_rgba8(), 0.0)]),
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Loads every frame of a GIF or APNG into one texture. Other images
+    /// (and PNGs that aren't animated) become an animation with a single
+    /// frame. Fails with `TextureError::AnimationTooLarge` if the frames
+    /// wouldn't fit in memory or in a texture.
+    pub fn load_animation_from_path(
+        &mut self,
+        path: &str,
+    ) -> Result<AnimationHandle, TextureError> {
+        let frames = load_frames_from_path(path)?;
+        if frames.is_empty() {
+            return Err(TextureError::CouldNotDecode(ImageError::Decoding(
+                DecodingError::from_format_hint(
+                    ImageFormatHint::PathExtension(path.into()),
+                ),
+            )));
+        }
+
+        // PACK FRAMES INTO A GRID
+        // =======================
+
+        // the frames of a GIF/APNG all have the size of the canvas
+        let (frame_width, frame_height) = frames[0].0.dimensions();
+        let columns = (frames.len() as f32).sqrt().ceil() as u32;
+        let rows = (frames.len() as u32).div_ceil(columns);
+
+        let max_dimension = self.device.limits().max_texture_dimension_2d;
+        let (width, height) = (frame_width * columns, frame_height * rows);
+        if width > max_dimension || height > max_dimension {
+            return Err(TextureError::AnimationTooLarge);
+        }
+
+        let mut sheet = RgbaImage::new(width, height);
+        let mut positions = Vec::with_capacity(frames.len());
+        for (i, (frame, _)) in frames.iter().enumerate() {
+            let x = (i as u32 % columns) * frame_width;
+            let y = (i as u32 / columns) * frame_height;
+
+            sheet
+                .copy_from(frame, x, y)
+                .map_err(TextureError::CouldNotDecode)?;
+            positions.push((x, y));
+        }
+
+        let texture =
+            self.create_texture_from_raw_data(&DynamicImage::from(sheet))?;
+        self.textures[texture].path = Some(path.to_string());
+
+        let sprites = positions
+            .into_iter()
+            .map(|(x, y)| SpriteHandle {
+                texture,
+                uv_pos: [x as f32 / width as f32, y as f32 / height as f32],
+                uv_size: [
+                    frame_width as f32 / width as f32,
+                    frame_height as f32 / height as f32,
+                ],
+            })
+            .collect();
+        let delays: Vec<f32> = frames.iter().map(|(_, delay)| *delay).collect();
+
+        self.animations.push(Animation {
+            frames: sprites,
+            duration: delays.iter().sum(),
+            delays,
+            frame_size: [frame_width as f32, frame_height as f32],
+        });
+
+        Ok(self.animations.len() - 1)
+    }
+
+    /// Draws the frame that's shown `elapsed_seconds` after the animation
+    /// started. Animations loop.
+    pub fn draw_animation(
+        &mut self,
+        handle: AnimationHandle,
+        pos: [f32; 2],
+        size: [f32; 2],
+        elapsed_seconds: f32,
+    ) -> Result<(), TextureError> {
+        let frame = self
+            .animations
+            .get(handle)
+            .ok_or(TextureError::InvalidHandle)?
+            .frame_at(elapsed_seconds);
+
+        self.draw_textured_rect(pos, size, frame);
+
+        Ok(())
+    }
+
+    /// Draws the current frame of an animation that isn't stored in the
+    /// context, like one from `Animation::from_sprite_sheet`, at its frame
+    /// size. The current frame is picked with `elapsed_time`.
+    pub fn draw_sprite_animation(
+        &mut self,
+        animation: &Animation,
+        pos: impl Into<Vec2>,
+    ) {
+        let frame = animation.frame_at(self.elapsed_time());
+
+        self.draw_textured_rect(pos, animation.frame_size, frame);
+    }
+}
@@ -0,0 +1,251 @@
+use image::{DynamicImage, GenericImageView};
+
+use crate::context::{write_to_texture, Context, TextureError, TextureHandle};
+
+pub type AtlasHandle = usize;
+
+// A part of a texture. Anything that draws a texture takes
+// impl Into<SpriteHandle>, so a plain TextureHandle (which covers the whole
+// texture) works just as well as a sprite packed into an atlas.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteHandle {
+    pub texture: TextureHandle,
+
+    // uv coordinates of the top left corner and size of the region, both
+    // in the 0..1 range
+    pub uv_pos: [f32; 2],
+    pub uv_size: [f32; 2],
+}
+
+impl From<TextureHandle> for SpriteHandle {
+    fn from(texture: TextureHandle) -> Self {
+        Self {
+            texture,
+            uv_pos: [0.0, 0.0],
+            uv_size: [1.0, 1.0],
+        }
+    }
+}
+
+pub struct Atlas {
+    pub texture: TextureHandle,
+    pub packer: ShelfPacker,
+}
+
+// Packs rectangles into rows ("shelves"). Each rectangle goes on the shelf
+// that wastes the least height, and a new shelf is opened under the last one
+// when none of them fit.
+#[derive(Clone, Debug)]
+pub struct ShelfPacker {
+    pub width: u32,
+    pub height: u32,
+
+    // empty space kept between entries so linear filtering doesn't bleed
+    // neighbouring images into each other
+    pub padding: u32,
+
+    shelves: Vec<Shelf>,
+}
+
+#[derive(Clone, Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    // where the next rectangle on this shelf goes
+    x: u32,
+}
+
+impl ShelfPacker {
+    pub fn new(width: u32, height: u32, padding: u32) -> Self {
+        Self {
+            width,
+            height,
+            padding,
+            shelves: vec![],
+        }
+    }
+
+    // returns the position of the top left corner, or AtlasFull if it
+    // doesn't fit
+    pub fn pack(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) -> Result<(u32, u32), TextureError> {
+        if width == 0 || height == 0 {
+            return Ok((0, 0));
+        }
+
+        if width > self.width || height > self.height {
+            return Err(TextureError::AtlasFull);
+        }
+
+        // TRY EXISTING SHELVES
+        // ====================
+
+        let best_shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| {
+                shelf.height >= height && shelf.x + width <= self.width
+            })
+            .min_by_key(|shelf| shelf.height - height);
+
+        if let Some(shelf) = best_shelf {
+            let pos = (shelf.x, shelf.y);
+            shelf.x += width + self.padding;
+
+            return Ok(pos);
+        }
+
+        // OPEN A NEW SHELF
+        // ================
+
+        let y = match self.shelves.last() {
+            Some(shelf) => shelf.y + shelf.height + self.padding,
+            None => 0,
+        };
+
+        if y + height > self.height {
+            return Err(TextureError::AtlasFull);
+        }
+
+        self.shelves.push(Shelf {
+            y,
+            height,
+            x: width + self.padding,
+        });
+
+        Ok((0, y))
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Creates an empty texture that many small images can be packed into,
+    /// so they only take up one texture slot. `padding` is the number of
+    /// empty pixels left between images.
+    pub fn create_atlas(
+        &mut self,
+        size: (u32, u32),
+        padding: u32,
+    ) -> Result<AtlasHandle, TextureError> {
+        let texture = self.create_texture_from_raw_data(
+            &DynamicImage::new_rgba8(size.0, size.1),
+        )?;
+
+        self.atlases.push(Atlas {
+            texture,
+            packer: ShelfPacker::new(size.0, size.1, padding),
+        });
+
+        Ok(self.atlases.len() - 1)
+    }
+
+    /// Packs the image into the atlas and returns the region it ended up
+    /// in. Fails with `TextureError::AtlasFull` if there's no room left.
+    pub fn add_image_to_atlas(
+        &mut self,
+        atlas: AtlasHandle,
+        data: &DynamicImage,
+    ) -> Result<SpriteHandle, TextureError> {
+        let atlas = self
+            .atlases
+            .get_mut(atlas)
+            .ok_or(TextureError::InvalidHandle)?;
+
+        let (width, height) = data.dimensions();
+        let (x, y) = atlas.packer.pack(width, height)?;
+
+        let atlas_size = (atlas.packer.width, atlas.packer.height);
+        let texture = &self.textures[atlas.texture];
+
+        // WRITE TO ATLAS TEXTURE
+        // ======================
+
+        write_to_texture(
+            &self.queue,
+            &texture.wgpu_texture,
+            texture.format,
+            (x, y),
+            data,
+        );
+        self.current_frame_stats.record_texture_upload();
+
+        Ok(SpriteHandle {
+            texture: atlas.texture,
+            uv_pos: [
+                x as f32 / atlas_size.0 as f32,
+                y as f32 / atlas_size.1 as f32,
+            ],
+            uv_size: [
+                width as f32 / atlas_size.0 as f32,
+                height as f32 / atlas_size.1 as f32,
+            ],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlaps(a: (u32, u32, u32, u32), b: (u32, u32, u32, u32)) -> bool {
+        a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3
+    }
+
+    #[test]
+    fn packs_odd_sized_rectangles_onto_shelves() {
+        let mut packer = ShelfPacker::new(64, 64, 0);
+
+        assert_eq!(packer.pack(13, 7).unwrap(), (0, 0));
+        assert_eq!(packer.pack(21, 5).unwrap(), (13, 0));
+        // taller than the first shelf, so it opens a new one
+        assert_eq!(packer.pack(9, 11).unwrap(), (0, 7));
+        // fits the first shelf with the least wasted height
+        assert_eq!(packer.pack(29, 3).unwrap(), (34, 0));
+        // doesn't fit next to the others on the first shelf anymore
+        assert_eq!(packer.pack(5, 7).unwrap(), (9, 7));
+    }
+
+    #[test]
+    fn keeps_padding_between_entries() {
+        let padding = 2;
+        let mut packer = ShelfPacker::new(32, 32, padding);
+
+        let sizes = [(5, 3), (7, 4), (3, 9), (11, 5), (9, 2), (15, 6)];
+        let rects: Vec<_> = sizes
+            .iter()
+            .map(|&(width, height)| {
+                let (x, y) = packer.pack(width, height).unwrap();
+                assert!(x + width <= 32 && y + height <= 32);
+                (x, y, width, height)
+            })
+            .collect();
+
+        // below the first shelf, and next to the first entry
+        assert_eq!((rects[1].0, rects[1].1), (0, 3 + padding));
+        assert_eq!((rects[4].0, rects[4].1), (5 + padding, 0));
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                // grown by the padding they still don't touch
+                let padded = (a.0, a.1, a.2 + padding, a.3 + padding);
+                assert!(!overlaps(padded, *b), "{:?} and {:?}", a, b);
+                let padded = (b.0, b.1, b.2 + padding, b.3 + padding);
+                assert!(!overlaps(padded, *a), "{:?} and {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn fails_with_atlas_full() {
+        let mut packer = ShelfPacker::new(16, 16, 1);
+
+        assert!(matches!(packer.pack(17, 1), Err(TextureError::AtlasFull)));
+        assert!(matches!(packer.pack(1, 17), Err(TextureError::AtlasFull)));
+
+        assert_eq!(packer.pack(16, 10).unwrap(), (0, 0));
+        // the padding under the first shelf leaves 5 rows
+        assert!(matches!(packer.pack(4, 6), Err(TextureError::AtlasFull)));
+        assert_eq!(packer.pack(4, 5).unwrap(), (0, 11));
+    }
+}
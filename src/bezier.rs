@@ -0,0 +1,121 @@
+use crate::{color::Color, context::Context};
+
+// what the draw_*_bezier functions use when they're given 0 segments
+pub const DEFAULT_BEZIER_SEGMENTS: u32 = 16;
+
+fn lerp(a: [f32; 2], b: [f32; 2], t: f32) -> [f32; 2] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+}
+
+// A cubic Bezier curve. It starts at the first control point, ends at the
+// last one and is pulled towards the two in between.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BezierCurve {
+    pub control_points: [[f32; 2]; 4],
+}
+
+impl BezierCurve {
+    pub fn new(p0: [f32; 2], p1: [f32; 2], p2: [f32; 2], p3: [f32; 2]) -> Self {
+        Self {
+            control_points: [p0, p1, p2, p3],
+        }
+    }
+
+    /// The cubic curve with the same shape as the quadratic curve with
+    /// control points `p0`, `p1` and `p2`.
+    pub fn quadratic(p0: [f32; 2], p1: [f32; 2], p2: [f32; 2]) -> Self {
+        Self::new(p0, lerp(p0, p1, 2.0 / 3.0), lerp(p2, p1, 2.0 / 3.0), p2)
+    }
+
+    /// The point at `t` (0 is the start, 1 the end), found with De
+    /// Casteljau's algorithm.
+    pub fn point_at(&self, t: f32) -> [f32; 2] {
+        let mut points = self.control_points;
+
+        // every round replaces the points with the ones between each pair,
+        // until one is left
+        for length in (1..4).rev() {
+            for i in 0..length {
+                points[i] = lerp(points[i], points[i + 1], t);
+            }
+        }
+
+        points[0]
+    }
+
+    /// `segments + 1` points evenly spaced in `t`, including both ends.
+    pub fn points(&self, segments: u32) -> Vec<[f32; 2]> {
+        let segments = segments.max(1);
+
+        (0..=segments)
+            .map(|i| self.point_at(i as f32 / segments as f32))
+            .collect()
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Draws the curve as `segments` straight lines (0 means
+    /// `DEFAULT_BEZIER_SEGMENTS`) with round joins. The joins overlap the
+    /// lines, so that shows with a translucent color.
+    pub fn draw_bezier_curve(
+        &mut self,
+        curve: &BezierCurve,
+        width: f32,
+        color: impl Into<Color>,
+        segments: u32,
+    ) {
+        let color: Color = color.into();
+        let segments = if segments == 0 {
+            DEFAULT_BEZIER_SEGMENTS
+        } else {
+            segments
+        };
+
+        let points = curve.points(segments);
+        for pair in points.windows(2) {
+            self.draw_line(pair[0], pair[1], width, color);
+        }
+
+        // the lines leave gaps on the outside of bends, a circle at every
+        // point between them covers those
+        for &point in &points[1..points.len() - 1] {
+            self.draw_line_cap(point, width / 2.0, color);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_cubic_bezier(
+        &mut self,
+        p0: [f32; 2],
+        p1: [f32; 2],
+        p2: [f32; 2],
+        p3: [f32; 2],
+        width: f32,
+        color: impl Into<Color>,
+        segments: u32,
+    ) {
+        self.draw_bezier_curve(
+            &BezierCurve::new(p0, p1, p2, p3),
+            width,
+            color,
+            segments,
+        );
+    }
+
+    pub fn draw_quadratic_bezier(
+        &mut self,
+        p0: [f32; 2],
+        p1: [f32; 2],
+        p2: [f32; 2],
+        width: f32,
+        color: impl Into<Color>,
+        segments: u32,
+    ) {
+        self.draw_bezier_curve(
+            &BezierCurve::quadratic(p0, p1, p2),
+            width,
+            color,
+            segments,
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{
+    atlas::SpriteHandle,
+    color::Color,
+    context::{create_render_pipeline, Context, RectangleDrawData},
+    geometry::Vec2,
+};
+
+// How a rectangle is combined with what's already drawn below it. Blending
+// is part of the pipeline, so the rectangles are split into runs with the
+// same mode like they are for materials. That's only extra draw calls when
+// more than one mode is used, and runs are still drawn in order.
+#[repr(u8)]
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, Hash, bytemuck::NoUninit,
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendMode {
+    // covers what's below based on alpha
+    #[default]
+    Normal,
+    // adds to what's below, for glows and lights
+    Additive,
+    // darkens what's below by multiplying with it, for shadows
+    Multiply,
+    // the opposite of multiply, only ever brightens what's below
+    Screen,
+    // darkens below 0.5 gray and brightens above it, gray changes nothing.
+    // Blending can't look at what's below, so this is 2 * color * below,
+    // which matches a real overlay where what's below is darker than 0.5
+    // and is too bright where it's lighter.
+    Overlay,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 5] = [
+        BlendMode::Normal,
+        BlendMode::Additive,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Overlay,
+    ];
+}
+
+impl BlendMode {
+    pub fn blend_state(self) -> wgpu::BlendState {
+        // the alpha channel is blended like usual, the surface doesn't use
+        // it anyway
+        let alpha = wgpu::BlendComponent::OVER;
+
+        match self {
+            BlendMode::Normal => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha,
+            },
+            // fs_multiply already faded the color to white by its alpha
+            BlendMode::Multiply => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Dst,
+                    dst_factor: wgpu::BlendFactor::Zero,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha,
+            },
+            // fs_screen already multiplied the color with its alpha
+            BlendMode::Screen => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrc,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha,
+            },
+            // color * below + below * color, fs_overlay faded the color to
+            // gray by its alpha
+            BlendMode::Overlay => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Dst,
+                    dst_factor: wgpu::BlendFactor::Src,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha,
+            },
+        }
+    }
+
+    pub fn fragment_entry_point(self) -> &'static str {
+        match self {
+            BlendMode::Multiply => "fs_multiply",
+            BlendMode::Screen => "fs_screen",
+            BlendMode::Overlay => "fs_overlay",
+            _ => "fs_main",
+        }
+    }
+}
+
+// The built-in shader's pipelines for every mode except Normal, which is
+// Context::render_pipeline. The other arguments are the same as for the
+// render pipeline.
+pub fn create_blend_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    sample_count: u32,
+) -> HashMap<BlendMode, wgpu::RenderPipeline> {
+    BlendMode::ALL
+        .into_iter()
+        .filter(|&mode| mode != BlendMode::Normal)
+        .map(|mode| {
+            let pipeline = create_render_pipeline(
+                device,
+                layout,
+                shader,
+                mode.fragment_entry_point(),
+                format,
+                wgpu::ColorWrites::ALL,
+                mode.blend_state(),
+                depth_stencil.clone(),
+                sample_count,
+            );
+            (mode, pipeline)
+        })
+        .collect()
+}
+
+// Splits the rectangles into runs with the same blend mode, like
+// material_runs. The ranges start at `offset`.
+pub fn blend_runs(
+    rectangles: &[RectangleDrawData],
+    offset: usize,
+) -> impl Iterator<Item = (Range<usize>, BlendMode)> + '_ {
+    let mut start = 0;
+
+    std::iter::from_fn(move || {
+        let blend_mode = rectangles.get(start)?.blend_mode;
+        let end = rectangles[start..]
+            .iter()
+            .position(|rectangle| rectangle.blend_mode != blend_mode)
+            .map_or(rectangles.len(), |length| start + length);
+
+        let run = (offset + start..offset + end, blend_mode);
+        start = end;
+        Some(run)
+    })
+}
+
+impl RectangleDrawData {
+    pub fn with_blend_mode(self, blend_mode: BlendMode) -> Self {
+        Self { blend_mode, ..self }
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Like `draw_textured_rect`, but blended with `blend_mode`. Blend modes
+    /// only apply to rectangles drawn into the window with the built-in
+    /// shader outside of stencil sections, the rest are always drawn with
+    /// `BlendMode::Normal`.
+    pub fn draw_textured_rect_blended(
+        &mut self,
+        pos: impl Into<Vec2>,
+        size: impl Into<Vec2>,
+        sprite: impl Into<SpriteHandle>,
+        blend_mode: BlendMode,
+    ) {
+        self.draw_textured_rect(pos, size, sprite);
+
+        if let Some(rectangle) = self.rectangles_to_render.last_mut() {
+            rectangle.blend_mode = blend_mode;
+        }
+    }
+
+    /// Draws a colored rectangle blended with `blend_mode`, see
+    /// `draw_textured_rect_blended`.
+    pub fn draw_rect_blended(
+        &mut self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        color: impl Into<Color>,
+        blend_mode: BlendMode,
+    ) {
+        self.rectangles_to_render.push(RectangleDrawData {
+            pos: [x, y],
+            size: [w, h],
+            color: color.into(),
+            blend_mode,
+            ..Default::default()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangles(modes: &[BlendMode]) -> Vec<RectangleDrawData> {
+        modes
+            .iter()
+            .enumerate()
+            .map(|(i, &blend_mode)| {
+                RectangleDrawData {
+                    pos: [i as f32, 0.0],
+                    ..Default::default()
+                }
+                .with_blend_mode(blend_mode)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn one_mode_is_one_run() {
+        let rectangles = rectangles(&[BlendMode::Additive; 5]);
+
+        assert_eq!(
+            blend_runs(&rectangles, 3).collect::<Vec<_>>(),
+            vec![(3..8, BlendMode::Additive)]
+        );
+        assert_eq!(blend_runs(&[], 0).count(), 0);
+    }
+
+    #[test]
+    fn mixed_modes_keep_submission_order() {
+        use BlendMode::*;
+        let rectangles =
+            rectangles(&[Normal, Normal, Additive, Normal, Multiply, Multiply]);
+
+        let runs: Vec<_> = blend_runs(&rectangles, 0).collect();
+        assert_eq!(
+            runs,
+            vec![
+                (0..2, Normal),
+                (2..3, Additive),
+                (3..4, Normal),
+                (4..6, Multiply),
+            ]
+        );
+
+        // every rectangle is in exactly one run, in the order it was drawn
+        let drawn: Vec<f32> = runs
+            .into_iter()
+            .flat_map(|(range, _)| range)
+            .map(|i| rectangles[i].pos[0])
+            .collect();
+        assert_eq!(drawn, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+}
@@ -0,0 +1,185 @@
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+
+use crate::{
+    clip::ClipRect,
+    context::Context,
+    geometry::{Rect, Vec2},
+};
+
+// how far the camera can zoom out and in
+pub const MIN_CAMERA_ZOOM: f32 = 0.01;
+pub const MAX_CAMERA_ZOOM: f32 = 100.0;
+
+// how much one notch of the scroll wheel zooms with the mouse controls.
+// Touchpads scroll in pixels, this many of them count as a notch.
+pub const CAMERA_ZOOM_PER_NOTCH: f32 = 1.1;
+pub const PIXELS_PER_SCROLL_NOTCH: f32 = 50.0;
+
+// What part of the world the window shows. Rectangles, polygons and clip
+// rects are positioned in world units, and the window shows the area
+// starting at `position`, scaled up by `zoom`. The default camera shows
+// everything where it was before there were cameras.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    // the world position at the window's top left corner
+    pub position: Vec2,
+    pub zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            zoom: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    /// Where a point in the window (in the units of `viewport_size`, like
+    /// the mouse position) is in the world.
+    pub fn screen_to_world(&self, point: impl Into<Vec2>) -> Vec2 {
+        self.position + point.into() * (1.0 / self.zoom)
+    }
+
+    pub fn world_to_screen(&self, point: impl Into<Vec2>) -> Vec2 {
+        (point.into() - self.position) * self.zoom
+    }
+
+    /// The part of the world a window of `viewport_size` shows.
+    pub fn visible_area(&self, viewport_size: impl Into<Vec2>) -> Rect {
+        Rect::from_pos_size(
+            self.position,
+            viewport_size.into() * (1.0 / self.zoom),
+        )
+    }
+
+    /// Moves the camera so the world moves by `screen_delta` in the window,
+    /// like dragging it.
+    pub fn pan(&mut self, screen_delta: impl Into<Vec2>) {
+        self.position = self.position - screen_delta.into() * (1.0 / self.zoom);
+    }
+
+    /// Zooms in by `factor` (or out below 1) so that the world point under
+    /// `screen_point` stays where it is, e.g. the one under the mouse.
+    pub fn zoom_at(&mut self, screen_point: impl Into<Vec2>, factor: f32) {
+        let screen_point = screen_point.into();
+        let world_point = self.screen_to_world(screen_point);
+
+        self.zoom =
+            (self.zoom * factor).clamp(MIN_CAMERA_ZOOM, MAX_CAMERA_ZOOM);
+
+        // solve screen_to_world(screen_point) == world_point for position
+        self.position = world_point - screen_point * (1.0 / self.zoom);
+    }
+
+    // a clip rect in world units moved to where it is in the window, for
+    // the scissor rect
+    pub fn clip_rect_to_screen(&self, clip: &ClipRect) -> ClipRect {
+        ClipRect::new(
+            self.world_to_screen(clip.pos).into(),
+            [clip.size[0] * self.zoom, clip.size[1] * self.zoom],
+        )
+    }
+}
+
+// Context::enable_mouse_camera_controls
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MouseCameraControls {
+    pub enabled: bool,
+    // the middle button is down
+    pub dragging: bool,
+    // in viewport units, None until the mouse moved over the window
+    pub cursor: Option<Vec2>,
+}
+
+impl<'a> Context<'a> {
+    pub fn camera(&self) -> Camera {
+        self.camera
+    }
+
+    /// Moves and zooms the view of the window, see `Camera`.
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = Camera {
+            zoom: camera.zoom.clamp(MIN_CAMERA_ZOOM, MAX_CAMERA_ZOOM),
+            ..camera
+        };
+
+        self.update_projection_matrix();
+        self.request_redraw();
+    }
+
+    /// Where a point in the window, like the mouse position in viewport
+    /// units, is in the world with the current camera.
+    pub fn screen_to_world(&self, point: impl Into<Vec2>) -> Vec2 {
+        self.camera.screen_to_world(point)
+    }
+
+    pub fn world_to_screen(&self, point: impl Into<Vec2>) -> Vec2 {
+        self.camera.world_to_screen(point)
+    }
+
+    /// Pans the camera while the middle mouse button is dragged and zooms
+    /// towards the mouse with the scroll wheel. Off by default.
+    pub fn enable_mouse_camera_controls(&mut self, enabled: bool) {
+        self.mouse_camera_controls.enabled = enabled;
+        self.mouse_camera_controls.dragging = false;
+    }
+
+    // called by handle_window_event with every event, before it handles
+    // them itself
+    pub fn handle_camera_event(&mut self, event: &WindowEvent) {
+        if !self.mouse_camera_controls.enabled {
+            return;
+        }
+
+        match event {
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Middle,
+                ..
+            } => {
+                self.mouse_camera_controls.dragging =
+                    *state == ElementState::Pressed;
+            }
+
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = self.window_to_viewport(*position);
+
+                let controls = &mut self.mouse_camera_controls;
+                let previous = controls.cursor.replace(cursor);
+                if let (true, Some(previous)) = (controls.dragging, previous) {
+                    let mut camera = self.camera;
+                    camera.pan(cursor - previous);
+                    self.set_camera(camera);
+                }
+            }
+
+            WindowEvent::CursorLeft { .. } => {
+                self.mouse_camera_controls.cursor = None;
+            }
+
+            WindowEvent::MouseWheel { delta, .. } => {
+                let notches = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => {
+                        position.y as f32 / PIXELS_PER_SCROLL_NOTCH
+                    }
+                };
+
+                // without a mouse position, zoom towards the middle
+                let (width, height) = self.viewport_size();
+                let anchor = self
+                    .mouse_camera_controls
+                    .cursor
+                    .unwrap_or(Vec2::new(width / 2.0, height / 2.0));
+
+                let mut camera = self.camera;
+                camera.zoom_at(anchor, CAMERA_ZOOM_PER_NOTCH.powf(notches));
+                self.set_camera(camera);
+            }
+
+            _ => {}
+        }
+    }
+}
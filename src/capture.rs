@@ -0,0 +1,128 @@
+use std::{iter, path::Path, sync::mpsc};
+
+use image::{ImageError, RgbaImage};
+
+use crate::context::Context;
+
+impl<'a> Context<'a> {
+    /// Draws the current frame again into an offscreen texture and reads it
+    /// back. This blocks until the GPU is done, so it's meant for
+    /// screenshots and tests, not for every frame.
+    pub fn capture_frame(&mut self) -> RgbaImage {
+        let (width, height) = (self.config.width, self.config.height);
+        let format = self.config.format;
+
+        // the surface texture usually can't be copied from, so the frame is
+        // drawn into a texture with the same format that can be
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC,
+            label: Some("Capture texture"),
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // rows in the buffer have to be aligned to 256 bytes, so there's
+        // padding at the end of each row unless the width is a multiple of 64
+        let unpadded_bytes_per_row = 4 * width;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor {
+                label: Some("Capture Encoder"),
+            },
+        );
+
+        self.encode_frame(&mut encoder, &view);
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+
+        self.queue.submit(iter::once(encoder.finish()));
+
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.after_submit();
+        }
+
+        // READ BACK
+        // =========
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).unwrap();
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .unwrap()
+            .expect("Could not map the capture buffer");
+
+        let mut pixels =
+            Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(padded_bytes_per_row as usize) {
+                pixels
+                    .extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        buffer.unmap();
+
+        // the surface is often BGRA, the image has to be RGBA. The sRGB
+        // encoding is kept since that's what PNGs expect.
+        if matches!(
+            format,
+            wgpu::TextureFormat::Bgra8Unorm
+                | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        RgbaImage::from_raw(width, height, pixels).unwrap()
+    }
+
+    /// Captures the current frame and saves it. The format is picked from
+    /// the extension.
+    pub fn save_screenshot(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), ImageError> {
+        self.capture_frame().save(path)
+    }
+}
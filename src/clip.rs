@@ -0,0 +1,146 @@
+use std::ops::Range;
+
+use crate::{
+    context::{Context, TextureError},
+    geometry::Vec2,
+    stencil::StencilMode,
+};
+
+// An area that rectangles are cut off at, in the same units they're
+// positioned in. See Context::push_clip_rect.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClipRect {
+    pub pos: [f32; 2],
+    pub size: [f32; 2],
+}
+
+impl ClipRect {
+    pub fn new(pos: [f32; 2], size: [f32; 2]) -> Self {
+        Self { pos, size }
+    }
+
+    /// The area covered by both, which has a size of 0 if they don't
+    /// overlap.
+    pub fn intersect(&self, other: &ClipRect) -> ClipRect {
+        let min =
+            [self.pos[0].max(other.pos[0]), self.pos[1].max(other.pos[1])];
+        let max = [
+            (self.pos[0] + self.size[0]).min(other.pos[0] + other.size[0]),
+            (self.pos[1] + self.size[1]).min(other.pos[1] + other.size[1]),
+        ];
+
+        ClipRect {
+            pos: min,
+            size: [(max[0] - min[0]).max(0.0), (max[1] - min[1]).max(0.0)],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size[0] <= 0.0 || self.size[1] <= 0.0
+    }
+
+    // the part of the surface the clip rect covers, in physical pixels, as
+    // x, y, width and height for set_scissor_rect. None if nothing of it
+    // is on the surface.
+    pub fn scissor_rect(
+        &self,
+        scale: f32,
+        surface_size: (u32, u32),
+    ) -> Option<(u32, u32, u32, u32)> {
+        let to_pixels = |value: f32, max: u32| {
+            (value * scale).round().clamp(0.0, max as f32) as u32
+        };
+
+        let x = to_pixels(self.pos[0], surface_size.0);
+        let y = to_pixels(self.pos[1], surface_size.1);
+        let right = to_pixels(self.pos[0] + self.size[0], surface_size.0);
+        let bottom = to_pixels(self.pos[1] + self.size[1], surface_size.1);
+
+        (right > x && bottom > y).then_some((x, y, right - x, bottom - y))
+    }
+}
+
+// The rectangles split up wherever the stencil mode or the clip rect
+// changes, with the mode and clip rect of each part. Both lists of changes
+// are (index of the first rectangle, new value) in order.
+pub fn draw_sections(
+    stencil_changes: &[(usize, StencilMode)],
+    clip_changes: &[(usize, Option<ClipRect>)],
+    rectangle_count: usize,
+) -> Vec<(Range<usize>, StencilMode, Option<ClipRect>)> {
+    let mut sections = vec![];
+    let mut stencil_changes = stencil_changes.iter().peekable();
+    let mut clip_changes = clip_changes.iter().peekable();
+
+    let mut start = 0;
+    let mut mode = StencilMode::None;
+    let mut clip = None;
+
+    while start < rectangle_count {
+        // everything that changes at or before start applies to this part
+        while let Some(&(_, next_mode)) =
+            stencil_changes.next_if(|(index, _)| *index <= start)
+        {
+            mode = next_mode;
+        }
+        while let Some(&(_, next_clip)) =
+            clip_changes.next_if(|(index, _)| *index <= start)
+        {
+            clip = next_clip;
+        }
+
+        let end = [
+            stencil_changes.peek().map(|(index, _)| *index),
+            clip_changes.peek().map(|(index, _)| *index),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(rectangle_count, usize::min);
+
+        sections.push((start..end, mode, clip));
+        start = end;
+    }
+
+    sections
+}
+
+impl<'a> Context<'a> {
+    /// Everything drawn until the matching `pop_clip_rect` is cut off
+    /// outside of `pos` and `size`, which are in the same units as the
+    /// rectangles. Clip rects can be nested, the inner one is cut to the
+    /// outer one. Like stencil sections, this only works when drawing to
+    /// the window (not to render targets).
+    pub fn push_clip_rect(
+        &mut self,
+        pos: impl Into<Vec2>,
+        size: impl Into<Vec2>,
+    ) {
+        let mut clip = ClipRect::new(pos.into().into(), size.into().into());
+        if let Some(outer) = self.clip_stack.last() {
+            clip = clip.intersect(outer);
+        }
+
+        self.clip_stack.push(clip);
+        self.record_clip_change(Some(clip));
+    }
+
+    /// Goes back to the clip rect from before the last `push_clip_rect`.
+    pub fn pop_clip_rect(&mut self) -> Result<(), TextureError> {
+        self.clip_stack.pop().ok_or(TextureError::NoClipRect)?;
+        self.record_clip_change(self.clip_stack.last().copied());
+
+        Ok(())
+    }
+
+    fn record_clip_change(&mut self, clip: Option<ClipRect>) {
+        if self.active_render_target.is_some() {
+            log::warn!("Render targets can't be clipped");
+            return;
+        }
+
+        // the rectangles are drawn in one go, so like stencil modes the
+        // clip rect is remembered along with the rectangle it starts at
+        self.clip_changes
+            .push((self.rectangles_to_render.len(), clip));
+    }
+}
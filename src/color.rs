@@ -0,0 +1,191 @@
+// A color as the GPU sees it: linear components from 0 to 1, which the
+// sRGB surface encodes when they're written. Arrays are converted as they
+// are, so they mean the same thing they do everywhere else. Values picked in
+// an image editor (bytes, hex codes, HSL) are sRGB encoded, the
+// constructors for those decode them so they come out looking the same.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+// sRGB encoded 0..1 to linear
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Color = Color::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Color = Color::rgb(1.0, 1.0, 1.0);
+    pub const RED: Color = Color::rgb(1.0, 0.0, 0.0);
+    pub const GREEN: Color = Color::rgb(0.0, 1.0, 0.0);
+    pub const BLUE: Color = Color::rgb(0.0, 0.0, 1.0);
+    pub const YELLOW: Color = Color::rgb(1.0, 1.0, 0.0);
+    pub const CYAN: Color = Color::rgb(0.0, 1.0, 1.0);
+    pub const MAGENTA: Color = Color::rgb(1.0, 0.0, 1.0);
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self { r, g, b, a: 1.0 }
+    }
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// An opaque color from sRGB bytes, like the ones in an image editor.
+    pub fn from_u8_rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::from_u8_rgba(r, g, b, 255)
+    }
+
+    /// Like `from_u8_rgb`. Alpha isn't sRGB encoded, it's only scaled to
+    /// 0..1.
+    pub fn from_u8_rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        let channel = |value: u8| srgb_to_linear(value as f32 / 255.0);
+
+        Self::rgba(channel(r), channel(g), channel(b), a as f32 / 255.0)
+    }
+
+    /// An opaque color from an sRGB hex code like `0xff8800`.
+    pub fn from_hex(hex: u32) -> Self {
+        let [_, r, g, b] = hex.to_be_bytes();
+        Self::from_u8_rgb(r, g, b)
+    }
+
+    /// Parses an sRGB hex code like `"#ff00aa"`, `"#f0a"` or (with alpha)
+    /// `"#ff00aa80"`. The `#` is optional.
+    pub fn hex(hex: &str) -> Option<Self> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let byte = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+        let nibble = |i: usize| {
+            u8::from_str_radix(&digits[i..i + 1], 16)
+                .ok()
+                .map(|value| value * 17)
+        };
+
+        match digits.len() {
+            3 => Some(Self::from_u8_rgb(nibble(0)?, nibble(1)?, nibble(2)?)),
+            6 => Some(Self::from_u8_rgb(byte(0)?, byte(2)?, byte(4)?)),
+            8 => {
+                Some(Self::from_u8_rgba(byte(0)?, byte(2)?, byte(4)?, byte(6)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// An opaque color from sRGB hue (in degrees), saturation and lightness
+    /// (both 0 to 1).
+    pub fn hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
+        let saturation = saturation.clamp(0.0, 1.0);
+        let lightness = lightness.clamp(0.0, 1.0);
+
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let channel = |n: f32| {
+            let k = (n + hue.rem_euclid(360.0) / 30.0) % 12.0;
+            let value = lightness
+                - chroma / 2.0 * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0);
+            srgb_to_linear(value)
+        };
+
+        Self::rgb(channel(0.0), channel(8.0), channel(4.0))
+    }
+
+    /// A color from sRGB hue (in degrees), saturation and value (both 0 to
+    /// 1), with alpha.
+    pub fn from_hsva(
+        hue: f32,
+        saturation: f32,
+        value: f32,
+        alpha: f32,
+    ) -> Self {
+        let saturation = saturation.clamp(0.0, 1.0);
+        let value = value.clamp(0.0, 1.0);
+
+        let channel = |n: f32| {
+            let k = (n + hue.rem_euclid(360.0) / 60.0) % 6.0;
+            let value =
+                value - value * saturation * k.min(4.0 - k).clamp(0.0, 1.0);
+            srgb_to_linear(value)
+        };
+
+        Self::rgba(channel(5.0), channel(3.0), channel(1.0), alpha)
+    }
+
+    /// Goes from this color (`t` = 0) to `other` (`t` = 1), alpha
+    /// included.
+    pub fn lerp(self, other: Color, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+
+        Self::rgba(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+            mix(self.a, other.a),
+        )
+    }
+
+    /// Moves the color towards black, 1 is completely black. Alpha stays
+    /// the same.
+    pub fn darken(self, amount: f32) -> Self {
+        Self {
+            a: self.a,
+            ..self.lerp(Color::BLACK, amount)
+        }
+    }
+
+    /// Moves the color towards white, 1 is completely white. Alpha stays
+    /// the same.
+    pub fn lighten(self, amount: f32) -> Self {
+        Self {
+            a: self.a,
+            ..self.lerp(Color::WHITE, amount)
+        }
+    }
+}
+
+impl From<[f32; 3]> for Color {
+    fn from([r, g, b]: [f32; 3]) -> Self {
+        Self::rgb(r, g, b)
+    }
+}
+
+impl From<[f32; 4]> for Color {
+    fn from([r, g, b, a]: [f32; 4]) -> Self {
+        Self::rgba(r, g, b, a)
+    }
+}
+
+impl From<Color> for [f32; 3] {
+    fn from(color: Color) -> Self {
+        [color.r, color.g, color.b]
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> Self {
+        [color.r, color.g, color.b, color.a]
+    }
+}
+
+impl From<Color> for wgpu::Color {
+    fn from(color: Color) -> Self {
+        Self {
+            r: color.r as f64,
+            g: color.g as f64,
+            b: color.b as f64,
+            a: color.a as f64,
+        }
+    }
+}
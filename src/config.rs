@@ -0,0 +1,99 @@
+use std::borrow::Cow;
+
+use winit::window::WindowAttributes;
+
+use crate::{
+    color::Color,
+    context::{RedrawMode, SamplerOptions},
+    present::PresentModeConfig,
+    shader::MAIN_SHADER,
+};
+
+// the shader rectangles and polygons are drawn with, see AppConfig::shader
+#[derive(Clone, Debug)]
+pub enum ShaderSource {
+    // a main shader like src/shader.wgsl, the texture, sampler and `time`
+    // declarations are generated and put in front of it
+    Wgsl(Cow<'static, str>),
+    // a complete module with the same entry points as shader.wgsl. It
+    // has to declare everything itself, matching what
+    // Context::generated_shader_source gives on the same machine, e.g. by
+    // compiling that with naga or glslang. Needs the `spirv` feature.
+    //
+    // The module goes through naga like WGSL does, so it's validated and
+    // works on every backend. SPIRV_SHADER_PASSTHROUGH isn't used, it only
+    // exists on Vulkan and hands the module to the driver unchecked.
+    #[cfg(feature = "spirv")]
+    SpirV(Vec<u32>),
+}
+
+impl Default for ShaderSource {
+    fn default() -> Self {
+        ShaderSource::Wgsl(Cow::Borrowed(MAIN_SHADER))
+    }
+}
+
+// settings used when creating the window and the Context
+#[derive(Clone, Debug, Default)]
+pub struct AppConfig {
+    // title, initial size, resizable etc. of the window. min_size and
+    // max_size below take precedence over the ones set here.
+    pub window_attributes: WindowAttributes,
+
+    // smallest and largest inner size the window can be resized to, in
+    // physical pixels
+    pub min_size: Option<(u32, u32)>,
+    pub max_size: Option<(u32, u32)>,
+
+    // sampler every texture uses unless Context::set_texture_sampler is
+    // called, e.g. SamplerOptions::nearest() for pixel art
+    pub default_sampler: SamplerOptions,
+
+    // position rectangles in logical pixels instead of physical ones, so
+    // things have the same size on HiDPI displays
+    pub logical_coordinates: bool,
+
+    // lets rectangles cover each other based on their z instead of the
+    // order they're drawn in
+    pub enable_depth_buffer: bool,
+
+    // what the window is cleared to before every frame, None keeps
+    // context::DEFAULT_CLEAR_COLOR. Color::TRANSPARENT is for transparent
+    // windows. Can be changed later with Context::set_clear_color.
+    pub clear_color: Option<Color>,
+
+    // samples per pixel for smoother edges, 2 or 4. Falls back to a lower
+    // count if the adapter doesn't support it, 0 and 1 turn multisampling
+    // off. Can be changed later with Context::set_sample_count.
+    pub sample_count: u32,
+
+    // needed for Context::begin_stencil_write and the other stencil
+    // functions
+    pub enable_stencil_buffer: bool,
+
+    // whether loading a texture with a name that's already taken replaces
+    // the name's texture or fails with TextureError::DuplicateName
+    pub replace_duplicate_texture_names: bool,
+
+    // vsync or not, see PresentModeConfig. Can be changed later with
+    // Context::set_present_mode.
+    pub present_mode: PresentModeConfig,
+
+    // whether frames are drawn all the time or only when asked for
+    pub redraw_mode: RedrawMode,
+
+    // measure how long frames take on the GPU, see
+    // Context::last_gpu_frame_ns
+    pub enable_gpu_timestamps: bool,
+
+    // longest delta_time can get, None keeps
+    // timing::DEFAULT_MAX_DELTA_TIME. Can be changed later with
+    // Context::set_max_delta_time.
+    pub max_delta_time: Option<f32>,
+
+    // the built-in shader by default. Context::new panics if a custom one
+    // doesn't compile, Context::from_adapter returns
+    // ContextError::InvalidShader. Materials are always built on top of
+    // WGSL, the built-in shader when this is SPIR-V.
+    pub shader: ShaderSource,
+}
@@ -1,69 +1,761 @@
-use std::{iter, num::NonZeroU32};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, iter,
+    num::NonZeroU32,
+    ops::Range,
+    sync::{
+        atomic::AtomicBool,
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use image::{DynamicImage, GenericImageView};
 use wgpu::{util::DeviceExt, BindGroup, BindGroupLayout, Buffer, Sampler};
-use winit::window::Window;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    window::{CursorIcon, Fullscreen, Window, WindowId},
+};
+
+use crate::{
+    animation::Animation,
+    atlas::{Atlas, SpriteHandle},
+    blend::{blend_runs, create_blend_pipelines, BlendMode},
+    camera::{Camera, MouseCameraControls},
+    clip::{draw_sections, ClipRect},
+    color::Color,
+    config::{AppConfig, ShaderSource},
+    culling::CullStats,
+    depth::{depth_stencil_state, DepthBuffer, DEPTH_FORMAT},
+    device_lost::watch_device_lost,
+    effect::{EffectId, DEFAULT_EFFECT},
+    event_bus::{AppEvent, EventBus},
+    font::BitmapFont,
+    geometry::{fit_rect, Alignment, Rect, Vec2},
+    gpu_timer::GpuTimer,
+    headless::{
+        create_headless_texture, ContextError, FrameTarget, HEADLESS_FORMAT,
+    },
+    loader::{load_image_from_path, TextureLoadResult, TextureState},
+    material::{
+        material_pipeline, material_runs, Material, MaterialHandle,
+        DEFAULT_MATERIAL,
+    },
+    msaa::{supported_sample_count, MsaaTexture},
+    multi_window::ExtraWindow,
+    polygon::{
+        create_polygon_buffer, create_polygon_pipeline, PolygonVertex,
+        INITIAL_POLYGON_CAPACITY,
+    },
+    post_process::PostProcess,
+    present::{choose_present_mode, PresentModeConfig},
+    render_target::{RenderTarget, RENDER_TARGET_FORMAT},
+    shader::{build_shader_source, create_main_shader_module},
+    stats::{stats_instant, FrameStats},
+    stencil::{StencilMode, StencilPipelines, STENCIL_REFERENCE},
+    texture_watch::{WatchedTexture, TEXTURE_WATCH_INTERVAL},
+    timing::{
+        FixedTimestep, FrameTimer, UpdateCallback, DEFAULT_MAX_DELTA_TIME,
+    },
+};
+
+#[cfg(any(
+    feature = "spirv",
+    all(feature = "hot-reload", not(target_arch = "wasm32"))
+))]
+use crate::shader::MAIN_SHADER;
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+use crate::{hot_reload::ShaderWatcher, shader::MAIN_SHADER_PATH};
+
+#[cfg(feature = "egui")]
+use crate::debug_ui::DebugUi;
+#[cfg(feature = "ttf")]
+use crate::ttf::TtfFont;
 
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
 );
 
+// how many textures fit in the binding array
+pub const MAX_TEXTURES: u32 = 1000;
+
+// how many separate texture bindings are used when binding arrays aren't
+// supported (16 is the minimum every adapter has to support)
+pub const MAX_BOUNDED_TEXTURES: u32 = 16;
+
+// how many different samplers can be in use at the same time. Samplers are
+// only created when a texture asks for a combination of settings that
+// doesn't exist yet, the unused slots are filled with the default one.
+pub const MAX_SAMPLERS: u32 = 8;
+
+// where the `time` uniform is bound when push constants aren't supported,
+// after the samplers
+pub const TIME_BINDING: u32 = 3 + MAX_SAMPLERS;
+
+// images that have to be converted before they're uploaded are converted
+// in chunks of about this many bytes
+pub const CONVERSION_CHUNK_SIZE: u32 = 1024 * 1024;
+
+// how many rectangles fit in the rectangles buffers at first, they grow
+// when more are drawn
+pub const INITIAL_RECTANGLES_CAPACITY: u64 = 10000;
+
+// after this many frames in a row where the surface was lost or outdated,
+// render() waits before the next try
+pub const SURFACE_FAILURES_BEFORE_BACKOFF: u32 = 3;
+pub const MAX_SURFACE_BACKOFF: Duration = Duration::from_secs(1);
+
+// a single f32, exposed to the fragment shader as `time`
+pub const PUSH_CONSTANTS_SIZE: u32 = 4;
+
+// what the window is cleared to unless AppConfig::clear_color or
+// Context::set_clear_color say otherwise
+pub const DEFAULT_CLEAR_COLOR: Color = Color::rgb(0.1, 0.2, 0.3);
+
 pub struct Context<'a> {
-    pub surface: wgpu::Surface<'a>,
+    pub frame_target: FrameTarget<'a>,
+    // kept by Context::new for add_window, None for the other constructors
+    pub instance: Option<wgpu::Instance>,
+    pub adapter: Option<wgpu::Adapter>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
+    // set by the device's lost callback, see recreate_device
+    pub device_lost: Arc<AtomicBool>,
     pub config: wgpu::SurfaceConfiguration,
+    // what set_present_mode picks from
+    pub present_modes: Vec<wgpu::PresentMode>,
+    pub present_mode: PresentModeConfig,
     pub size: winit::dpi::PhysicalSize<u32>,
+    pub min_size: Option<(u32, u32)>,
+    pub max_size: Option<(u32, u32)>,
+    // set by set_fullscreen and sync_fullscreen, the size constraints don't
+    // apply while it is
+    pub fullscreen: bool,
+    pub scale_factor: f64,
+    // if set, rectangles are positioned in logical pixels instead of
+    // physical ones
+    pub logical_coordinates: bool,
+    pub render_pipeline_layout: wgpu::PipelineLayout,
     pub render_pipeline: wgpu::RenderPipeline,
-    pub window: &'a Window,
+    // the render pipeline with the other blend modes
+    pub blend_pipelines: HashMap<BlendMode, wgpu::RenderPipeline>,
+    // render targets have a different format than the surface
+    pub render_target_pipeline: wgpu::RenderPipeline,
+    // the extra windows' pipelines by surface format, cleared when the
+    // shader changes
+    pub window_pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+
+    // triangulated polygons, drawn after the rectangles
+    pub polygon_pipeline: wgpu::RenderPipeline,
+    pub polygon_vertices: Vec<PolygonVertex>,
+    pub polygon_indices: Vec<u32>,
+    pub polygon_vertex_buffer: Buffer,
+    pub polygon_index_buffer: Buffer,
+    pub materials: Vec<Material>,
+    // the main shader the pipelines were built from, materials are put
+    // after it
+    pub main_shader_source: String,
+    // the SPIR-V module from AppConfig::shader, used instead of
+    // main_shader_source for everything but materials
+    pub main_shader_spirv: Option<Vec<u32>>,
+    // the snippets from register_effect, already renamed
+    pub effects: Vec<String>,
+    // the pipelines haven't been rebuilt with the latest effects yet
+    pub effects_changed: bool,
+    pub depth_buffer: Option<DepthBuffer>,
+    // what the window is cleared to at the start of every frame
+    pub clear_color: Color,
+    // samples per pixel of the main pass, 1 without multisampling. Every
+    // pipeline that draws to the window and the depth buffer use it.
+    pub sample_count: u32,
+    // what the color and depth formats support, for set_sample_count
+    pub sample_count_flags: wgpu::TextureFormatFeatureFlags,
+    pub msaa_texture: Option<MsaaTexture>,
+    pub post_process: Option<PostProcess>,
+    pub gpu_timer: Option<GpuTimer>,
+    pub depth_test: bool,
+    // only set if the stencil buffer is enabled
+    pub stencil_pipelines: Option<StencilPipelines>,
+    // index of the rectangle each stencil mode starts at, in order
+    pub stencil_changes: Vec<(usize, StencilMode)>,
+    // the clip rects that were pushed and not popped yet, each one already
+    // cut to the one before it
+    pub clip_stack: Vec<ClipRect>,
+    // index of the rectangle each clip rect starts at, like stencil_changes
+    pub clip_changes: Vec<(usize, Option<ClipRect>)>,
+
+    // None culls while the camera is in use, see Context::set_culling.
+    // The culled lists are what's actually drawn when culling is on,
+    // refilled every frame.
+    pub culling: Option<bool>,
+    pub culled_rectangles: Vec<RectangleDrawData>,
+    pub culled_stencil_changes: Vec<(usize, StencilMode)>,
+    pub culled_clip_changes: Vec<(usize, Option<ClipRect>)>,
+    pub cull_stats: CullStats,
+
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub shader_watcher: Option<ShaderWatcher>,
+
+    pub push_constants_supported: bool,
+    // what `time` is set to in the shaders, updated before every frame
+    pub push_constant_value: f32,
+    // replaces the elapsed time as `time` if set
+    pub time_override: Option<f32>,
+    pub start_instant: Instant,
+    // see elapsed_time
+    pub running_time: f32,
+    pub frame_index: u64,
+    // longest delta_time can get, see set_max_delta_time
+    pub max_delta_time: f32,
+    pub fixed_timestep: FixedTimestep<'a>,
+    // see set_frame_callback
+    pub frame_callback: Option<UpdateCallback<'a>>,
+    // `time` when push constants aren't supported
+    pub time_buffer: Buffer,
+    // None for headless contexts
+    pub window: Option<&'a Window>,
+
+    // None means redraw as fast as possible
+    pub target_frame_duration: Option<Duration>,
+    // when the frame rate limiter wants the next frame to start
+    pub next_frame_deadline: Option<Instant>,
+    pub redraw_mode: RedrawMode,
+    // the bytes of the rectangles and polygon vertices drawn in the last
+    // frame, only kept with RedrawMode::OnDemand
+    pub drawn_snapshot: Vec<u8>,
+    pub last_redraw_instant: Instant,
+    pub frame_timer: FrameTimer,
+    #[cfg(feature = "egui")]
+    pub debug_ui: Option<DebugUi<'a>>,
+    // run once in the next render(), see with_encoder
+    pub encoder_callbacks: Vec<EncoderCallback<'a>>,
+    // run before and after the main pass in every frame
+    pub pre_render_hook: Option<RenderHook<'a>>,
+    pub post_render_hook: Option<RenderHook<'a>>,
+
+    // the previous frame's stats, and the ones being counted
+    pub frame_stats: FrameStats,
+    pub current_frame_stats: FrameStats,
+    pub frame_stats_history: VecDeque<FrameStats>,
+    // frames in a row where the surface was lost or outdated
+    pub surface_failures: u32,
+    // render() doesn't try to get a surface texture again before this, see
+    // SURFACE_FAILURES_BEFORE_BACKOFF
+    pub retry_surface_at: Option<Instant>,
 
     pub projection_matrix_bytes: [u8; 64],
+    pub camera: Camera,
+    pub mouse_camera_controls: MouseCameraControls,
+    pub event_bus: EventBus<AppEvent>,
     pub projection_buffer: Buffer,
 
     pub rectangles_to_render: Vec<RectangleDrawData>,
     pub rectangles_buffer: Buffer,
+    // a copy of what's in rectangles_buffer, to see which part of it has
+    // to be uploaded again
+    pub uploaded_rectangles: Vec<u8>,
 
+    // the uniform bind group is recreated when a new sampler is added
+    pub uniform_bind_group_layout: BindGroupLayout,
     pub uniform_bind_group: BindGroup,
 
     // this bind group is recreated each time a texture is added, so it's
     // easier to also store the layout here
     pub textures_bind_group_layout: BindGroupLayout,
     pub textures_bind_group: BindGroup,
-
-    pub sampler: Sampler,
+    // set when textures were added or replaced, the bind group is rebuilt
+    // once before the next frame is drawn instead of after every change
+    pub textures_bind_group_dirty: bool,
+    pub texture_binding_mode: TextureBindingMode,
+
+    pub samplers: Vec<(SamplerOptions, Sampler)>,
+    // index into samplers for each texture
+    pub texture_settings_buffer: Buffer,
+    // what's in texture_settings_buffer, one for every texture
+    pub texture_settings: Vec<TextureSettings>,
     pub empty_texture: Texture, /* used to fill in the empty entries in
                                  * textures_bind_group */
     pub textures: Vec<Texture>,
+    pub atlases: Vec<Atlas>,
+    pub animations: Vec<Animation>,
+    pub fonts: Vec<BitmapFont>,
+    #[cfg(feature = "ttf")]
+    pub ttf_fonts: Vec<TtfFont>,
+
+    // names given to textures with load_texture_named
+    pub texture_names: HashMap<String, TextureHandle>,
+    pub replace_duplicate_texture_names: bool,
+
+    pub render_targets: Vec<RenderTarget>,
+    // index of the target between begin_target and end_target
+    pub active_render_target: Option<usize>,
+    // windows besides the main one, see add_window
+    pub extra_windows: HashMap<WindowId, ExtraWindow<'a>>,
+    // the one begin_window swapped in
+    pub active_window: Option<WindowId>,
+
+    // textures decoded on other threads come back through here
+    pub texture_load_sender: Sender<TextureLoadResult>,
+    pub texture_load_receiver: Receiver<TextureLoadResult>,
+
+    // textures that are loaded again when their file changes
+    pub watched_textures: Vec<WatchedTexture>,
+    pub frames_until_texture_watch: u32,
 }
 
 pub type TextureHandle = usize;
 
+pub type EncoderCallback<'a> =
+    Box<dyn FnOnce(&mut wgpu::CommandEncoder, &wgpu::TextureView) + 'a>;
+
+// see Context::set_pre_render_hook
+pub type RenderHook<'a> = Box<
+    dyn FnMut(
+            &wgpu::Device,
+            &wgpu::Queue,
+            &mut wgpu::CommandEncoder,
+            &wgpu::TextureView,
+        ) + 'a,
+>;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RedrawMode {
+    // draw frames all the time (limited by set_target_fps)
+    #[default]
+    Continuous,
+
+    // only draw a frame when Context::request_redraw is called or the
+    // window needs it (e.g. after a resize), so nothing runs while idle
+    OnDemand,
+}
+
+// How the channels of a texture end up in the color the shader uses. Only
+// really useful for single-channel textures, which are sampled as
+// (r, 0, 0, 1).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Swizzle {
+    // channels stay where they are
+    #[default]
+    Rgba = 0,
+
+    // (1, 1, 1, r), white with the red channel as alpha. For masks and font
+    // atlases.
+    Alpha = 1,
+
+    // (r, r, r, 1), grayscale
+    Gray = 2,
+
+    // (1, 1, 1, a), where a comes from a signed distance field in the red
+    // channel with the edge at 0.5. For SDF fonts.
+    Sdf = 3,
+}
+
+// what's written to the texture settings buffer for each texture
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextureSettings {
+    pub sampler_index: u32,
+    pub swizzle: Swizzle,
+}
+
+impl TextureSettings {
+    // the sampler index in the lowest 8 bits, the swizzle above that
+    pub fn packed(&self) -> u32 {
+        self.sampler_index | (self.swizzle as u32) << 8
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TextureFormat {
+    // colors, converted from sRGB to linear when sampled
+    #[default]
+    Rgba8UnormSrgb,
+
+    // data that's already linear, like normal maps
+    Rgba8Unorm,
+
+    // a single channel, like masks. What it looks like when it's drawn
+    // depends on its Swizzle.
+    R8Unorm,
+}
+
+impl TextureFormat {
+    pub fn to_wgpu(self) -> wgpu::TextureFormat {
+        match self {
+            TextureFormat::Rgba8UnormSrgb => {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            }
+            TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8Unorm,
+            TextureFormat::R8Unorm => wgpu::TextureFormat::R8Unorm,
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> u32 {
+        match self {
+            TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm => 4,
+            TextureFormat::R8Unorm => 1,
+        }
+    }
+
+    // R8Unorm only takes grayscale images, so a color image isn't silently
+    // reduced to its red channel. The RGBA formats take anything, grayscale
+    // is converted to gray color and a missing alpha channel becomes opaque.
+    pub fn check_image(self, data: &DynamicImage) -> Result<(), TextureError> {
+        let channels = data.color().channel_count();
+
+        let matches = match self {
+            TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm => true,
+            TextureFormat::R8Unorm => channels == 1,
+        };
+
+        if matches {
+            Ok(())
+        } else {
+            Err(TextureError::FormatMismatch {
+                format: self,
+                channels,
+            })
+        }
+    }
+
+    // the image's own data if it's already laid out the way this format
+    // expects, so it can be uploaded without a copy
+    fn pixel_bytes(self, data: &DynamicImage) -> Option<&[u8]> {
+        match (self, data) {
+            (
+                TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm,
+                DynamicImage::ImageRgba8(image),
+            ) => Some(image.as_raw()),
+            (TextureFormat::R8Unorm, DynamicImage::ImageLuma8(image)) => {
+                Some(image.as_raw())
+            }
+            _ => None,
+        }
+    }
+
+    // the image data converted to the layout this format expects
+    fn convert_pixels(self, data: &DynamicImage) -> Vec<u8> {
+        match self {
+            TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm => {
+                data.to_rgba8().into_raw()
+            }
+            TextureFormat::R8Unorm => data.to_luma8().into_raw(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SamplerOptions {
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+
+    // Repeat makes uv coordinates greater than 1 tile the texture
+    pub address_mode: wgpu::AddressMode,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            address_mode: wgpu::AddressMode::ClampToEdge,
+        }
+    }
+}
+
+impl SamplerOptions {
+    /// Nearest filtering everywhere, so pixel art stays sharp when scaled.
+    pub fn nearest() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        }
+    }
+
+    /// Linear filtering everywhere, smooth but blurry when scaled up.
+    pub fn linear() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    CouldNotOpenFile(std::io::Error),
+    CouldNotDecode(image::ImageError),
+    TooManyTextures,
+    TooManySamplers,
+    InvalidHandle,
+    AtlasFull,
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    NestedRenderTarget,
+    NoActiveRenderTarget,
+    NoClipRect,
+    FormatMismatch {
+        format: TextureFormat,
+        channels: u8,
+    },
+    DuplicateName(String),
+    InvalidManifest(String),
+    AnimationTooLarge,
+    InvalidFontMetrics(String),
+    #[cfg(feature = "svg")]
+    InvalidSvg(String),
+    #[cfg(feature = "ttf")]
+    InvalidFont(String),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::CouldNotOpenFile(e) => {
+                write!(f, "Could not open file: {}", e)
+            }
+            TextureError::CouldNotDecode(e) => {
+                write!(f, "Could not decode image data: {}", e)
+            }
+            TextureError::TooManyTextures => {
+                write!(f, "Maximum number of textures reached.")
+            }
+            TextureError::TooManySamplers => {
+                write!(f, "Maximum number of samplers reached.")
+            }
+            TextureError::InvalidHandle => {
+                write!(f, "Invalid texture handle.")
+            }
+            TextureError::AtlasFull => {
+                write!(f, "There is no room left in the atlas.")
+            }
+            TextureError::SizeMismatch { expected, actual } => write!(
+                f,
+                "Expected an image of size {}x{}, got {}x{}.",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            TextureError::NestedRenderTarget => {
+                write!(f, "Render targets can't be nested.")
+            }
+            TextureError::NoActiveRenderTarget => {
+                write!(f, "No render target is active.")
+            }
+            TextureError::NoClipRect => {
+                write!(f, "No clip rect has been pushed.")
+            }
+            TextureError::FormatMismatch { format, channels } => write!(
+                f,
+                "An image with {} channels can't be used for a {:?} texture.",
+                channels, format
+            ),
+            TextureError::DuplicateName(name) => {
+                write!(f, "There already is a texture named {}.", name)
+            }
+            TextureError::InvalidManifest(e) => {
+                write!(f, "Could not parse texture manifest: {}", e)
+            }
+            TextureError::AnimationTooLarge => {
+                write!(f, "The animation has too many or too large frames.")
+            }
+            TextureError::InvalidFontMetrics(e) => {
+                write!(f, "Could not parse font metrics: {}", e)
+            }
+            #[cfg(feature = "svg")]
+            TextureError::InvalidSvg(e) => {
+                write!(f, "Could not parse SVG: {}", e)
+            }
+            #[cfg(feature = "ttf")]
+            TextureError::InvalidFont(e) => {
+                write!(f, "Could not load font: {}", e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextureBindingMode {
+    // all textures are in one binding_array, needs TEXTURE_BINDING_ARRAY
+    // and non-uniform indexing (each rectangle can use a different texture)
+    BindingArray,
+
+    // fallback for adapters without binding arrays (integrated GPUs, WebGL):
+    // the given number of separate texture bindings
+    Bounded(u32),
+}
+
+impl TextureBindingMode {
+    pub fn max_textures(&self) -> u32 {
+        match self {
+            TextureBindingMode::BindingArray => MAX_TEXTURES,
+            TextureBindingMode::Bounded(count) => *count,
+        }
+    }
+}
+
 pub struct Texture {
     pub wgpu_texture: wgpu::Texture,
     pub wgpu_texture_view: wgpu::TextureView,
+    pub size: (u32, u32),
+    pub format: TextureFormat,
+
+    // set if the texture was loaded from a file
+    pub path: Option<String>,
+
+    pub state: TextureState,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureInfo {
+    pub size: (u32, u32),
+    pub format: TextureFormat,
+    pub path: Option<String>,
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::NoUninit)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RectangleDrawData {
+    // Top left corner and size. These are in physical pixels, or in logical
+    // pixels (physical pixels divided by the scale factor) if
+    // logical_coordinates is set. Either way (0, 0) is the top left of the
+    // window.
     pub pos: [f32; 2],
     pub size: [f32; 2],
 
-    pub color: [f32; 3],
+    // The fill color of colored rectangles. Textured rectangles are
+    // multiplied with it, so white draws the texture unchanged and a white
+    // texture (like a UI icon) can be tinted with any color. Its alpha is
+    // multiplied with the alpha of the texture.
+    pub color: Color,
+
+    // the part of the texture that's drawn, in uv coordinates (0..1 covers
+    // the whole texture)
+    pub uv_pos: [f32; 2],
+    pub uv_size: [f32; 2],
 
     // This is used to index into the array of textures. If it's -1, that means
     // it's a colored rectangle
     pub texture_index: i32,
+
+    // Only matters with the depth buffer enabled, rectangles with a higher z
+    // cover the ones with a lower z. Has to be between -1 and 1.
+    pub z: f32,
+
+    // in the same units as pos and size, 0 for sharp corners
+    pub corner_radius: f32,
+
+    // which material draws the rectangle, see Context::create_material.
+    // DEFAULT_MATERIAL is the built-in shader.
+    pub material: MaterialHandle,
+
+    // RECTANGLE_* bits
+    pub flags: u8,
+
+    // see Context::draw_textured_rect_blended
+    pub blend_mode: BlendMode,
+
+    // which effect from Context::register_effect the rectangle is drawn
+    // with, and what it gets as `in.params`. DEFAULT_EFFECT is the built-in
+    // look.
+    pub params: [f32; 4],
+    pub effect: EffectId,
+
+    // the rounded rectangle the rectangle is cut to, in the same units as
+    // pos. A mask_size of 0 means there's no mask, see Context::draw_masked.
+    pub mask_radius: f32,
+    pub mask_pos: [f32; 2],
+    pub mask_size: [f32; 2],
+
+    // the shader's struct is a multiple of 16 bytes
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub _padding: [u32; 2],
 }
 
-impl<'a> Context<'a> {
-    pub fn new(window: &'a Window) -> Context<'a> {
-        let size = window.inner_size();
+// never left out by culling, see Context::set_culling
+pub const RECTANGLE_NO_CULL: u8 = 1 << 0;
+
+impl Default for RectangleDrawData {
+    fn default() -> Self {
+        Self {
+            pos: [0.0, 0.0],
+            size: [0.0, 0.0],
+            color: Color::WHITE,
+            uv_pos: [0.0, 0.0],
+            uv_size: [1.0, 1.0],
+            texture_index: -1,
+            z: 0.0,
+            corner_radius: 0.0,
+            material: DEFAULT_MATERIAL,
+            flags: 0,
+            blend_mode: BlendMode::Normal,
+            params: [0.0; 4],
+            effect: DEFAULT_EFFECT,
+            mask_radius: 0.0,
+            mask_pos: [0.0, 0.0],
+            mask_size: [0.0, 0.0],
+            _padding: [0; 2],
+        }
+    }
+}
+
+impl RectangleDrawData {
+    pub fn with_color(self, color: impl Into<Color>) -> Self {
+        Self {
+            color: color.into(),
+            ..self
+        }
+    }
+
+    /// Only changes the alpha of `color`.
+    pub fn with_alpha(self, alpha: f32) -> Self {
+        Self {
+            color: Color {
+                a: alpha,
+                ..self.color
+            },
+            ..self
+        }
+    }
+}
+
+// A colored rectangle with rounded corners. The corners are cut out in the
+// fragment shader, so this is still a single rectangle.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RoundedRectDrawData {
+    pub pos: [f32; 2],
+    pub size: [f32; 2],
+    pub color: Color,
+    pub corner_radius: f32,
+}
+
+impl From<RoundedRectDrawData> for RectangleDrawData {
+    fn from(rect: RoundedRectDrawData) -> Self {
+        Self {
+            pos: rect.pos,
+            size: rect.size,
+            color: rect.color,
+            corner_radius: rect.corner_radius,
+            ..Default::default()
+        }
+    }
+}
 
+impl<'a> Context<'a> {
+    pub fn new(window: &'a Window, app_config: &AppConfig) -> Context<'a> {
         // BORING BOILERPLATE
         // ==================
 
@@ -83,19 +775,126 @@ impl<'a> Context<'a> {
         ))
         .unwrap();
 
-        let mut required_limits = wgpu::Limits::default();
-        required_limits.max_sampled_textures_per_shader_stage = 1000;
+        let mut context = Self::from_adapter(
+            &adapter,
+            Some(window),
+            Some(surface),
+            window.inner_size(),
+            window.scale_factor(),
+            app_config,
+        )
+        .unwrap();
+        context.instance = Some(instance);
+        context.adapter = Some(adapter);
+
+        context
+    }
+
+    // everything after picking the adapter, shared by new and
+    // build_headless. Without a surface the frames are drawn into a texture.
+    pub fn from_adapter(
+        adapter: &wgpu::Adapter,
+        window: Option<&'a Window>,
+        surface: Option<wgpu::Surface<'a>>,
+        size: PhysicalSize<u32>,
+        scale_factor: f64,
+        app_config: &AppConfig,
+    ) -> Result<Context<'a>, ContextError> {
+        // use a binding array for textures if the adapter supports it,
+        // otherwise fall back to a small number of separate bindings
+        let adapter_limits = adapter.limits();
+        let binding_array_features = wgpu::Features::TEXTURE_BINDING_ARRAY
+            | wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING;
+        let texture_binding_mode =
+            if adapter.features().contains(binding_array_features)
+                && adapter_limits.max_sampled_textures_per_shader_stage
+                    >= MAX_TEXTURES
+            {
+                TextureBindingMode::BindingArray
+            } else {
+                let count = adapter_limits
+                    .max_sampled_textures_per_shader_stage
+                    .min(MAX_BOUNDED_TEXTURES);
+
+                log::warn!(
+                "Texture binding arrays aren't supported, falling back to {} \
+                 texture bindings",
+                count
+            );
+
+                TextureBindingMode::Bounded(count)
+            };
+
+        // push constants are optional, the shader falls back to a constant
+        // when they aren't available
+        let push_constants_supported =
+            adapter.features().contains(wgpu::Features::PUSH_CONSTANTS)
+                && adapter_limits.max_push_constant_size >= PUSH_CONSTANTS_SIZE;
+
+        let (mut required_features, mut required_limits) =
+            match texture_binding_mode {
+                TextureBindingMode::BindingArray => (
+                    binding_array_features,
+                    wgpu::Limits {
+                        max_sampled_textures_per_shader_stage: MAX_TEXTURES,
+                        ..Default::default()
+                    },
+                ),
+                TextureBindingMode::Bounded(count) => (
+                    wgpu::Features::empty(),
+                    wgpu::Limits {
+                        max_sampled_textures_per_shader_stage: count,
+                        ..wgpu::Limits::downlevel_defaults()
+                    }
+                    .using_resolution(adapter_limits),
+                ),
+            };
+
+        if push_constants_supported {
+            required_features |= wgpu::Features::PUSH_CONSTANTS;
+            required_limits.max_push_constant_size = PUSH_CONSTANTS_SIZE;
+        }
+
+        let gpu_timestamps_supported = app_config.enable_gpu_timestamps
+            && adapter.features().contains(wgpu::Features::TIMESTAMP_QUERY);
+        if gpu_timestamps_supported {
+            required_features |= wgpu::Features::TIMESTAMP_QUERY;
+        } else if app_config.enable_gpu_timestamps {
+            log::warn!("Timestamp queries aren't supported");
+        }
+
+        // needed for sample counts other than 1 and 4
+        let adapter_specific_format_features = adapter
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        if adapter_specific_format_features {
+            required_features |=
+                wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+        }
+
         let (device, queue) = pollster::block_on(adapter.request_device(
             &wgpu::DeviceDescriptor {
                 label: None,
-                required_features: wgpu::Features::TEXTURE_BINDING_ARRAY,
+                required_features,
                 required_limits,
             },
             None,
         ))
-        .unwrap();
-
-        let surface_caps = surface.get_capabilities(&adapter);
+        .map_err(ContextError::RequestDevice)?;
+
+        let device_lost = Arc::new(AtomicBool::new(false));
+        watch_device_lost(&device, device_lost.clone());
+
+        let surface_caps = match &surface {
+            Some(surface) => surface.get_capabilities(adapter),
+            // there's nothing to present to, the texture works like Fifo
+            None => wgpu::SurfaceCapabilities {
+                formats: vec![HEADLESS_FORMAT],
+                present_modes: vec![wgpu::PresentMode::Fifo],
+                alpha_modes: vec![wgpu::CompositeAlphaMode::Opaque],
+                usages: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            },
+        };
 
         // srgb surface format (or fall back to the first one)
         let surface_format = surface_caps
@@ -105,12 +904,22 @@ impl<'a> Context<'a> {
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
 
+        let present_mode = choose_present_mode(
+            app_config.present_mode,
+            &surface_caps.present_modes,
+        );
+        log::info!(
+            "Present mode {:?} for {:?}",
+            present_mode,
+            app_config.present_mode
+        );
+
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode,
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
@@ -119,15 +928,12 @@ impl<'a> Context<'a> {
         // TEXTURES
         // ========
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        // only the default sampler exists at the start, the rest are
+        // created when they're first needed
+        let samplers = vec![(
+            app_config.default_sampler,
+            create_sampler(&device, app_config.default_sampler),
+        )];
 
         // start with 0 textures
         let textures: Vec<Texture> = vec![];
@@ -136,14 +942,22 @@ impl<'a> Context<'a> {
             &device,
             &queue,
             &DynamicImage::new(1, 1, image::ColorType::Rgba8),
+            TextureFormat::default(),
         );
 
         // BUFFERS
         // =======
 
+        let (projection_width, projection_height) =
+            if app_config.logical_coordinates {
+                let logical_size = size.to_logical::<f32>(scale_factor);
+                (logical_size.width, logical_size.height)
+            } else {
+                (size.width as f32, size.height as f32)
+            };
         let projection_matrix_bytes = Self::calculate_projection_matrix(
-            size.width as f32,
-            size.height as f32,
+            projection_width,
+            projection_height,
         );
 
         let projection_buffer =
@@ -154,115 +968,165 @@ impl<'a> Context<'a> {
                     | wgpu::BufferUsages::COPY_DST,
             });
 
-        let rectangles_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Rectangles Buffer"),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            size: 10000 * std::mem::size_of::<RectangleDrawData>() as u64,
+        let rectangles_buffer = create_rectangles_buffer(
+            &device,
+            "Rectangles Buffer",
+            INITIAL_RECTANGLES_CAPACITY,
+        );
+
+        // zeroed, so every texture starts out with the default sampler and
+        // no swizzle
+        let texture_settings_buffer =
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Texture Settings Buffer"),
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_DST,
+                size: MAX_TEXTURES as u64 * std::mem::size_of::<u32>() as u64,
+                mapped_at_creation: false,
+            });
+
+        // uniform buffers are padded to 16 bytes on some backends
+        let time_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Time Buffer"),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            size: 16,
             mapped_at_creation: false,
         });
 
         // UNIFORM BIND GROUP
         // ==================
 
+        let mut uniform_bind_group_layout_entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ];
+
+        // one binding per sampler slot, starting at 3
+        for i in 0..MAX_SAMPLERS {
+            uniform_bind_group_layout_entries.push(
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3 + i,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(
+                        wgpu::SamplerBindingType::Filtering,
+                    ),
+                    count: None,
+                },
+            );
+        }
+
+        uniform_bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding: TIME_BINDING,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+
         let uniform_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage {
-                                read_only: true,
-                            },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(
-                            wgpu::SamplerBindingType::Filtering,
-                        ),
-                        count: None,
-                    },
-                ],
+                entries: &uniform_bind_group_layout_entries,
                 label: Some("Uniform bind group layout"),
             });
 
-        let uniform_bind_group =
-            device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &uniform_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: projection_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: rectangles_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::Sampler(&sampler),
-                    },
-                ],
-                label: Some("Uniform bind group"),
-            });
+        let uniform_bind_group = create_uniform_bind_group(
+            &device,
+            &uniform_bind_group_layout,
+            &projection_buffer,
+            &rectangles_buffer,
+            &texture_settings_buffer,
+            &time_buffer,
+            &samplers,
+        );
 
         // TEXTURES BIND GROUP
         // ===================
 
         let textures_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float {
-                            filterable: true,
-                        },
-                    },
-                    count: NonZeroU32::new(1000),
-                }],
-                label: Some("Textures bind group layout"),
-            });
+            create_textures_bind_group_layout(&device, texture_binding_mode);
 
-        let textures_bind_group =
-            device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &textures_bind_group_layout,
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureViewArray(
-                        &[&empty_texture.wgpu_texture_view; 1000],
-                    ),
-                }],
-                label: Some("Textures bind group"),
-            });
+        let textures_bind_group = create_textures_bind_group(
+            &device,
+            &textures_bind_group_layout,
+            texture_binding_mode,
+            &[],
+            &empty_texture,
+            None,
+        );
 
         // PIPELINE
         // ========
 
-        let shader =
-            device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("Shader"),
-                source: wgpu::ShaderSource::Wgsl(
-                    include_str!("shader.wgsl").into(),
-                ),
-            });
+        // with a SPIR-V shader materials are still built on top of the
+        // built-in WGSL one
+        let (main_shader_source, main_shader_spirv) = match &app_config.shader {
+            ShaderSource::Wgsl(source) => (source.to_string(), None),
+            #[cfg(feature = "spirv")]
+            ShaderSource::SpirV(spirv) => {
+                (MAIN_SHADER.to_string(), Some(spirv.clone()))
+            }
+        };
+
+        // with hot reloading the built-in shader is read from disk so
+        // changes since the last build are picked up too
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        let (main_shader_source, shader_watcher) = (
+            if main_shader_source == MAIN_SHADER {
+                std::fs::read_to_string(MAIN_SHADER_PATH).unwrap_or_else(|e| {
+                    log::warn!("Could not read {}: {}", MAIN_SHADER_PATH, e);
+                    main_shader_source
+                })
+            } else {
+                main_shader_source
+            },
+            ShaderWatcher::new(MAIN_SHADER_PATH)
+                .map_err(|e| log::warn!("Could not watch shader: {}", e))
+                .ok(),
+        );
+
+        // a custom shader can fail to compile or not match the pipeline
+        // layout, that's reported once the pipelines are created
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let shader = create_main_shader_module(
+            &device,
+            &main_shader_source,
+            &[],
+            main_shader_spirv.as_deref(),
+            texture_binding_mode,
+            push_constants_supported,
+        );
 
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -271,92 +1135,274 @@ impl<'a> Context<'a> {
                     &uniform_bind_group_layout,
                     &textures_bind_group_layout,
                 ],
-                push_constant_ranges: &[],
-            });
-
-        let render_pipeline =
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Render Pipeline"),
-                layout: Some(&render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &[],
-                    compilation_options: Default::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: config.format,
-                        blend: Some(wgpu::BlendState {
-                            color: wgpu::BlendComponent::REPLACE,
-                            alpha: wgpu::BlendComponent::REPLACE,
-                        }),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                    compilation_options: Default::default(),
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    // no culling since I'm only drawing rectangles!!!!!
-                    cull_mode: None,
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
+                push_constant_ranges: if push_constants_supported {
+                    &[wgpu::PushConstantRange {
+                        stages: wgpu::ShaderStages::FRAGMENT,
+                        range: 0..PUSH_CONSTANTS_SIZE,
+                    }]
+                } else {
+                    &[]
                 },
-                multiview: None,
             });
 
-        Self {
-            surface,
+        let depth_test = app_config.enable_depth_buffer;
+        let depth_enabled = depth_test || app_config.enable_stencil_buffer;
+
+        // MULTISAMPLING
+        // =============
+
+        // the depth buffer is multisampled too, so its format has to
+        // support the count as well
+        let mut sample_count_flags =
+            adapter.get_texture_format_features(config.format).flags;
+        if depth_enabled {
+            sample_count_flags &=
+                adapter.get_texture_format_features(DEPTH_FORMAT).flags;
+        }
+        // 1 and 4 samples are always allowed, 2 only with this feature
+        if !adapter_specific_format_features {
+            sample_count_flags -=
+                wgpu::TextureFormatFeatureFlags::MULTISAMPLE_X2;
+        }
+
+        let sample_count =
+            supported_sample_count(sample_count_flags, app_config.sample_count);
+        if sample_count < app_config.sample_count {
+            log::warn!(
+                "{}x multisampling isn't supported, using {}x",
+                app_config.sample_count,
+                sample_count
+            );
+        }
+        let msaa_texture = (sample_count > 1).then(|| {
+            MsaaTexture::new(
+                &device,
+                config.format,
+                size.width,
+                size.height,
+                sample_count,
+            )
+        });
+
+        let depth_buffer = depth_enabled.then(|| {
+            DepthBuffer::new(&device, size.width, size.height, sample_count)
+        });
+
+        // alpha blending is needed for the anti-aliased edges of rounded
+        // rectangles and textures with transparency
+        let render_pipeline = create_render_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            "fs_main",
+            config.format,
+            wgpu::ColorWrites::ALL,
+            wgpu::BlendState::ALPHA_BLENDING,
+            depth_buffer
+                .as_ref()
+                .map(|_| depth_stencil_state(depth_test, StencilMode::None)),
+            sample_count,
+        );
+        let blend_pipelines = create_blend_pipelines(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            config.format,
+            depth_buffer
+                .as_ref()
+                .map(|_| depth_stencil_state(depth_test, StencilMode::None)),
+            sample_count,
+        );
+
+        let stencil_pipelines = app_config.enable_stencil_buffer.then(|| {
+            StencilPipelines::new(
+                &device,
+                &render_pipeline_layout,
+                &shader,
+                config.format,
+                depth_test,
+                sample_count,
+            )
+        });
+
+        // render targets don't have a depth buffer or multisampling
+        let render_target_pipeline = create_render_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            "fs_main",
+            RENDER_TARGET_FORMAT,
+            wgpu::ColorWrites::ALL,
+            wgpu::BlendState::ALPHA_BLENDING,
+            None,
+            1,
+        );
+
+        // POLYGONS
+        // ========
+
+        let polygon_pipeline = create_polygon_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            config.format,
+            depth_buffer
+                .as_ref()
+                .map(|_| depth_stencil_state(depth_test, StencilMode::None)),
+            sample_count,
+        );
+        let polygon_vertex_buffer = create_polygon_buffer(
+            &device,
+            "Polygon Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            INITIAL_POLYGON_CAPACITY
+                * std::mem::size_of::<PolygonVertex>() as u64,
+        );
+        let polygon_index_buffer = create_polygon_buffer(
+            &device,
+            "Polygon Index Buffer",
+            wgpu::BufferUsages::INDEX,
+            INITIAL_POLYGON_CAPACITY * std::mem::size_of::<u32>() as u64,
+        );
+
+        if let Some(e) = pollster::block_on(device.pop_error_scope()) {
+            return Err(ContextError::InvalidShader(e));
+        }
+
+        let (texture_load_sender, texture_load_receiver) = mpsc::channel();
+
+        let gpu_timer =
+            gpu_timestamps_supported.then(|| GpuTimer::new(&device, &queue));
+
+        let frame_target = match surface {
+            Some(surface) => FrameTarget::Surface(surface),
+            None => {
+                FrameTarget::Headless(create_headless_texture(&device, &config))
+            }
+        };
+
+        Ok(Self {
+            frame_target,
+            instance: None,
+            adapter: None,
             device,
             queue,
+            device_lost,
             size,
+            min_size: app_config.min_size,
+            max_size: app_config.max_size,
+            fullscreen: false,
+            scale_factor,
+            logical_coordinates: app_config.logical_coordinates,
             config,
+            present_modes: surface_caps.present_modes.clone(),
+            present_mode: app_config.present_mode,
+            render_pipeline_layout,
             render_pipeline,
+            blend_pipelines,
+            render_target_pipeline,
+            window_pipelines: HashMap::new(),
+            polygon_pipeline,
+            polygon_vertices: vec![],
+            polygon_indices: vec![],
+            polygon_vertex_buffer,
+            polygon_index_buffer,
+            materials: vec![],
+            main_shader_source,
+            main_shader_spirv,
+            effects: vec![],
+            effects_changed: false,
+            depth_buffer,
+            clear_color: app_config.clear_color.unwrap_or(DEFAULT_CLEAR_COLOR),
+            sample_count,
+            sample_count_flags,
+            msaa_texture,
+            post_process: None,
+            gpu_timer,
+            depth_test,
+            stencil_pipelines,
+            stencil_changes: vec![],
+            clip_stack: vec![],
+            clip_changes: vec![],
+            culling: None,
+            culled_rectangles: vec![],
+            culled_stencil_changes: vec![],
+            culled_clip_changes: vec![],
+            cull_stats: CullStats::default(),
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            shader_watcher,
+            push_constants_supported,
+            push_constant_value: 0.0,
+            time_override: None,
+            start_instant: Instant::now(),
+            running_time: 0.0,
+            frame_index: 0,
+            max_delta_time: app_config
+                .max_delta_time
+                .unwrap_or(DEFAULT_MAX_DELTA_TIME),
+            fixed_timestep: FixedTimestep::new(),
+            frame_callback: None,
+            time_buffer,
             window,
+            target_frame_duration: None,
+            next_frame_deadline: None,
+            redraw_mode: app_config.redraw_mode,
+            drawn_snapshot: vec![],
+            last_redraw_instant: Instant::now(),
+            frame_timer: FrameTimer::new(),
+            #[cfg(feature = "egui")]
+            debug_ui: None,
+            encoder_callbacks: vec![],
+            pre_render_hook: None,
+            post_render_hook: None,
+            frame_stats: FrameStats::default(),
+            current_frame_stats: FrameStats::default(),
+            frame_stats_history: VecDeque::new(),
+            surface_failures: 0,
+            retry_surface_at: None,
             projection_matrix_bytes,
+            camera: Camera::default(),
+            mouse_camera_controls: MouseCameraControls::default(),
+            event_bus: EventBus::new(),
             projection_buffer,
-            rectangles_to_render: vec![
-                RectangleDrawData {
-                    pos: [10.0, 10.0],
-                    size: [100.0, 100.0],
-                    color: [1.0, 1.0, 1.0],
-                    texture_index: -1,
-                },
-                RectangleDrawData {
-                    pos: [120.0, 20.0],
-                    size: [100.0, 100.0],
-                    color: [0.0, 0.0, 0.0],
-                    texture_index: 0,
-                },
-                RectangleDrawData {
-                    pos: [230.0, 50.0],
-                    size: [100.0, 150.0],
-                    color: [0.4, 0.3, 0.3],
-                    texture_index: -1,
-                },
-            ],
+            rectangles_to_render: vec![],
             rectangles_buffer,
+            uploaded_rectangles: vec![],
+            uniform_bind_group_layout,
             uniform_bind_group,
             textures_bind_group_layout,
             textures_bind_group,
-            sampler,
+            textures_bind_group_dirty: false,
+            texture_binding_mode,
+            samplers,
+            texture_settings_buffer,
+            texture_settings: vec![],
             empty_texture,
             textures,
-        }
+            atlases: vec![],
+            animations: vec![],
+            fonts: vec![],
+            #[cfg(feature = "ttf")]
+            ttf_fonts: vec![],
+            texture_names: HashMap::new(),
+            replace_duplicate_texture_names: app_config
+                .replace_duplicate_texture_names,
+            render_targets: vec![],
+            active_render_target: None,
+            extra_windows: HashMap::new(),
+            active_window: None,
+            texture_load_sender,
+            texture_load_receiver,
+            watched_textures: vec![],
+            frames_until_texture_watch: 0,
+        })
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        // the window manager doesn't always respect the size constraints,
+        // so never configure the surface with a size outside of them
+        let new_size = self.clamp_to_size_constraints(new_size);
+
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
 
@@ -366,31 +1412,779 @@ impl<'a> Context<'a> {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
 
-            self.surface.configure(&self.device, &self.config);
+            self.reconfigure_surface();
+
+            if self.depth_buffer.is_some() {
+                self.depth_buffer = Some(DepthBuffer::new(
+                    &self.device,
+                    new_size.width,
+                    new_size.height,
+                    self.sample_count,
+                ));
+            }
+
+            if self.msaa_texture.is_some() {
+                self.msaa_texture = Some(MsaaTexture::new(
+                    &self.device,
+                    self.config.format,
+                    new_size.width,
+                    new_size.height,
+                    self.sample_count,
+                ));
+            }
+
+            if let Some(post_process) = &mut self.post_process {
+                post_process.resize(&self.device, &self.config);
+            }
 
             // UPDATE PROJECTION MATRIX
             // ========================
 
-            self.projection_matrix_bytes = Self::calculate_projection_matrix(
-                new_size.width as f32,
-                new_size.height as f32,
-            );
+            self.update_projection_matrix();
+        }
+    }
 
-            self.queue.write_buffer(
-                &self.projection_buffer,
-                0,
-                &self.projection_matrix_bytes,
-            );
+    /// Configures the surface again with the current size and settings.
+    /// This is what fixes `SurfaceError::Lost` and `SurfaceError::Outdated`,
+    /// nothing else has to change since the size didn't. Headless contexts
+    /// get a new texture.
+    pub fn reconfigure_surface(&mut self) {
+        match &mut self.frame_target {
+            FrameTarget::Surface(surface) => {
+                surface.configure(&self.device, &self.config)
+            }
+            FrameTarget::Headless(texture) => {
+                *texture = create_headless_texture(&self.device, &self.config)
+            }
         }
     }
 
-    pub fn update(&mut self) {}
+    /// Size of the window in the units rectangles are positioned in, which
+    /// depends on `set_logical_coordinates`.
+    pub fn viewport_size(&self) -> (f32, f32) {
+        if self.logical_coordinates {
+            self.logical_size()
+        } else {
+            (self.size.width as f32, self.size.height as f32)
+        }
+    }
+
+    /// A position from a window event, like `CursorMoved`, in the units
+    /// rectangles are positioned in (before the camera).
+    pub fn window_to_viewport(&self, position: PhysicalPosition<f64>) -> Vec2 {
+        let scale = if self.logical_coordinates {
+            self.scale_factor
+        } else {
+            1.0
+        };
+
+        Vec2::new((position.x / scale) as f32, (position.y / scale) as f32)
+    }
+
+    // the projection covers the window in either physical or logical
+    // pixels, depending on logical_coordinates. Called whenever the viewport
+    // or the camera changes.
+    pub fn update_projection_matrix(&mut self) {
+        let area = self.camera.visible_area(self.viewport_size());
+
+        self.projection_matrix_bytes =
+            Self::calculate_view_projection_matrix(area);
+
+        self.queue.write_buffer(
+            &self.projection_buffer,
+            0,
+            &self.projection_matrix_bytes,
+        );
+        self.current_frame_stats
+            .record_buffer_write(self.projection_matrix_bytes.len());
+    }
+
+    /// Switches between positioning rectangles in logical pixels (the same
+    /// size on every display) and physical pixels (one unit per pixel on the
+    /// screen).
+    pub fn set_logical_coordinates(&mut self, logical_coordinates: bool) {
+        self.logical_coordinates = logical_coordinates;
+
+        self.update_projection_matrix();
+    }
+
+    /// Limits how often the window is redrawn, with or without vsync, e.g.
+    /// to save power on a 240 Hz monitor. Input events are still handled
+    /// while waiting for the next frame, only the last
+    /// `LIMITER_WAKE_MARGIN` of the wait blocks to hit it within a fraction
+    /// of a millisecond. Has no effect with `RedrawMode::OnDemand`, frames
+    /// are only drawn when asked for there. `FrameStats::fps` shows what
+    /// was reached.
+    pub fn set_target_fps(&mut self, fps: u32) {
+        if fps == 0 {
+            self.clear_target_fps();
+            return;
+        }
+
+        self.target_frame_duration =
+            Some(Duration::from_secs_f64(1.0 / fps as f64));
+        self.next_frame_deadline = None;
+    }
+
+    /// Goes back to redrawing as fast as possible.
+    pub fn clear_target_fps(&mut self) {
+        self.target_frame_duration = None;
+        self.next_frame_deadline = None;
+        self.request_redraw();
+    }
+
+    pub fn set_redraw_mode(&mut self, mode: RedrawMode) {
+        self.redraw_mode = mode;
+
+        // get the continuous frames going again
+        self.request_redraw();
+    }
+
+    /// Asks for a new frame. With `RedrawMode::OnDemand` that already
+    /// happens when the rectangles or polygons change between events, call
+    /// it for anything else that changes what's drawn.
+    pub fn request_redraw(&self) {
+        if let Some(window) = self.window {
+            window.request_redraw();
+        }
+    }
+
+    // the rectangles and polygons as bytes, to compare them with
+    // drawn_snapshot
+    fn draw_list_bytes(&self) -> (&[u8], &[u8]) {
+        (
+            bytemuck::cast_slice(&self.rectangles_to_render),
+            bytemuck::cast_slice(&self.polygon_vertices),
+        )
+    }
+
+    /// Whether something different from the last frame would be drawn.
+    /// Only tracked with `RedrawMode::OnDemand`, always true otherwise.
+    pub fn draw_list_changed(&self) -> bool {
+        if self.redraw_mode != RedrawMode::OnDemand {
+            return true;
+        }
+
+        let (rectangles, polygons) = self.draw_list_bytes();
+        let (drawn_rectangles, drawn_polygons) = self
+            .drawn_snapshot
+            .split_at(rectangles.len().min(self.drawn_snapshot.len()));
+
+        rectangles != drawn_rectangles || polygons != drawn_polygons
+    }
+
+    // called after every event with RedrawMode::OnDemand, so drawing
+    // something new from an event handler shows up without asking for it.
+    // A lost device is only recreated before a frame, so that asks for one
+    // too. wgpu notices the loss during the calls the frame makes, so it's
+    // seen here right after it.
+    pub fn request_redraw_if_changed(&self) {
+        if self.redraw_mode == RedrawMode::OnDemand
+            && (self.draw_list_changed() || self.device_lost())
+        {
+            self.request_redraw();
+        }
+    }
+
+    fn snapshot_draw_list(&mut self) {
+        let mut snapshot = std::mem::take(&mut self.drawn_snapshot);
+        let (rectangles, polygons) = self.draw_list_bytes();
+
+        snapshot.clear();
+        snapshot.extend_from_slice(rectangles);
+        snapshot.extend_from_slice(polygons);
+        self.drawn_snapshot = snapshot;
+    }
+
+    /// Ratio between physical and logical pixels of the monitor the window
+    /// is on.
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Size of the window in logical pixels.
+    pub fn logical_size(&self) -> (f32, f32) {
+        let logical_size = self.size.to_logical::<f32>(self.scale_factor);
+
+        (logical_size.width, logical_size.height)
+    }
+
+    /// Sets the smallest and largest inner size of the window in physical
+    /// pixels. `None` removes the constraint.
+    pub fn set_size_constraints(
+        &mut self,
+        min: Option<(u32, u32)>,
+        max: Option<(u32, u32)>,
+    ) {
+        self.min_size = min;
+        self.max_size = max;
+
+        // applied when leaving fullscreen
+        if self.fullscreen {
+            return;
+        }
+
+        if let Some(window) = self.window {
+            window.set_min_inner_size(
+                min.map(|(width, height)| PhysicalSize::new(width, height)),
+            );
+            window.set_max_inner_size(
+                max.map(|(width, height)| PhysicalSize::new(width, height)),
+            );
+        }
+    }
+
+    pub fn clamp_to_size_constraints(
+        &self,
+        size: PhysicalSize<u32>,
+    ) -> PhysicalSize<u32> {
+        // the surface has to cover the whole monitor
+        if self.fullscreen {
+            return size;
+        }
+
+        clamp_size(size, self.min_size, self.max_size)
+    }
+
+    /// Switches the window to borderless fullscreen on the monitor it's on,
+    /// or back to a normal window. The size constraints are lifted while
+    /// it's fullscreen. Bind it to a key yourself, e.g. F11 with
+    /// `!context.is_fullscreen()`.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        let Some(window) = self.window else {
+            return;
+        };
+        if self.fullscreen == fullscreen {
+            return;
+        }
+        self.fullscreen = fullscreen;
+
+        // some platforms don't let a window with a maximum size cover the
+        // monitor
+        if fullscreen {
+            window.set_min_inner_size(None::<PhysicalSize<u32>>);
+            window.set_max_inner_size(None::<PhysicalSize<u32>>);
+        }
+
+        window.set_fullscreen(
+            fullscreen
+                .then(|| Fullscreen::Borderless(window.current_monitor())),
+        );
+
+        if !fullscreen {
+            self.set_size_constraints(self.min_size, self.max_size);
+        }
+
+        // Resized comes later on some platforms and not at all on others
+        // when the size happens to stay the same, so the surface and the
+        // projection are updated right away. A Resized with the final size
+        // updates them again.
+        self.resize(window.inner_size());
+        self.request_redraw();
+    }
+
+    /// Also true when the user or the OS made the window fullscreen, e.g.
+    /// with the green button on macOS.
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    // The OS can take the window in and out of fullscreen without
+    // set_fullscreen, called on every Resized to catch that
+    pub fn sync_fullscreen(&mut self) {
+        let Some(window) = self.window else {
+            return;
+        };
+        let fullscreen = window.fullscreen().is_some();
+        if self.fullscreen == fullscreen {
+            return;
+        }
+        self.fullscreen = fullscreen;
+
+        if fullscreen {
+            window.set_min_inner_size(None::<PhysicalSize<u32>>);
+            window.set_max_inner_size(None::<PhysicalSize<u32>>);
+        } else {
+            self.set_size_constraints(self.min_size, self.max_size);
+        }
+    }
+
+    /// Changes the cursor shown while hovering the window.
+    ///
+    /// Any `CursorIcon` can be passed (`Default`, `Pointer`, `Text`, `Grab`,
+    /// `Move`, the resize arrows etc.), but not every platform has a native
+    /// cursor for each of them: on Windows and macOS the less common icons
+    /// fall back to the closest system cursor, on X11/Wayland it depends on
+    /// the cursor theme.
+    pub fn set_cursor_icon(&mut self, icon: CursorIcon) {
+        if let Some(window) = self.window {
+            window.set_cursor(icon);
+        }
+    }
+
+    /// Hides or shows the cursor while it's over the window. On some
+    /// platforms (X11, Windows) this only applies once the cursor moves
+    /// inside the window.
+    pub fn hide_cursor(&mut self, hidden: bool) {
+        if let Some(window) = self.window {
+            window.set_cursor_visible(!hidden);
+        }
+    }
+
+    /// Advances everything that's animated by `dt` seconds, usually
+    /// `delta_time()`, and picks up finished texture loads and changed
+    /// files. Called before every frame by the event loop.
+    pub fn update(&mut self, dt: f32) {
+        self.running_time += dt.max(0.0);
+        self.frame_index += 1;
+
+        self.finish_texture_loads();
+
+        // checking files every frame would be a waste
+        if self.frames_until_texture_watch == 0 {
+            self.poll_texture_reloads();
+            self.frames_until_texture_watch = TEXTURE_WATCH_INTERVAL;
+        }
+        self.frames_until_texture_watch -= 1;
+
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        self.reload_shader_if_changed();
+    }
+
+    /// Compiles `main_shader` (the contents of shader.wgsl) and swaps it in
+    /// for the current pipeline. Bind groups are kept as they are. If the
+    /// shader doesn't compile the error is logged and the old pipeline stays.
+    /// With a SPIR-V shader from `AppConfig::shader` only the materials
+    /// pick up `main_shader`.
+    pub fn rebuild_render_pipeline(&mut self, main_shader: &str) {
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let shader = create_main_shader_module(
+            &self.device,
+            main_shader,
+            &self.effects,
+            self.main_shader_spirv.as_deref(),
+            self.texture_binding_mode,
+            self.push_constants_supported,
+        );
+
+        let render_pipeline = create_render_pipeline(
+            &self.device,
+            &self.render_pipeline_layout,
+            &shader,
+            "fs_main",
+            self.config.format,
+            wgpu::ColorWrites::ALL,
+            wgpu::BlendState::ALPHA_BLENDING,
+            self.depth_buffer.as_ref().map(|_| {
+                depth_stencil_state(self.depth_test, StencilMode::None)
+            }),
+            self.sample_count,
+        );
+        let blend_pipelines = create_blend_pipelines(
+            &self.device,
+            &self.render_pipeline_layout,
+            &shader,
+            self.config.format,
+            self.depth_buffer.as_ref().map(|_| {
+                depth_stencil_state(self.depth_test, StencilMode::None)
+            }),
+            self.sample_count,
+        );
+        let polygon_pipeline = create_polygon_pipeline(
+            &self.device,
+            &self.render_pipeline_layout,
+            &shader,
+            self.config.format,
+            self.depth_buffer.as_ref().map(|_| {
+                depth_stencil_state(self.depth_test, StencilMode::None)
+            }),
+            self.sample_count,
+        );
+        let stencil_pipelines = self.stencil_pipelines.as_ref().map(|_| {
+            StencilPipelines::new(
+                &self.device,
+                &self.render_pipeline_layout,
+                &shader,
+                self.config.format,
+                self.depth_test,
+                self.sample_count,
+            )
+        });
+        let render_target_pipeline = create_render_pipeline(
+            &self.device,
+            &self.render_pipeline_layout,
+            &shader,
+            "fs_main",
+            RENDER_TARGET_FORMAT,
+            wgpu::ColorWrites::ALL,
+            wgpu::BlendState::ALPHA_BLENDING,
+            None,
+            1,
+        );
+
+        // the materials are compiled together with the main shader, so
+        // they have to be rebuilt too
+        let materials: Vec<Material> = self
+            .materials
+            .iter()
+            .map(|material| self.build_material(main_shader, &material.wgsl))
+            .collect();
+
+        match pollster::block_on(self.device.pop_error_scope()) {
+            Some(e) => log::error!("Could not rebuild render pipeline: {}", e),
+            None => {
+                self.render_pipeline = render_pipeline;
+                self.blend_pipelines = blend_pipelines;
+                self.polygon_pipeline = polygon_pipeline;
+                self.stencil_pipelines = stencil_pipelines;
+                self.render_target_pipeline = render_target_pipeline;
+                self.window_pipelines.clear();
+                self.materials = materials;
+                self.main_shader_source = main_shader.to_string();
+            }
+        }
+    }
+
+    /// The complete WGSL the built-in pipelines are compiled from, with the
+    /// texture, sampler and `time` declarations this adapter needs. Compile
+    /// this (or a changed version of it) to get a SPIR-V module that fits
+    /// `AppConfig::shader`.
+    pub fn generated_shader_source(&self) -> String {
+        build_shader_source(
+            &self.main_shader_source,
+            &self.effects,
+            self.texture_binding_mode,
+            self.push_constants_supported,
+        )
+    }
+
+    /// `time` in the shaders is the number of seconds the context has been
+    /// running (see `elapsed_time`). This replaces it with a fixed value
+    /// until `clear_shader_time` is called, e.g. to pause effects. It's a
+    /// push constant where push constants are supported and a uniform
+    /// everywhere else, shaders use it the same way either way.
+    pub fn set_shader_time(&mut self, value: f32) {
+        self.time_override = Some(value);
+    }
+
+    /// Goes back to using the elapsed time as `time`.
+    pub fn clear_shader_time(&mut self) {
+        self.time_override = None;
+    }
+
+    #[deprecated(
+        note = "`time` isn't always a push constant, use set_shader_time"
+    )]
+    pub fn set_push_constant_f32(&mut self, value: f32) {
+        self.set_shader_time(value);
+    }
+
+    #[deprecated(note = "use clear_shader_time")]
+    pub fn clear_push_constant_f32(&mut self) {
+        self.clear_shader_time();
+    }
+
+    /// Sets what the window is cleared to before anything is drawn,
+    /// starting with the next frame. `Color::TRANSPARENT` leaves the
+    /// window see-through where nothing is drawn, if the window itself is
+    /// transparent.
+    pub fn set_clear_color(&mut self, color: impl Into<Color>) {
+        self.clear_color = color.into();
+    }
+
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
+
+    /// Runs `f` during the next `render`, right after the rectangles were
+    /// drawn and before anything is submitted, so custom wgpu passes can be
+    /// added to the same frame. `f` gets the frame's encoder and the view
+    /// of the surface texture.
+    pub fn with_encoder(
+        &mut self,
+        f: impl FnOnce(&mut wgpu::CommandEncoder, &wgpu::TextureView) + 'a,
+    ) {
+        self.encoder_callbacks.push(Box::new(f));
+    }
+
+    /// Runs `hook` in every frame before the rectangles are drawn, with the
+    /// frame's encoder and the view they're drawn into (the surface's, or
+    /// the post process texture, which has the same format). The view is
+    /// already cleared to the clear color, and the rectangles are drawn on
+    /// top of what the hook drew.
+    /// Hooks get the encoder between passes, so they can't change the
+    /// main pass. With multisampling the rectangles are drawn into a
+    /// separate texture that doesn't have what the hook drew, so the hook
+    /// isn't run then and a warning is logged.
+    pub fn set_pre_render_hook(
+        &mut self,
+        hook: impl FnMut(
+                &wgpu::Device,
+                &wgpu::Queue,
+                &mut wgpu::CommandEncoder,
+                &wgpu::TextureView,
+            ) + 'a,
+    ) {
+        if self.sample_count > 1 {
+            log::warn!("The pre render hook isn't run with multisampling");
+        }
+        self.pre_render_hook = Some(Box::new(hook));
+    }
+
+    pub fn clear_pre_render_hook(&mut self) {
+        self.pre_render_hook = None;
+    }
+
+    /// Like `set_pre_render_hook`, but runs after the rectangles (and the
+    /// post process effect) with the surface's view, so it draws on top.
+    pub fn set_post_render_hook(
+        &mut self,
+        hook: impl FnMut(
+                &wgpu::Device,
+                &wgpu::Queue,
+                &mut wgpu::CommandEncoder,
+                &wgpu::TextureView,
+            ) + 'a,
+    ) {
+        self.post_render_hook = Some(Box::new(hook));
+    }
+
+    pub fn clear_post_render_hook(&mut self) {
+        self.post_render_hook = None;
+    }
+
+    /// For creating your own pipelines and buffers, e.g. in render hooks.
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// The format of the window's surface (or of the headless texture),
+    /// which pipelines drawing into the render hooks' views need.
+    pub fn surface_format(&self) -> wgpu::TextureFormat {
+        self.config.format
+    }
+
+    /// Seconds the context has been running, the sum of the `dt` passed to
+    /// every `update`. Unlike `time_since_start` it doesn't jump after a
+    /// stall. This is what `time` is set to in the shaders before every
+    /// frame.
+    pub fn elapsed_time(&self) -> f32 {
+        self.running_time
+    }
+
+    /// Wall clock seconds since the context was created.
+    pub fn time_since_start(&self) -> f32 {
+        self.start_instant.elapsed().as_secs_f32()
+    }
+
+    /// How many times `update` was called, which is the number of the
+    /// current frame starting at 1.
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    /// Replaces everything that's going to be drawn with `rectangles`, for
+    /// when the whole frame is rebuilt at once. Stencil sections and clip
+    /// rects are dropped as well. While a render target is active this
+    /// replaces the target's rectangles instead.
+    pub fn set_rectangles(&mut self, rectangles: Vec<RectangleDrawData>) {
+        self.rectangles_to_render = rectangles;
+
+        // render targets don't have stencil sections or clip rects
+        if self.active_render_target.is_none() {
+            self.stencil_changes.clear();
+            self.clip_changes.clear();
+        }
+    }
+
+    /// The rectangles that are going to be drawn, to change them in place.
+    /// The buffer on the GPU grows as needed, so there's no limit on how
+    /// many can be added. Removing rectangles before a stencil section or
+    /// clip rect moves where it starts.
+    pub fn rectangles_mut(&mut self) -> &mut Vec<RectangleDrawData> {
+        &mut self.rectangles_to_render
+    }
+
+    /// Draws the whole texture, or just a part of it if a sprite from an
+    /// atlas is passed.
+    pub fn draw_textured_rect(
+        &mut self,
+        pos: impl Into<Vec2>,
+        size: impl Into<Vec2>,
+        sprite: impl Into<SpriteHandle>,
+    ) {
+        let sprite = sprite.into();
+
+        self.rectangles_to_render.push(RectangleDrawData {
+            pos: pos.into().into(),
+            size: size.into().into(),
+            color: Color::WHITE,
+            texture_index: sprite.texture as i32,
+            uv_pos: sprite.uv_pos,
+            uv_size: sprite.uv_size,
+            ..Default::default()
+        });
+    }
+
+    /// Draws a colored rectangle with anti-aliased rounded corners. The
+    /// radius is clamped to half of the shorter side, so that gives a
+    /// circle or a pill shape.
+    pub fn draw_rounded_rect(
+        &mut self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        radius: f32,
+        color: impl Into<Color>,
+    ) {
+        self.rectangles_to_render.push(RectangleDrawData::from(
+            RoundedRectDrawData {
+                pos: [x, y],
+                size: [w, h],
+                color: color.into(),
+                corner_radius: radius,
+            },
+        ));
+    }
+
+    /// Draws the whole texture at its native size.
+    pub fn draw_texture_at(
+        &mut self,
+        handle: TextureHandle,
+        pos: impl Into<Vec2>,
+    ) -> Result<(), TextureError> {
+        let (width, height) = self
+            .texture_size(handle)
+            .ok_or(TextureError::InvalidHandle)?;
+
+        self.draw_textured_rect(pos, [width as f32, height as f32], handle);
+
+        Ok(())
+    }
+
+    /// Draws the whole texture at its native size multiplied by `scale`.
+    pub fn draw_sprite(
+        &mut self,
+        handle: TextureHandle,
+        pos: impl Into<Vec2>,
+        scale: f32,
+    ) -> Result<(), TextureError> {
+        let (width, height) = self
+            .texture_size(handle)
+            .ok_or(TextureError::InvalidHandle)?;
+
+        self.draw_textured_rect(
+            pos,
+            [width as f32 * scale, height as f32 * scale],
+            handle,
+        );
+
+        Ok(())
+    }
+
+    /// Draws the whole texture as large as it fits into the area without
+    /// stretching it, placed inside the area according to `align`. Returns
+    /// where it ended up.
+    pub fn draw_image_scaled_to_fit(
+        &mut self,
+        x: f32,
+        y: f32,
+        max_width: f32,
+        max_height: f32,
+        handle: TextureHandle,
+        align: Alignment,
+    ) -> Result<Rect, TextureError> {
+        let (width, height) = self
+            .texture_size(handle)
+            .ok_or(TextureError::InvalidHandle)?;
+
+        let rect = fit_rect(
+            [width as f32, height as f32],
+            Rect::new(x, y, max_width, max_height),
+            align,
+        );
+        self.draw_textured_rect(rect.pos(), rect.size(), handle);
+
+        Ok(rect)
+    }
+
+    /// When `render` tries to get a surface texture again, if it's backing
+    /// off after the surface kept failing. Frames before then aren't drawn.
+    pub fn surface_retry_at(&self) -> Option<Instant> {
+        self.retry_surface_at.filter(|&at| at > Instant::now())
+    }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let render_start = stats_instant();
+
+        self.rebuild_effects_if_changed();
+
+        if self.redraw_mode == RedrawMode::OnDemand {
+            self.snapshot_draw_list();
+        }
+
+        let surface = match &self.frame_target {
+            FrameTarget::Surface(surface) => Some(surface),
+            FrameTarget::Headless(_) => None,
+        };
+
+        // still backing off, nothing is drawn this frame
+        if surface.is_some() && self.surface_retry_at().is_some() {
+            return Ok(());
+        }
+
+        let output = match surface.map(|surface| surface.get_current_texture())
+        {
+            None => None,
+            Some(Ok(output)) => {
+                self.surface_failures = 0;
+                self.retry_surface_at = None;
+                Some(output)
+            }
+            // a slow frame, the surface itself is fine
+            Some(Err(e @ wgpu::SurfaceError::Timeout)) => return Err(e),
+            Some(Err(e)) => {
+                self.surface_failures += 1;
+
+                // some drivers keep failing for a while (e.g. during a
+                // resize), so don't spin on it. Waits 10ms, 20ms, 40ms...
+                if self.surface_failures >= SURFACE_FAILURES_BEFORE_BACKOFF {
+                    let exponent = (self.surface_failures
+                        - SURFACE_FAILURES_BEFORE_BACKOFF)
+                        .min(10);
+                    let delay = (Duration::from_millis(10)
+                        * 2u32.pow(exponent))
+                    .min(MAX_SURFACE_BACKOFF);
+
+                    log::warn!(
+                        "Could not get the surface texture {} times in a row \
+                         ({}), retrying in {:?}",
+                        self.surface_failures,
+                        e,
+                        delay
+                    );
+
+                    self.retry_surface_at = Some(Instant::now() + delay);
+                }
+
+                return Err(e);
+            }
+        };
+        self.current_frame_stats.record_present_wait(render_start);
+
+        // headless contexts draw into their texture instead
+        let texture = match (&output, &self.frame_target) {
+            (Some(output), _) => &output.texture,
+            (None, FrameTarget::Headless(texture)) => texture,
+            (None, FrameTarget::Surface(_)) => unreachable!(),
+        };
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
         let mut encoder = self.device.create_command_encoder(
             &wgpu::CommandEncoderDescriptor {
@@ -398,117 +2192,717 @@ impl<'a> Context<'a> {
             },
         );
 
-        self.queue.write_buffer(
-            &self.rectangles_buffer,
-            0,
-            bytemuck::cast_slice(self.rectangles_to_render.as_slice()),
+        self.poll_gpu_timer();
+
+        self.encode_frame(&mut encoder, &view);
+
+        // the debug UI still goes on top of whatever these draw
+        for encoder_callback in std::mem::take(&mut self.encoder_callbacks) {
+            encoder_callback(&mut encoder, &view);
+        }
+
+        #[cfg(feature = "egui")]
+        let callback_buffers = self.encode_debug_ui(&mut encoder, &view);
+        #[cfg(not(feature = "egui"))]
+        let callback_buffers = iter::empty();
+
+        self.queue.submit(
+            callback_buffers
+                .into_iter()
+                .chain(iter::once(encoder.finish())),
         );
 
-        {
-            let mut render_pass =
-                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("Render Pass"),
-                    color_attachments: &[Some(
-                        wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(wgpu::Color {
-                                    r: 0.1,
-                                    g: 0.2,
-                                    b: 0.3,
-                                    a: 1.0,
-                                }),
-                                store: wgpu::StoreOp::Store,
-                            },
-                        },
-                    )],
-                    depth_stencil_attachment: None,
-                    occlusion_query_set: None,
-                    timestamp_writes: None,
-                });
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.textures_bind_group, &[]);
-
-            let vertex_count = 6 * self.rectangles_to_render.len() as u32;
-            render_pass.draw(0..vertex_count, 0..1);
-        }
-
-        self.queue.submit(iter::once(encoder.finish()));
-        output.present();
+        if let Some(output) = output {
+            let present_start = stats_instant();
+            output.present();
+            self.current_frame_stats.record_present_wait(present_start);
+        }
+
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.after_submit();
+        }
+
+        self.current_frame_stats.record_cpu_time(render_start);
+        self.finish_frame_stats();
 
         Ok(())
     }
 
+    // records everything needed to draw the frame into `view`, which has to
+    // have the surface's format and size
+    pub fn encode_frame(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        if self.textures_bind_group_dirty {
+            self.rebuild_textures_bind_group();
+        }
+
+        self.push_constant_value =
+            self.time_override.unwrap_or_else(|| self.elapsed_time());
+        if !self.push_constants_supported {
+            self.queue.write_buffer(
+                &self.time_buffer,
+                0,
+                bytemuck::bytes_of(&self.push_constant_value),
+            );
+            self.current_frame_stats
+                .record_buffer_write(std::mem::size_of::<f32>());
+        }
+
+        self.render_dirty_targets(encoder);
+        self.upload_polygons();
+
+        let culling =
+            self.culling_enabled() && self.active_render_target.is_none();
+        if culling {
+            self.cull_rectangles();
+        }
+
+        // while a target is active rectangles_to_render is the target's
+        // list, the window's is swapped out. Render targets don't have a
+        // stencil buffer or clip rects.
+        let (rectangles_to_render, stencil_changes, clip_changes) =
+            match self.active_render_target {
+                Some(index) => (
+                    &self.render_targets[index].rectangles_to_render,
+                    &[][..],
+                    &[][..],
+                ),
+                None if culling => (
+                    &self.culled_rectangles,
+                    self.culled_stencil_changes.as_slice(),
+                    self.culled_clip_changes.as_slice(),
+                ),
+                None => {
+                    self.cull_stats = CullStats {
+                        submitted: self.rectangles_to_render.len(),
+                        culled: 0,
+                    };
+                    (
+                        &self.rectangles_to_render,
+                        self.stencil_changes.as_slice(),
+                        self.clip_changes.as_slice(),
+                    )
+                }
+            };
+
+        let (grown, rectangle_count) = grow_rectangles_buffer(
+            &self.device,
+            &mut self.rectangles_buffer,
+            "Rectangles Buffer",
+            rectangles_to_render.len(),
+        );
+        if grown {
+            self.current_frame_stats.record_bind_group_rebuild();
+            self.uniform_bind_group = create_uniform_bind_group(
+                &self.device,
+                &self.uniform_bind_group_layout,
+                &self.projection_buffer,
+                &self.rectangles_buffer,
+                &self.texture_settings_buffer,
+                &self.time_buffer,
+                &self.samplers,
+            );
+        }
+
+        // only upload the part of the list that's different from what's
+        // already in the buffer. Comparing is a lot cheaper than the upload,
+        // and it works no matter how the list was changed (or whether it
+        // was rebuilt from scratch with the same contents).
+        let rectangles: &[u8] =
+            bytemuck::cast_slice(&rectangles_to_render[..rectangle_count]);
+        let changed = if grown {
+            Some(0..rectangles.len())
+        } else {
+            changed_rectangles(rectangles, &self.uploaded_rectangles)
+        };
+
+        self.uploaded_rectangles.resize(rectangles.len(), 0);
+        if let Some(changed) = changed {
+            self.queue.write_buffer(
+                &self.rectangles_buffer,
+                changed.start as u64,
+                &rectangles[changed.clone()],
+            );
+            self.frame_timer.record_upload(changed.len() as u64);
+            self.current_frame_stats.record_buffer_write(changed.len());
+
+            self.uploaded_rectangles[changed.clone()]
+                .copy_from_slice(&rectangles[changed]);
+        }
+
+        let measure_gpu_time = self
+            .gpu_timer
+            .as_mut()
+            .is_some_and(|gpu_timer| gpu_timer.start_measurement());
+
+        // with a post process shader the frame is drawn into its texture
+        // first, and from there onto `view`
+        let main_view = match &self.post_process {
+            Some(post_process) => &post_process.view,
+            None => view,
+        };
+
+        // the view is cleared first, so the hook draws on the clear color.
+        // The MSAA texture is cleared in the main pass and resolved over
+        // main_view, there'd be nothing left of what the hook drew.
+        if let (Some(hook), None) =
+            (&mut self.pre_render_hook, &self.msaa_texture)
+        {
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Pre Render Hook Clear Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: main_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clear_color.into()),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            hook(&self.device, &self.queue, encoder, main_view);
+        }
+
+        // with multisampling everything is drawn into the MSAA texture and
+        // resolved into main_view at the end, the samples aren't needed
+        // after that
+        let (color_view, resolve_target, store) = match &self.msaa_texture {
+            Some(msaa_texture) => {
+                (&msaa_texture.view, Some(main_view), wgpu::StoreOp::Discard)
+            }
+            None => (main_view, None, wgpu::StoreOp::Store),
+        };
+
+        // keep what the pre render hook drew
+        let load = match (&self.pre_render_hook, &self.msaa_texture) {
+            (Some(_), None) => wgpu::LoadOp::Load,
+            _ => wgpu::LoadOp::Clear(self.clear_color.into()),
+        };
+
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color_view,
+                    resolve_target,
+                    ops: wgpu::Operations { load, store },
+                })],
+                depth_stencil_attachment: self.depth_buffer.as_ref().map(
+                    |depth_buffer| wgpu::RenderPassDepthStencilAttachment {
+                        view: &depth_buffer.view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(0),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                    },
+                ),
+                occlusion_query_set: None,
+                timestamp_writes: self
+                    .gpu_timer
+                    .as_ref()
+                    .filter(|_| measure_gpu_time)
+                    .map(|gpu_timer| gpu_timer.timestamp_writes()),
+            });
+
+        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.textures_bind_group, &[]);
+
+        if self.push_constants_supported {
+            render_pass.set_push_constants(
+                wgpu::ShaderStages::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&self.push_constant_value),
+            );
+        }
+
+        if self.stencil_pipelines.is_some() {
+            render_pass.set_stencil_reference(STENCIL_REFERENCE);
+        }
+
+        // DRAW
+        // ====
+
+        // the rectangles are split up wherever the stencil mode or the clip
+        // rect changes, and each part is drawn with the pipeline for its
+        // mode and a scissor rect for its clip rect. Without a stencil mode
+        // they're split up further by material.
+        let clip_scale = if self.logical_coordinates {
+            self.scale_factor as f32
+        } else {
+            1.0
+        };
+        let surface_size = (self.config.width, self.config.height);
+
+        for (section, mode, clip) in
+            draw_sections(stencil_changes, clip_changes, rectangle_count)
+        {
+            let (start, end) = (section.start, section.end);
+
+            let scissor_rect = match clip {
+                Some(clip) => self
+                    .camera
+                    .clip_rect_to_screen(&clip)
+                    .scissor_rect(clip_scale, surface_size),
+                None => Some((0, 0, surface_size.0, surface_size.1)),
+            };
+            // clipped away completely
+            let Some((x, y, width, height)) = scissor_rect else {
+                continue;
+            };
+            render_pass.set_scissor_rect(x, y, width, height);
+
+            let stencil_pipeline = match (&self.stencil_pipelines, mode) {
+                (Some(pipelines), StencilMode::Write) => Some(&pipelines.write),
+                (Some(pipelines), StencilMode::Test) => Some(&pipelines.test),
+                _ => None,
+            };
+
+            match stencil_pipeline {
+                Some(pipeline) => {
+                    render_pass.set_pipeline(pipeline);
+                    render_pass.draw(0..6, start as u32..end as u32);
+                    self.current_frame_stats.record_draw_call(end - start);
+                }
+                None => {
+                    for (run, material) in
+                        material_runs(&rectangles_to_render[start..end], start)
+                    {
+                        // materials have their own pipeline that always
+                        // blends with alpha, only the built-in shader has
+                        // one for each blend mode
+                        let blend_runs: Vec<_> = if material == DEFAULT_MATERIAL
+                        {
+                            blend_runs(
+                                &rectangles_to_render[run.clone()],
+                                run.start,
+                            )
+                            .collect()
+                        } else {
+                            vec![(run, BlendMode::Normal)]
+                        };
+
+                        for (run, blend_mode) in blend_runs {
+                            let pipeline =
+                                match self.blend_pipelines.get(&blend_mode) {
+                                    Some(pipeline) => pipeline,
+                                    None => material_pipeline(
+                                        &self.materials,
+                                        &self.render_pipeline,
+                                        material,
+                                        false,
+                                    ),
+                                };
+                            render_pass.set_pipeline(pipeline);
+                            render_pass
+                                .draw(0..6, run.start as u32..run.end as u32);
+                            self.current_frame_stats
+                                .record_draw_call(run.len());
+                        }
+                    }
+                }
+            }
+        }
+
+        // polygons aren't clipped
+        render_pass.set_scissor_rect(0, 0, surface_size.0, surface_size.1);
+
+        if !self.polygon_indices.is_empty() {
+            render_pass.set_pipeline(&self.polygon_pipeline);
+            render_pass
+                .set_vertex_buffer(0, self.polygon_vertex_buffer.slice(..));
+            render_pass.set_index_buffer(
+                self.polygon_index_buffer.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
+            render_pass.draw_indexed(
+                0..self.polygon_indices.len() as u32,
+                0,
+                0..1,
+            );
+            self.current_frame_stats.record_draw_call(0);
+        }
+
+        drop(render_pass);
+
+        if let Some(gpu_timer) =
+            self.gpu_timer.as_ref().filter(|_| measure_gpu_time)
+        {
+            gpu_timer.resolve(encoder);
+        }
+
+        if let Some(post_process) = &self.post_process {
+            post_process.encode(encoder, view);
+        }
+
+        if let Some(hook) = &mut self.post_render_hook {
+            hook(&self.device, &self.queue, encoder, view);
+        }
+    }
+
     pub fn create_texture_from_raw_data(
         &mut self,
         data: &DynamicImage,
-    ) -> Result<TextureHandle, &str> {
-        let texture =
-            create_texture_from_raw_data(&self.device, &self.queue, data);
+    ) -> Result<TextureHandle, TextureError> {
+        self.create_texture_from_raw_data_with_format(
+            data,
+            TextureFormat::default(),
+        )
+    }
 
-        self.textures.push(texture);
+    /// Creates a texture for every image, the handles are in the same order.
+    /// Nothing is created if they don't all fit. Textures created one by one
+    /// are just as fast since the bind group is only rebuilt once before the
+    /// next frame either way, this is just shorter.
+    pub fn create_textures_batch(
+        &mut self,
+        images: &[DynamicImage],
+    ) -> Result<Vec<TextureHandle>, TextureError> {
+        if self.textures.len() + images.len()
+            > self.texture_binding_mode.max_textures() as usize
+        {
+            return Err(TextureError::TooManyTextures);
+        }
 
-        // UPDATE BIND GROUP
-        // =================
+        images
+            .iter()
+            .map(|image| self.create_texture_from_raw_data(image))
+            .collect()
+    }
 
-        let mut texture_views: Vec<&wgpu::TextureView> =
-            Vec::with_capacity(1000);
-        for texture in self.textures.iter() {
-            texture_views.push(&texture.wgpu_texture_view);
+    /// Same as `create_texture_from_raw_data`, but the texture isn't
+    /// necessarily sRGB. Fails with `TextureError::FormatMismatch` if a
+    /// color image is used for an `R8Unorm` texture.
+    pub fn create_texture_from_raw_data_with_format(
+        &mut self,
+        data: &DynamicImage,
+        format: TextureFormat,
+    ) -> Result<TextureHandle, TextureError> {
+        if self.textures.len()
+            >= self.texture_binding_mode.max_textures() as usize
+        {
+            return Err(TextureError::TooManyTextures);
         }
 
-        // fill the rest with an empty texture view
-        for _ in texture_views.len()..1000 {
-            texture_views.push(&self.empty_texture.wgpu_texture_view)
+        format.check_image(data)?;
+
+        let texture = create_texture_from_raw_data(
+            &self.device,
+            &self.queue,
+            data,
+            format,
+        );
+        self.current_frame_stats.record_texture_upload();
+
+        self.add_texture(texture)
+    }
+
+    // gives the texture a slot in the textures bind group
+    pub fn add_texture(
+        &mut self,
+        texture: Texture,
+    ) -> Result<TextureHandle, TextureError> {
+        if self.textures.len()
+            >= self.texture_binding_mode.max_textures() as usize
+        {
+            return Err(TextureError::TooManyTextures);
         }
 
-        self.textures_bind_group =
-            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &self.textures_bind_group_layout,
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureViewArray(
-                        &texture_views,
-                    ),
-                }],
-                label: Some("Textures bind group"),
+        // single-channel textures are mostly masks
+        let swizzle = match texture.format {
+            TextureFormat::R8Unorm => Swizzle::Alpha,
+            _ => Swizzle::Rgba,
+        };
+
+        self.textures.push(texture);
+        let handle = self.textures.len() - 1;
+
+        self.texture_settings.push(TextureSettings {
+            sampler_index: 0,
+            swizzle,
+        });
+        self.write_texture_settings(handle);
+
+        self.textures_bind_group_dirty = true;
+
+        Ok(handle)
+    }
+
+    pub fn write_texture_settings(&mut self, handle: TextureHandle) {
+        self.queue.write_buffer(
+            &self.texture_settings_buffer,
+            (handle * std::mem::size_of::<u32>()) as u64,
+            bytemuck::bytes_of(&self.texture_settings[handle].packed()),
+        );
+        self.current_frame_stats
+            .record_buffer_write(std::mem::size_of::<u32>());
+    }
+
+    /// Changes how the texture's channels are used when it's drawn. R8Unorm
+    /// textures use `Swizzle::Alpha` by default, everything else
+    /// `Swizzle::Rgba`.
+    pub fn set_texture_swizzle(
+        &mut self,
+        handle: TextureHandle,
+        swizzle: Swizzle,
+    ) -> Result<(), TextureError> {
+        let settings = self
+            .texture_settings
+            .get_mut(handle)
+            .ok_or(TextureError::InvalidHandle)?;
+        settings.swizzle = swizzle;
+
+        self.write_texture_settings(handle);
+
+        Ok(())
+    }
+
+    /// Rebuilds the textures bind group right away. This happens before
+    /// drawing anyway when textures changed, so it's rarely needed.
+    pub fn rebuild_textures_bind_group(&mut self) {
+        self.textures_bind_group_dirty = false;
+        self.current_frame_stats.record_bind_group_rebuild();
+        self.textures_bind_group = create_textures_bind_group(
+            &self.device,
+            &self.textures_bind_group_layout,
+            self.texture_binding_mode,
+            &self.textures,
+            &self.empty_texture,
+            None,
+        );
+    }
+
+    /// Sets how the texture is filtered and what happens outside of the 0..1
+    /// uv range. Textures use `AppConfig::default_sampler` until this is
+    /// called.
+    pub fn set_texture_sampler(
+        &mut self,
+        handle: TextureHandle,
+        options: SamplerOptions,
+    ) -> Result<(), TextureError> {
+        if handle >= self.textures.len() {
+            return Err(TextureError::InvalidHandle);
+        }
+
+        let sampler_index = match self
+            .samplers
+            .iter()
+            .position(|(sampler_options, _)| *sampler_options == options)
+        {
+            Some(index) => index,
+            None => {
+                if self.samplers.len() >= MAX_SAMPLERS as usize {
+                    return Err(TextureError::TooManySamplers);
+                }
+
+                self.samplers
+                    .push((options, create_sampler(&self.device, options)));
+
+                self.current_frame_stats.record_bind_group_rebuild();
+                self.uniform_bind_group = create_uniform_bind_group(
+                    &self.device,
+                    &self.uniform_bind_group_layout,
+                    &self.projection_buffer,
+                    &self.rectangles_buffer,
+                    &self.texture_settings_buffer,
+                    &self.time_buffer,
+                    &self.samplers,
+                );
+
+                self.samplers.len() - 1
+            }
+        };
+
+        self.texture_settings[handle].sampler_index = sampler_index as u32;
+        self.write_texture_settings(handle);
+
+        Ok(())
+    }
+
+    /// Overwrites the contents of an existing texture. The image has to be
+    /// the same size as the texture. Draws using the texture don't need to
+    /// change since it keeps its handle.
+    pub fn update_texture(
+        &mut self,
+        handle: TextureHandle,
+        data: &DynamicImage,
+    ) -> Result<(), TextureError> {
+        let texture = self
+            .textures
+            .get(handle)
+            .ok_or(TextureError::InvalidHandle)?;
+
+        if texture.size != data.dimensions() {
+            return Err(TextureError::SizeMismatch {
+                expected: texture.size,
+                actual: data.dimensions(),
             });
+        }
+
+        texture.format.check_image(data)?;
 
-        // return index of the added texture
-        Ok(self.textures.len() - 1)
+        write_to_texture(
+            &self.queue,
+            &texture.wgpu_texture,
+            texture.format,
+            (0, 0),
+            data,
+        );
+        self.current_frame_stats.record_texture_upload();
+
+        Ok(())
+    }
+
+    /// Like `update_texture`, but the image can have a different size. The
+    /// texture is created again if it does, keeping its handle, path and
+    /// format.
+    pub fn replace_texture(
+        &mut self,
+        handle: TextureHandle,
+        data: &DynamicImage,
+    ) -> Result<(), TextureError> {
+        let texture = self
+            .textures
+            .get_mut(handle)
+            .ok_or(TextureError::InvalidHandle)?;
+
+        if texture.size == data.dimensions() {
+            return self.update_texture(handle, data);
+        }
+
+        texture.format.check_image(data)?;
+
+        let mut new_texture = create_texture_from_raw_data(
+            &self.device,
+            &self.queue,
+            data,
+            texture.format,
+        );
+        new_texture.path = texture.path.take();
+        *texture = new_texture;
+        self.current_frame_stats.record_texture_upload();
+
+        self.textures_bind_group_dirty = true;
+
+        Ok(())
+    }
+
+    /// Size of the texture in pixels, or `None` if the handle is invalid.
+    pub fn texture_size(&self, handle: TextureHandle) -> Option<(u32, u32)> {
+        self.textures.get(handle).map(|texture| texture.size)
+    }
+
+    /// Same as `texture_size`.
+    pub fn texture_dimensions(
+        &self,
+        handle: TextureHandle,
+    ) -> Option<(u32, u32)> {
+        self.texture_size(handle)
+    }
+
+    /// How many textures were created, including the ones that are still
+    /// loading.
+    pub fn texture_count(&self) -> usize {
+        self.textures.len()
+    }
+
+    /// Every valid texture handle, in the order they were created.
+    pub fn texture_handles(&self) -> impl Iterator<Item = TextureHandle> {
+        0..self.textures.len()
+    }
+
+    /// Size and source path of the texture, or `None` if the handle is
+    /// invalid.
+    pub fn texture_info(&self, handle: TextureHandle) -> Option<TextureInfo> {
+        self.textures.get(handle).map(|texture| TextureInfo {
+            size: texture.size,
+            format: texture.format,
+            path: texture.path.clone(),
+        })
     }
 
     pub fn create_texture_from_path(
         &mut self,
         path: &str,
-    ) -> Result<TextureHandle, &str> {
+    ) -> Result<TextureHandle, TextureError> {
+        self.create_texture_from_path_with_format(
+            path,
+            TextureFormat::default(),
+        )
+    }
+
+    pub fn create_texture_from_path_with_format(
+        &mut self,
+        path: &str,
+        format: TextureFormat,
+    ) -> Result<TextureHandle, TextureError> {
         // LOAD IMAGE DATA
         // ===============
 
-        let img = image::io::Reader::open(path);
-        if img.is_err() {
-            return Err("Could not open file.");
-        }
-        let img = img.unwrap();
+        let decoded_img = load_image_from_path(path)?;
 
-        let decoded_img = img.decode();
-        if decoded_img.is_err() {
-            return Err("Could not decode image data.");
-        }
-        let decoded_img = decoded_img.unwrap();
+        let handle = self
+            .create_texture_from_raw_data_with_format(&decoded_img, format)?;
+        self.textures[handle].path = Some(path.to_string());
 
-        return self.create_texture_from_raw_data(&decoded_img);
+        Ok(handle)
     }
 
-    fn calculate_projection_matrix(
+    /// Loads an encoded image (png, jpeg) that's already in memory, e.g. from
+    /// `include_bytes!`.
+    pub fn create_texture_from_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<TextureHandle, TextureError> {
+        self.create_texture_from_bytes_with_format(
+            bytes,
+            TextureFormat::default(),
+        )
+    }
+
+    pub fn create_texture_from_bytes_with_format(
+        &mut self,
+        bytes: &[u8],
+        format: TextureFormat,
+    ) -> Result<TextureHandle, TextureError> {
+        let decoded_img = image::load_from_memory(bytes)
+            .map_err(TextureError::CouldNotDecode)?;
+
+        self.create_texture_from_raw_data_with_format(&decoded_img, format)
+    }
+
+    pub fn calculate_projection_matrix(
         window_width: f32,
         window_height: f32,
     ) -> [u8; 64] {
+        Self::calculate_view_projection_matrix(Rect::new(
+            0.0,
+            0.0,
+            window_width,
+            window_height,
+        ))
+    }
+
+    // maps `area` to the whole surface, top left to top left
+    pub fn calculate_view_projection_matrix(area: Rect) -> [u8; 64] {
         let matrix = OPENGL_TO_WGPU_MATRIX
-            * cgmath::ortho(0.0, window_width, window_height, 0.0, -1.0, 1.0);
+            * cgmath::ortho(
+                area.x,
+                area.x + area.w,
+                area.y + area.h,
+                area.y,
+                -1.0,
+                1.0,
+            );
 
         let matrix_transformed: [[f32; 4]; 4] = matrix.into();
 
@@ -523,8 +2917,8 @@ pub fn create_texture_from_raw_data(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     data: &DynamicImage,
+    format: TextureFormat,
 ) -> Texture {
-    let rgba = data.to_rgba8();
     let dimensions = data.dimensions();
 
     // CREATE WGPU TEXTURE
@@ -540,7 +2934,7 @@ pub fn create_texture_from_raw_data(
         mip_level_count: 1,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        format: format.to_wgpu(),
         usage: wgpu::TextureUsages::TEXTURE_BINDING
             | wgpu::TextureUsages::COPY_DST,
         label: Some("Texture created from raw data"),
@@ -550,27 +2944,461 @@ pub fn create_texture_from_raw_data(
     // WRITE TO WGPU TEXTURE
     // =====================
 
+    write_to_texture(queue, &texture, format, (0, 0), data);
+
+    let texture_view =
+        texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    Texture {
+        wgpu_texture: texture,
+        wgpu_texture_view: texture_view,
+        size: dimensions,
+        format,
+        path: None,
+        state: TextureState::Ready,
+    }
+}
+
+// writes the image into the texture with its top left corner at origin
+pub fn write_to_texture(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    format: TextureFormat,
+    origin: (u32, u32),
+    data: &DynamicImage,
+) {
+    let (width, height) = data.dimensions();
+
+    // nothing to copy
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    if let Some(bytes) = format.pixel_bytes(data) {
+        write_texture_rows(
+            queue, texture, format, origin, bytes, width, height,
+        );
+        return;
+    }
+
+    // images in a different layout (like RGB without alpha) are converted
+    // a few rows at a time, so there's never a second copy of the whole
+    // image
+    let bytes_per_row = format.bytes_per_pixel() * width;
+    let rows_per_chunk = (CONVERSION_CHUNK_SIZE / bytes_per_row).max(1);
+
+    for y in (0..height).step_by(rows_per_chunk as usize) {
+        let rows = rows_per_chunk.min(height - y);
+        let chunk = format.convert_pixels(&data.crop_imm(0, y, width, rows));
+
+        write_texture_rows(
+            queue,
+            texture,
+            format,
+            (origin.0, origin.1 + y),
+            &chunk,
+            width,
+            rows,
+        );
+    }
+}
+
+fn write_texture_rows(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    format: TextureFormat,
+    origin: (u32, u32),
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+) {
     queue.write_texture(
         wgpu::ImageCopyTexture {
-            texture: &texture,
+            texture,
             mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
+            origin: wgpu::Origin3d {
+                x: origin.0,
+                y: origin.1,
+                z: 0,
+            },
             aspect: wgpu::TextureAspect::All,
         },
-        &rgba,
+        bytes,
         wgpu::ImageDataLayout {
             offset: 0,
-            bytes_per_row: Some(4 * dimensions.0),
-            rows_per_image: Some(dimensions.1),
+            bytes_per_row: Some(format.bytes_per_pixel() * width),
+            rows_per_image: Some(height),
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
         },
-        texture_size,
     );
+}
 
-    let texture_view =
-        texture.create_view(&wgpu::TextureViewDescriptor::default());
+#[allow(clippy::too_many_arguments)]
+pub fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    fragment_entry_point: &str,
+    format: wgpu::TextureFormat,
+    write_mask: wgpu::ColorWrites,
+    blend: wgpu::BlendState,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: fragment_entry_point,
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(blend),
+                write_mask,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            // no culling since I'm only drawing rectangles!!!!!
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
 
-    return Texture {
-        wgpu_texture: texture,
-        wgpu_texture_view: texture_view,
+pub fn create_sampler(
+    device: &wgpu::Device,
+    options: SamplerOptions,
+) -> Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: options.address_mode,
+        address_mode_v: options.address_mode,
+        address_mode_w: options.address_mode,
+        mag_filter: options.mag_filter,
+        min_filter: options.min_filter,
+        mipmap_filter: options.mipmap_filter,
+        ..Default::default()
+    })
+}
+
+// the max wins if the min is bigger than it
+fn clamp_size(
+    size: PhysicalSize<u32>,
+    min_size: Option<(u32, u32)>,
+    max_size: Option<(u32, u32)>,
+) -> PhysicalSize<u32> {
+    let mut size = size;
+
+    if let Some((min_width, min_height)) = min_size {
+        size.width = size.width.max(min_width);
+        size.height = size.height.max(min_height);
+    }
+    if let Some((max_width, max_height)) = max_size {
+        size.width = size.width.min(max_width);
+        size.height = size.height.min(max_height);
+    }
+
+    size
+}
+
+pub fn create_rectangles_buffer(
+    device: &wgpu::Device,
+    label: &str,
+    capacity: u64,
+) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        size: capacity * std::mem::size_of::<RectangleDrawData>() as u64,
+        mapped_at_creation: false,
+    })
+}
+
+// The bytes of `new` that differ from `old`, from the start of the first
+// rectangle that changed to the end of the last one. Rectangles past the end
+// of `old` count as changed, ones that were removed from the end don't have
+// to be written at all.
+pub fn changed_rectangles(new: &[u8], old: &[u8]) -> Option<Range<usize>> {
+    let rectangle_size = std::mem::size_of::<RectangleDrawData>();
+    let common = new.len().min(old.len());
+    let pairs = || {
+        new[..common]
+            .chunks_exact(rectangle_size)
+            .zip(old[..common].chunks_exact(rectangle_size))
+    };
+
+    let first = pairs().position(|(a, b)| a != b);
+    let start = first.map_or(common, |index| index * rectangle_size);
+    if start == new.len() {
+        return None;
+    }
+
+    let end = if new.len() > old.len() {
+        new.len()
+    } else {
+        // there's a difference in the common part, otherwise start would
+        // be new.len()
+        let last = pairs().rposition(|(a, b)| a != b).unwrap_or(0);
+        (last + 1) * rectangle_size
+    };
+
+    Some(start..end)
+}
+
+// Makes sure `buffer` can hold `count` rectangles, recreating it at least
+// twice as big if it can't. The bind groups that use it have to be rebuilt
+// when it was recreated (the first value). The buffer can't grow past what
+// the device allows, the second value is how many rectangles actually fit
+// and the rest aren't drawn.
+pub fn grow_rectangles_buffer(
+    device: &wgpu::Device,
+    buffer: &mut Buffer,
+    label: &str,
+    count: usize,
+) -> (bool, usize) {
+    let rectangle_size = std::mem::size_of::<RectangleDrawData>() as u64;
+    let capacity = buffer.size() / rectangle_size;
+    let count = count as u64;
+
+    if count <= capacity {
+        return (false, count as usize);
+    }
+
+    let limits = device.limits();
+    let max_capacity = (limits.max_storage_buffer_binding_size as u64)
+        .min(limits.max_buffer_size)
+        / rectangle_size;
+
+    if capacity >= max_capacity {
+        return (false, capacity as usize);
+    }
+
+    let new_capacity = (capacity * 2).max(count).min(max_capacity);
+    if new_capacity < count {
+        log::warn!(
+            "Only {} of {} rectangles fit in a buffer on this device",
+            new_capacity,
+            count
+        );
+    }
+
+    *buffer = create_rectangles_buffer(device, label, new_capacity);
+
+    (true, count.min(new_capacity) as usize)
+}
+
+pub fn create_uniform_bind_group(
+    device: &wgpu::Device,
+    layout: &BindGroupLayout,
+    projection_buffer: &Buffer,
+    rectangles_buffer: &Buffer,
+    texture_settings_buffer: &Buffer,
+    time_buffer: &Buffer,
+    samplers: &[(SamplerOptions, Sampler)],
+) -> BindGroup {
+    let mut entries = vec![
+        wgpu::BindGroupEntry {
+            binding: 0,
+            resource: projection_buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+            binding: 1,
+            resource: rectangles_buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+            binding: 2,
+            resource: texture_settings_buffer.as_entire_binding(),
+        },
+    ];
+
+    // fill the unused sampler slots with the default sampler
+    for i in 0..MAX_SAMPLERS as usize {
+        let (_, sampler) = samplers.get(i).unwrap_or(&samplers[0]);
+
+        entries.push(wgpu::BindGroupEntry {
+            binding: 3 + i as u32,
+            resource: wgpu::BindingResource::Sampler(sampler),
+        });
+    }
+
+    entries.push(wgpu::BindGroupEntry {
+        binding: TIME_BINDING,
+        resource: time_buffer.as_entire_binding(),
+    });
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &entries,
+        label: Some("Uniform bind group"),
+    })
+}
+
+pub fn create_textures_bind_group_layout(
+    device: &wgpu::Device,
+    mode: TextureBindingMode,
+) -> BindGroupLayout {
+    let entry = |binding, count| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+        count,
     };
+
+    let entries: Vec<wgpu::BindGroupLayoutEntry> = match mode {
+        TextureBindingMode::BindingArray => {
+            vec![entry(0, NonZeroU32::new(MAX_TEXTURES))]
+        }
+        TextureBindingMode::Bounded(count) => {
+            (0..count).map(|i| entry(i, None)).collect()
+        }
+    };
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &entries,
+        label: Some("Textures bind group layout"),
+    })
+}
+
+pub fn create_textures_bind_group(
+    device: &wgpu::Device,
+    layout: &BindGroupLayout,
+    mode: TextureBindingMode,
+    textures: &[Texture],
+    empty_texture: &Texture,
+    excluded: Option<TextureHandle>,
+) -> BindGroup {
+    let mut texture_views: Vec<&wgpu::TextureView> =
+        Vec::with_capacity(mode.max_textures() as usize);
+    for (handle, texture) in textures.iter().enumerate() {
+        if excluded == Some(handle) {
+            texture_views.push(&empty_texture.wgpu_texture_view);
+        } else {
+            texture_views.push(&texture.wgpu_texture_view);
+        }
+    }
+
+    // fill the rest with an empty texture view
+    for _ in texture_views.len()..mode.max_textures() as usize {
+        texture_views.push(&empty_texture.wgpu_texture_view)
+    }
+
+    let entries: Vec<wgpu::BindGroupEntry> = match mode {
+        TextureBindingMode::BindingArray => vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureViewArray(&texture_views),
+        }],
+        TextureBindingMode::Bounded(_) => texture_views
+            .iter()
+            .enumerate()
+            .map(|(i, view)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: wgpu::BindingResource::TextureView(view),
+            })
+            .collect(),
+    };
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &entries,
+        label: Some("Textures bind group"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgba_formats_take_grayscale_images() {
+        let gray = DynamicImage::new_luma8(2, 2);
+        let gray_alpha = DynamicImage::new_luma_a8(2, 2);
+        let rgb = DynamicImage::new_rgb8(2, 2);
+
+        for format in [TextureFormat::Rgba8UnormSrgb, TextureFormat::Rgba8Unorm]
+        {
+            for image in [&gray, &gray_alpha, &rgb] {
+                assert!(format.check_image(image).is_ok());
+            }
+            assert_eq!(format.convert_pixels(&gray_alpha).len(), 2 * 2 * 4);
+        }
+
+        assert!(TextureFormat::R8Unorm.check_image(&gray).is_ok());
+        assert!(TextureFormat::R8Unorm.check_image(&gray_alpha).is_err());
+        assert!(TextureFormat::R8Unorm.check_image(&rgb).is_err());
+    }
+
+    #[test]
+    fn projection_maps_the_area_into_clip_space() {
+        let area = Rect::new(10.0, 20.0, 800.0, 600.0);
+        let bytes = Context::calculate_view_projection_matrix(area);
+        let columns: [[f32; 4]; 4] = bytemuck::cast(bytes);
+        let matrix = cgmath::Matrix4::from(columns);
+
+        let project = |x: f32, y: f32, z: f32| {
+            let clip = matrix * cgmath::Vector4::new(x, y, z, 1.0);
+            [clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, clip.w]
+        };
+        let assert_near = |actual: [f32; 4], expected: [f32; 4]| {
+            for (a, e) in actual.iter().zip(expected) {
+                assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+            }
+        };
+
+        // the corners of the area are the corners of the frame whatever z
+        // is, and a higher z is closer
+        for (z, depth) in [(-1.0, 1.0), (0.0, 0.5), (1.0, 0.0)] {
+            assert_near(project(10.0, 20.0, z), [-1.0, 1.0, depth, 1.0]);
+            assert_near(project(810.0, 620.0, z), [1.0, -1.0, depth, 1.0]);
+        }
+    }
+
+    #[test]
+    fn clamp_size_to_constraints() {
+        let size = PhysicalSize::new(50, 500);
+
+        assert_eq!(clamp_size(size, None, None), size);
+        assert_eq!(
+            clamp_size(size, Some((100, 100)), None),
+            PhysicalSize::new(100, 500)
+        );
+        assert_eq!(
+            clamp_size(size, None, Some((300, 300))),
+            PhysicalSize::new(50, 300)
+        );
+        assert_eq!(
+            clamp_size(size, Some((100, 100)), Some((300, 300))),
+            PhysicalSize::new(100, 300)
+        );
+        // a min bigger than the max
+        assert_eq!(
+            clamp_size(size, Some((400, 400)), Some((300, 300))),
+            PhysicalSize::new(300, 300)
+        );
+    }
 }
@@ -0,0 +1,120 @@
+use crate::{
+    camera::Camera,
+    clip::ClipRect,
+    context::{Context, RectangleDrawData, RECTANGLE_NO_CULL},
+};
+
+// how many rectangles were drawn in the last frame and how many of them
+// were left out because they were outside of the window or their clip rect
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CullStats {
+    pub submitted: usize,
+    pub culled: usize,
+}
+
+// whether any part of the rectangle is inside the area (the viewport, or
+// the part of the clip rect that's in it). Touching the edge counts as
+// inside.
+fn is_visible(rectangle: &RectangleDrawData, area: &ClipRect) -> bool {
+    if rectangle.flags & RECTANGLE_NO_CULL != 0 {
+        return true;
+    }
+
+    // rectangles with a negative size extend to the left or up
+    let [x, y] = rectangle.pos;
+    let [w, h] = rectangle.size;
+    let (min_x, max_x) = (x.min(x + w), x.max(x + w));
+    let (min_y, max_y) = (y.min(y + h), y.max(y + h));
+
+    max_x >= area.pos[0]
+        && min_x <= area.pos[0] + area.size[0]
+        && max_y >= area.pos[1]
+        && min_y <= area.pos[1] + area.size[1]
+}
+
+impl<'a> Context<'a> {
+    /// Leaves out rectangles that are completely outside of the window (or
+    /// their clip rect) before they're uploaded, so they don't cost
+    /// anything on the GPU. Rectangles with `RECTANGLE_NO_CULL` in their
+    /// flags are always drawn. Only applies when drawing to the window, not
+    /// to render targets. By default it's on while the camera is moved or
+    /// zoomed (anything other than `Camera::default()`), since that's when
+    /// most of the world is usually off screen. This turns it on or off for
+    /// good.
+    pub fn set_culling(&mut self, culling: bool) {
+        self.culling = Some(culling);
+    }
+
+    pub fn culling_enabled(&self) -> bool {
+        self.culling
+            .unwrap_or_else(|| self.camera != Camera::default())
+    }
+
+    /// What culling did in the last frame. Without culling every rectangle
+    /// counts as submitted.
+    pub fn cull_stats(&self) -> CullStats {
+        self.cull_stats
+    }
+
+    /// How many rectangles were actually drawn into the window in the last
+    /// frame, after culling.
+    pub fn visible_rectangle_count(&self) -> usize {
+        self.cull_stats.submitted
+    }
+
+    // fills culled_rectangles with the visible rectangles, and moves the
+    // stencil and clip changes to where their rectangles ended up
+    pub fn cull_rectangles(&mut self) {
+        // what the camera sees, in the units rectangles are positioned in
+        let visible = self.camera.visible_area(self.viewport_size());
+        let viewport =
+            ClipRect::new(visible.pos().into(), visible.size().into());
+        let mut area = viewport;
+
+        self.culled_rectangles.clear();
+        self.culled_stencil_changes.clear();
+        self.culled_clip_changes.clear();
+
+        let mut stencil_changes = self.stencil_changes.iter().peekable();
+        let mut clip_changes = self.clip_changes.iter().peekable();
+        for (index, rectangle) in self.rectangles_to_render.iter().enumerate() {
+            while let Some(&(_, mode)) =
+                stencil_changes.next_if(|(start, _)| *start <= index)
+            {
+                self.culled_stencil_changes
+                    .push((self.culled_rectangles.len(), mode));
+            }
+            while let Some(&(_, clip)) =
+                clip_changes.next_if(|(start, _)| *start <= index)
+            {
+                self.culled_clip_changes
+                    .push((self.culled_rectangles.len(), clip));
+                area = clip.map_or(viewport, |clip| clip.intersect(&viewport));
+            }
+
+            // a clip rect that's off-screen hides everything in it, even
+            // rectangles that aren't supposed to be culled
+            if !area.is_empty() && is_visible(rectangle, &area) {
+                self.culled_rectangles.push(*rectangle);
+            }
+        }
+
+        // changes after the last rectangle
+        for &(_, mode) in stencil_changes {
+            self.culled_stencil_changes
+                .push((self.culled_rectangles.len(), mode));
+        }
+        for &(_, clip) in clip_changes {
+            self.culled_clip_changes
+                .push((self.culled_rectangles.len(), clip));
+        }
+
+        self.cull_stats = CullStats {
+            submitted: self.culled_rectangles.len(),
+            culled: self.rectangles_to_render.len()
+                - self.culled_rectangles.len(),
+        };
+        self.current_frame_stats
+            .record_culled(self.cull_stats.culled);
+    }
+}
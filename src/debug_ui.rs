@@ -0,0 +1,281 @@
+use winit::{
+    event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{Key, PhysicalKey},
+};
+
+use crate::context::Context;
+
+// An egui UI drawn on top of everything else, after the post process
+// shader. egui-winit is built for an older winit, so the events are
+// translated here instead.
+pub struct DebugUi<'a> {
+    pub context: egui::Context,
+    pub renderer: egui_wgpu::Renderer,
+    // events collected since the last frame
+    pub raw_input: egui::RawInput,
+    pub build_ui: Box<dyn FnMut(&egui::Context) + 'a>,
+
+    // in points, egui wants it with every button event
+    pub pointer_pos: egui::Pos2,
+    pub modifiers: egui::Modifiers,
+}
+
+fn translate_key(key: &Key) -> Option<egui::Key> {
+    match key {
+        Key::Named(named) => egui::Key::from_name(&format!("{:?}", named)),
+        Key::Character(c) => egui::Key::from_name(c),
+        _ => None,
+    }
+}
+
+// KeyCode's names are KeyA, Digit1, ArrowUp... egui knows all but the first
+fn translate_physical_key(key: PhysicalKey) -> Option<egui::Key> {
+    match key {
+        PhysicalKey::Code(code) => {
+            let name = format!("{:?}", code);
+            egui::Key::from_name(name.strip_prefix("Key").unwrap_or(&name))
+        }
+        PhysicalKey::Unidentified(_) => None,
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Draws an egui UI over the frame. `build_ui` is called once per frame
+    /// with the egui context to build it, e.g. by showing an
+    /// `egui::Window`. Window events are forwarded to it by
+    /// `handle_window_event`.
+    pub fn set_debug_ui(&mut self, build_ui: impl FnMut(&egui::Context) + 'a) {
+        self.debug_ui = Some(DebugUi {
+            context: egui::Context::default(),
+            renderer: egui_wgpu::Renderer::new(
+                &self.device,
+                self.config.format,
+                None,
+                1,
+            ),
+            raw_input: egui::RawInput::default(),
+            build_ui: Box::new(build_ui),
+            pointer_pos: egui::Pos2::ZERO,
+            modifiers: egui::Modifiers::default(),
+        });
+    }
+
+    pub fn clear_debug_ui(&mut self) {
+        self.debug_ui = None;
+    }
+
+    /// Whether the pointer or keyboard is being used by the debug UI, in
+    /// which case the application should probably ignore it.
+    pub fn debug_ui_wants_input(&self) -> bool {
+        self.debug_ui.as_ref().is_some_and(|debug_ui| {
+            debug_ui.context.wants_pointer_input()
+                || debug_ui.context.wants_keyboard_input()
+        })
+    }
+
+    // turns the window event into egui events for the next frame
+    pub fn handle_debug_ui_event(&mut self, event: &WindowEvent) {
+        let pixels_per_point = self.scale_factor as f32;
+        let Some(debug_ui) = &mut self.debug_ui else {
+            return;
+        };
+        let modifiers = debug_ui.modifiers;
+
+        let event = match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                debug_ui.pointer_pos = egui::pos2(
+                    position.x as f32 / pixels_per_point,
+                    position.y as f32 / pixels_per_point,
+                );
+                egui::Event::PointerMoved(debug_ui.pointer_pos)
+            }
+            WindowEvent::CursorLeft { .. } => egui::Event::PointerGone,
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    MouseButton::Back => egui::PointerButton::Extra1,
+                    MouseButton::Forward => egui::PointerButton::Extra2,
+                    MouseButton::Other(_) => return,
+                };
+                egui::Event::PointerButton {
+                    pos: debug_ui.pointer_pos,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers,
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (unit, delta) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        (egui::MouseWheelUnit::Line, egui::vec2(*x, *y))
+                    }
+                    MouseScrollDelta::PixelDelta(delta) => (
+                        egui::MouseWheelUnit::Point,
+                        egui::vec2(delta.x as f32, delta.y as f32)
+                            / pixels_per_point,
+                    ),
+                };
+                egui::Event::MouseWheel {
+                    unit,
+                    delta,
+                    modifiers,
+                }
+            }
+            WindowEvent::ModifiersChanged(new_modifiers) => {
+                let state = new_modifiers.state();
+                let mac = cfg!(target_os = "macos");
+                debug_ui.modifiers = egui::Modifiers {
+                    alt: state.alt_key(),
+                    ctrl: state.control_key(),
+                    shift: state.shift_key(),
+                    mac_cmd: mac && state.super_key(),
+                    command: if mac {
+                        state.super_key()
+                    } else {
+                        state.control_key()
+                    },
+                };
+                return;
+            }
+            WindowEvent::Focused(focused) => {
+                egui::Event::WindowFocused(*focused)
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                let pressed = event.state == ElementState::Pressed;
+
+                // typed text, but not shortcuts or things like backspace
+                let text = event.text.as_ref().filter(|text| {
+                    pressed
+                        && !modifiers.command
+                        && !text.chars().any(char::is_control)
+                });
+                if let Some(text) = text {
+                    debug_ui
+                        .raw_input
+                        .events
+                        .push(egui::Event::Text(text.to_string()));
+                }
+
+                let Some(key) = translate_key(&event.logical_key) else {
+                    return;
+                };
+                egui::Event::Key {
+                    key,
+                    physical_key: translate_physical_key(event.physical_key),
+                    pressed,
+                    repeat: false,
+                    modifiers,
+                }
+            }
+            _ => return,
+        };
+
+        debug_ui.raw_input.events.push(event);
+
+        // so the UI reacts in RedrawMode::OnDemand too
+        self.request_redraw();
+    }
+
+    // runs the UI and draws it onto `view`. The returned command buffers
+    // come from egui paint callbacks and have to be submitted before the
+    // encoder.
+    pub fn encode_debug_ui(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) -> Vec<wgpu::CommandBuffer> {
+        let pixels_per_point = self.scale_factor as f32;
+        let size_in_pixels = [self.config.width, self.config.height];
+        let time = self.start_instant.elapsed().as_secs_f64();
+        let max_texture_side =
+            self.device.limits().max_texture_dimension_2d as usize;
+
+        let Some(debug_ui) = &mut self.debug_ui else {
+            return vec![];
+        };
+
+        // RUN THE UI
+        // ==========
+
+        let mut raw_input = std::mem::take(&mut debug_ui.raw_input);
+        raw_input.screen_rect = Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(size_in_pixels[0] as f32, size_in_pixels[1] as f32)
+                / pixels_per_point,
+        ));
+        raw_input.time = Some(time);
+        raw_input.max_texture_side = Some(max_texture_side);
+        raw_input.modifiers = debug_ui.modifiers;
+        raw_input
+            .viewports
+            .entry(raw_input.viewport_id)
+            .or_default()
+            .native_pixels_per_point = Some(pixels_per_point);
+
+        let output = debug_ui
+            .context
+            .run(raw_input, |context| (debug_ui.build_ui)(context));
+        let paint_jobs = debug_ui
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+        let screen_descriptor = egui_wgpu::ScreenDescriptor {
+            size_in_pixels,
+            pixels_per_point: output.pixels_per_point,
+        };
+
+        // UPLOAD
+        // ======
+
+        for (id, delta) in &output.textures_delta.set {
+            debug_ui.renderer.update_texture(
+                &self.device,
+                &self.queue,
+                *id,
+                delta,
+            );
+        }
+
+        let callback_buffers = debug_ui.renderer.update_buffers(
+            &self.device,
+            &self.queue,
+            encoder,
+            &paint_jobs,
+            &screen_descriptor,
+        );
+
+        // DRAW
+        // ====
+
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Debug UI Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+        debug_ui.renderer.render(
+            &mut render_pass,
+            &paint_jobs,
+            &screen_descriptor,
+        );
+
+        drop(render_pass);
+
+        for id in &output.textures_delta.free {
+            debug_ui.renderer.free_texture(id);
+        }
+
+        callback_buffers
+    }
+}
@@ -0,0 +1,87 @@
+use crate::stencil::StencilMode;
+
+// stencil is needed for masking, so it's always a combined depth/stencil
+// texture
+pub const DEPTH_FORMAT: wgpu::TextureFormat =
+    wgpu::TextureFormat::Depth24PlusStencil8;
+
+// Lets rectangles cover each other based on their z instead of the order
+// they're drawn in, and holds the stencil masks. Created if either
+// AppConfig::enable_depth_buffer or AppConfig::enable_stencil_buffer is set.
+pub struct DepthBuffer {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl DepthBuffer {
+    // has to be created again whenever the window is resized, it must have
+    // the same size as the surface and the same sample count as the
+    // pipelines
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            label: Some("Depth texture"),
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self { texture, view }
+    }
+}
+
+// the depth state of the pipelines that draw to the window
+pub fn depth_stencil_state(
+    depth_test: bool,
+    stencil: StencilMode,
+) -> wgpu::DepthStencilState {
+    let stencil_face = match stencil {
+        StencilMode::None => wgpu::StencilFaceState::IGNORE,
+        StencilMode::Write => wgpu::StencilFaceState {
+            compare: wgpu::CompareFunction::Always,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op: wgpu::StencilOperation::IncrementClamp,
+        },
+        StencilMode::Test => wgpu::StencilFaceState {
+            compare: wgpu::CompareFunction::Equal,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op: wgpu::StencilOperation::Keep,
+        },
+    };
+
+    wgpu::DepthStencilState {
+        format: DEPTH_FORMAT,
+        // masks shouldn't hide what's drawn under them later
+        depth_write_enabled: depth_test && stencil != StencilMode::Write,
+        // LessEqual instead of Less, otherwise out of rectangles with the
+        // same z the one drawn first would stay on top, the opposite of what
+        // happens without a depth buffer
+        depth_compare: if depth_test {
+            wgpu::CompareFunction::LessEqual
+        } else {
+            wgpu::CompareFunction::Always
+        },
+        stencil: wgpu::StencilState {
+            front: stencil_face,
+            back: stencil_face,
+            read_mask: 0xff,
+            write_mask: 0xff,
+        },
+        bias: wgpu::DepthBiasState::default(),
+    }
+}
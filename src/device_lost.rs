@@ -0,0 +1,321 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use image::DynamicImage;
+
+use crate::{
+    config::AppConfig,
+    context::{
+        create_rectangles_buffer, create_sampler, create_texture_from_raw_data,
+        create_uniform_bind_group, Context, Texture, TextureFormat,
+        TextureHandle, INITIAL_RECTANGLES_CAPACITY,
+    },
+    event_bus::AppEvent,
+    headless::{ContextError, FrameTarget},
+    loader::{load_image_from_path, TextureState},
+    msaa::MsaaTexture,
+    render_target::create_render_target_resources,
+};
+
+// sets `lost` when the device stops working. Dropping the device calls the
+// callback as well, that's not a lost device.
+pub fn watch_device_lost(device: &wgpu::Device, lost: Arc<AtomicBool>) {
+    device.set_device_lost_callback(move |reason, message| match reason {
+        wgpu::DeviceLostReason::Dropped
+        | wgpu::DeviceLostReason::ReplacedCallback => {}
+        _ => {
+            log::error!("The device was lost ({:?}): {}", reason, message);
+            lost.store(true, Ordering::Relaxed);
+        }
+    });
+}
+
+impl<'a> Context<'a> {
+    /// Whether the device stopped working, e.g. because the GPU was reset,
+    /// its driver was updated or a laptop switched GPUs. Nothing can be
+    /// drawn until `recreate_device` is called, which the context's
+    /// `ApplicationHandler` does before the next frame.
+    pub fn device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Creates the device, queue, pipelines and buffers again on a new
+    /// adapter and uploads the textures again, keeping every handle.
+    /// Textures loaded from files are read again, render targets are drawn
+    /// again from what was last drawn into them with `begin_target` and ttf
+    /// fonts rasterize their glyphs again. Other textures only existed on
+    /// the GPU, they're empty afterwards and their handles are returned
+    /// (and sent as `AppEvent::DeviceRecreated`) so they can be filled
+    /// again with `update_texture`. Only contexts from `Context::new` can
+    /// do this, headless ones get `ContextError::NoSurface`.
+    pub fn recreate_device(
+        &mut self,
+    ) -> Result<Vec<TextureHandle>, ContextError> {
+        let (Some(instance), FrameTarget::Surface(surface)) =
+            (&self.instance, &self.frame_target)
+        else {
+            return Err(ContextError::NoSurface);
+        };
+
+        let adapter = pollster::block_on(instance.request_adapter(
+            &wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: Some(surface),
+                force_fallback_adapter: false,
+            },
+        ))
+        .ok_or(ContextError::NoAdapter)?;
+        let surface_caps = surface.get_capabilities(&adapter);
+
+        // DEVICE
+        // ======
+
+        // a headless context on the new device has everything that doesn't
+        // depend on the surface or on what was created since the start
+        let fresh = Context::from_adapter(
+            &adapter,
+            None,
+            None,
+            self.size,
+            self.scale_factor,
+            &AppConfig {
+                default_sampler: self.samplers[0].0,
+                enable_depth_buffer: self.depth_test,
+                enable_stencil_buffer: self.stencil_pipelines.is_some(),
+                sample_count: self.sample_count,
+                enable_gpu_timestamps: self.gpu_timer.is_some(),
+                ..Default::default()
+            },
+        )?;
+
+        self.device = fresh.device;
+        self.queue = fresh.queue;
+        self.device_lost = fresh.device_lost;
+        self.texture_binding_mode = fresh.texture_binding_mode;
+        self.push_constants_supported = fresh.push_constants_supported;
+        self.sample_count = fresh.sample_count;
+        self.sample_count_flags = fresh.sample_count_flags;
+        self.gpu_timer = fresh.gpu_timer;
+
+        // SURFACE
+        // =======
+
+        // the new adapter doesn't necessarily support the same settings
+        if !surface_caps.formats.contains(&self.config.format) {
+            self.config.format = surface_caps
+                .formats
+                .iter()
+                .copied()
+                .find(|f| f.is_srgb())
+                .unwrap_or(surface_caps.formats[0]);
+        }
+        if !surface_caps
+            .present_modes
+            .contains(&self.config.present_mode)
+        {
+            self.config.present_mode = wgpu::PresentMode::Fifo;
+        }
+        if !surface_caps.alpha_modes.contains(&self.config.alpha_mode) {
+            self.config.alpha_mode = surface_caps.alpha_modes[0];
+        }
+        self.present_modes = surface_caps.present_modes;
+        self.reconfigure_surface();
+
+        self.depth_buffer = fresh.depth_buffer;
+        self.msaa_texture = (self.sample_count > 1).then(|| {
+            MsaaTexture::new(
+                &self.device,
+                self.config.format,
+                self.size.width,
+                self.size.height,
+                self.sample_count,
+            )
+        });
+
+        // BUFFERS AND BIND GROUPS
+        // =======================
+
+        self.projection_buffer = fresh.projection_buffer;
+        self.update_projection_matrix();
+
+        self.rectangles_buffer = fresh.rectangles_buffer;
+        self.uploaded_rectangles.clear();
+        self.polygon_vertex_buffer = fresh.polygon_vertex_buffer;
+        self.polygon_index_buffer = fresh.polygon_index_buffer;
+        self.time_buffer = fresh.time_buffer;
+
+        self.texture_settings_buffer = fresh.texture_settings_buffer;
+        for handle in 0..self.texture_settings.len() {
+            self.write_texture_settings(handle);
+        }
+
+        self.samplers = self
+            .samplers
+            .iter()
+            .map(|(options, _)| {
+                (*options, create_sampler(&self.device, *options))
+            })
+            .collect();
+
+        self.uniform_bind_group_layout = fresh.uniform_bind_group_layout;
+        self.uniform_bind_group = create_uniform_bind_group(
+            &self.device,
+            &self.uniform_bind_group_layout,
+            &self.projection_buffer,
+            &self.rectangles_buffer,
+            &self.texture_settings_buffer,
+            &self.time_buffer,
+            &self.samplers,
+        );
+        self.textures_bind_group_layout = fresh.textures_bind_group_layout;
+        self.textures_bind_group = fresh.textures_bind_group;
+        self.empty_texture = fresh.empty_texture;
+
+        // PIPELINES
+        // =========
+
+        // the built-in shader's pipelines stay if the shader doesn't build
+        // on the new device
+        self.render_pipeline_layout = fresh.render_pipeline_layout;
+        self.render_pipeline = fresh.render_pipeline;
+        self.blend_pipelines = fresh.blend_pipelines;
+        self.polygon_pipeline = fresh.polygon_pipeline;
+        self.stencil_pipelines = fresh.stencil_pipelines;
+        self.render_target_pipeline = fresh.render_target_pipeline;
+        self.window_pipelines.clear();
+
+        let main_shader = self.main_shader_source.clone();
+        self.rebuild_render_pipeline(&main_shader);
+
+        if let Some(wgsl) = self.post_process.as_ref().map(|p| p.wgsl.clone()) {
+            if let Err(e) = self.set_post_process_shader(&wgsl) {
+                log::error!("Could not rebuild the post process shader: {}", e);
+                self.post_process = None;
+            }
+        }
+
+        #[cfg(feature = "egui")]
+        if let Some(debug_ui) = &mut self.debug_ui {
+            // egui only sends its textures once, a new context sends them
+            // again
+            debug_ui.context = egui::Context::default();
+            debug_ui.renderer = egui_wgpu::Renderer::new(
+                &self.device,
+                self.config.format,
+                None,
+                1,
+            );
+        }
+
+        for window in self.extra_windows.values_mut() {
+            window.surface.configure(&self.device, &window.config);
+            window.projection_buffer =
+                self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Window Projection Buffer"),
+                    size: 64,
+                    usage: wgpu::BufferUsages::UNIFORM
+                        | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+            window.rectangles_buffer = create_rectangles_buffer(
+                &self.device,
+                "Window Rectangles Buffer",
+                INITIAL_RECTANGLES_CAPACITY,
+            );
+            window.uniform_bind_group = None;
+        }
+
+        // TEXTURES
+        // ========
+
+        let lost_textures = self.recreate_textures();
+
+        self.adapter = Some(adapter);
+        self.request_redraw();
+        self.event_bus.emit(&AppEvent::DeviceRecreated {
+            lost_textures: lost_textures.clone(),
+        });
+
+        Ok(lost_textures)
+    }
+
+    // creates every texture again on the new device, returns the ones whose
+    // contents couldn't be restored
+    fn recreate_textures(&mut self) -> Vec<TextureHandle> {
+        let mut lost_textures = vec![];
+
+        for handle in 0..self.textures.len() {
+            // render targets are created again below
+            if self.render_targets.iter().any(|t| t.texture == handle) {
+                continue;
+            }
+
+            let texture = &mut self.textures[handle];
+            let image = texture
+                .path
+                .as_deref()
+                .and_then(|path| load_image_from_path(path).ok())
+                .filter(|image| texture.format.check_image(image).is_ok());
+
+            // loading textures are filled in when they finish, and ones that
+            // failed to load didn't have anything in them
+            if image.is_none() && matches!(texture.state, TextureState::Ready) {
+                lost_textures.push(handle);
+            }
+
+            let image = image.unwrap_or_else(|| {
+                let (width, height) = texture.size;
+                match texture.format {
+                    TextureFormat::R8Unorm => {
+                        DynamicImage::new_luma8(width, height)
+                    }
+                    _ => DynamicImage::new_rgba8(width, height),
+                }
+            });
+
+            let new_texture = Texture {
+                path: texture.path.take(),
+                state: std::mem::replace(
+                    &mut texture.state,
+                    TextureState::Ready,
+                ),
+                ..create_texture_from_raw_data(
+                    &self.device,
+                    &self.queue,
+                    &image,
+                    texture.format,
+                )
+            };
+            *texture = new_texture;
+            self.current_frame_stats.record_texture_upload();
+        }
+
+        for target in &mut self.render_targets {
+            let (texture, projection_buffer, rectangles_buffer) =
+                create_render_target_resources(
+                    &self.device,
+                    target.size.0,
+                    target.size.1,
+                );
+
+            self.textures[target.texture] = texture;
+            target.projection_buffer = projection_buffer;
+            target.rectangles_buffer = rectangles_buffer;
+            target.needs_render = true;
+        }
+
+        // the glyph caches are empty now, but the glyphs can be rasterized
+        // again
+        #[cfg(feature = "ttf")]
+        for font in &mut self.ttf_fonts {
+            font.clear_glyph_cache();
+            lost_textures.retain(|handle| *handle != font.texture);
+        }
+
+        self.textures_bind_group_dirty = true;
+
+        lost_textures
+    }
+}
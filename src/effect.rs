@@ -0,0 +1,245 @@
+use crate::{
+    atlas::SpriteHandle,
+    context::{Context, RectangleDrawData},
+    geometry::Vec2,
+    shader::build_shader_source,
+};
+
+pub type EffectId = u32;
+
+// the built-in look, effects registered with Context::register_effect start
+// at 1
+pub const DEFAULT_EFFECT: EffectId = 0;
+
+// Renames the snippet's `fn effect` to `effect_{id}`, so several effects
+// can be put into the same shader. Snippets without one are left alone and
+// fail to compile. Comments are skipped, WGSL has no strings so there's
+// nothing else it could be hidden in.
+fn rename_effect_fn(wgsl: &str, id: EffectId) -> String {
+    let bytes = wgsl.as_bytes();
+    let is_ident = |i: usize| {
+        bytes
+            .get(i)
+            .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_')
+    };
+    let skip_whitespace = |mut i: usize| {
+        while bytes.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        i
+    };
+
+    let mut i = 0;
+    // block comments nest in WGSL
+    let mut comment_depth = 0;
+
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+
+        if rest.starts_with(b"/*") {
+            comment_depth += 1;
+            i += 2;
+        } else if comment_depth > 0 && rest.starts_with(b"*/") {
+            comment_depth -= 1;
+            i += 2;
+        } else if comment_depth > 0 {
+            i += 1;
+        } else if rest.starts_with(b"//") {
+            i += rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+        } else if rest.starts_with(b"fn") && (i == 0 || !is_ident(i - 1)) {
+            let name_start = skip_whitespace(i + 2);
+            let name_end = name_start + "effect".len();
+
+            // `fn effect_color(` and similar helpers aren't it
+            if name_start > i + 2
+                && bytes[name_start..].starts_with(b"effect")
+                && !is_ident(name_end)
+                && bytes.get(skip_whitespace(name_end)) == Some(&b'(')
+            {
+                return format!(
+                    "{}effect_{id}{}",
+                    &wgsl[..name_start],
+                    &wgsl[name_end..]
+                );
+            }
+
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    wgsl.to_string()
+}
+
+// The registered effects and apply_effect(), which fs_main calls to pick
+// the rectangle's one. Put after the main shader by build_shader_source.
+pub fn build_effects_source(effects: &[String]) -> String {
+    let mut source = String::new();
+
+    for effect in effects {
+        source.push_str(effect);
+        source.push('\n');
+    }
+
+    // effects are per rectangle like texture indices, see the note about
+    // uniform control flow in build_shader_source. Derivatives only go wrong
+    // on the pixels where two rectangles with different effects meet.
+    source.push_str(
+        "
+fn apply_effect(in: VertexOutput) -> vec4<f32> {
+    switch (in.effect) {
+",
+    );
+    for id in 1..=effects.len() {
+        source.push_str(&format!(
+            "        case {id}u: {{
+            return effect_{id}(in);
+        }}
+"
+        ));
+    }
+    source.push_str(
+        "        default: {
+            return rectangle_color(in);
+        }
+    }
+}
+",
+    );
+
+    source
+}
+
+impl RectangleDrawData {
+    pub fn with_effect(self, effect: EffectId, params: [f32; 4]) -> Self {
+        Self {
+            effect,
+            params,
+            ..self
+        }
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Adds a fragment effect that rectangles can be drawn with by setting
+    /// `RectangleDrawData::effect` to the returned id. Unlike materials all
+    /// effects share one pipeline, so using a lot of them doesn't add draw
+    /// calls. The snippet is put after the main shader and has to define a
+    /// function called `effect`, which usually calls `rectangle_color` (the
+    /// built-in look) with changed inputs or changes what it returns.
+    /// `in.params` is `RectangleDrawData::params`:
+    ///
+    /// ```wgsl
+    /// fn effect(in: VertexOutput) -> vec4<f32> {
+    ///     var wavy = in;
+    ///     wavy.uv.x += sin(in.uv.y * in.params.x + time) * in.params.y;
+    ///     return rectangle_color(wavy);
+    /// }
+    /// ```
+    ///
+    /// Other functions in the snippet need names that no other effect
+    /// uses. The snippet is checked right away, the pipelines are rebuilt
+    /// before the next frame. Ignored with a SPIR-V shader from
+    /// `AppConfig::shader`.
+    pub fn register_effect(
+        &mut self,
+        wgsl: &str,
+    ) -> Result<EffectId, wgpu::Error> {
+        let id = self.effects.len() as EffectId + 1;
+
+        let mut effects = self.effects.clone();
+        effects.push(rename_effect_fn(wgsl, id));
+
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        // only the shader module, the pipelines are rebuilt later
+        self.device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Effect Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    build_shader_source(
+                        &self.main_shader_source,
+                        &effects,
+                        self.texture_binding_mode,
+                        self.push_constants_supported,
+                    )
+                    .into(),
+                ),
+            });
+
+        if let Some(e) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(e);
+        }
+
+        self.effects = effects;
+        self.effects_changed = true;
+
+        Ok(id)
+    }
+
+    /// Like `draw_textured_rect`, but drawn with an effect from
+    /// `register_effect`.
+    pub fn draw_textured_rect_with_effect(
+        &mut self,
+        pos: impl Into<Vec2>,
+        size: impl Into<Vec2>,
+        sprite: impl Into<SpriteHandle>,
+        effect: EffectId,
+        params: [f32; 4],
+    ) {
+        self.draw_textured_rect(pos, size, sprite);
+
+        if let Some(rectangle) = self.rectangles_to_render.last_mut() {
+            *rectangle = rectangle.with_effect(effect, params);
+        }
+    }
+
+    // called before every frame, so registering several effects only
+    // rebuilds the pipelines once
+    pub fn rebuild_effects_if_changed(&mut self) {
+        if self.effects_changed {
+            self.effects_changed = false;
+
+            let main_shader = self.main_shader_source.clone();
+            self.rebuild_render_pipeline(&main_shader);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_the_effect_fn() {
+        assert_eq!(
+            rename_effect_fn("fn effect(in: VertexOutput) {}", 3),
+            "fn effect_3(in: VertexOutput) {}"
+        );
+        assert_eq!(
+            rename_effect_fn("fn  effect\n(in: VertexOutput) {}", 1),
+            "fn  effect_1\n(in: VertexOutput) {}"
+        );
+    }
+
+    #[test]
+    fn skips_helpers_and_comments() {
+        let wgsl = "// fn effect( in a comment
+/* fn effect( /* nested */ fn effect( */
+fn effect_color(c: vec4<f32>) -> vec4<f32> { return c; }
+fn effect(in: VertexOutput) -> vec4<f32> { return effect_color(in.color); }";
+
+        assert_eq!(
+            rename_effect_fn(wgsl, 2),
+            wgsl.replace("fn effect(in", "fn effect_2(in")
+        );
+    }
+
+    #[test]
+    fn needs_a_word_boundary() {
+        let wgsl = "fn notfn effect() {} fn effects() {}";
+        assert_eq!(rename_effect_fn(wgsl, 1), wgsl);
+        assert_eq!(rename_effect_fn("no effect here", 1), "no effect here");
+    }
+}
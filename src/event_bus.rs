@@ -0,0 +1,196 @@
+use std::{any::Any, fmt};
+
+use winit::{
+    dpi::PhysicalPosition,
+    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use crate::{
+    context::{Context, TextureHandle},
+    geometry::Vec2,
+};
+
+// What the context's event bus sends, see Context::event_bus. Positions
+// and sizes are in the units rectangles are positioned in.
+#[derive(Debug)]
+pub enum AppEvent {
+    // the new viewport_size
+    Resized { width: f32, height: f32 },
+    // not sent again while the key is held down
+    KeyPressed(KeyCode),
+    MouseMoved(Vec2),
+    MouseButton { button: MouseButton, pressed: bool },
+    // after Context::recreate_device, with the textures that are empty now
+    DeviceRecreated { lost_textures: Vec<TextureHandle> },
+    // whatever the application sends itself, handlers downcast it
+    Custom(Box<dyn Any>),
+}
+
+type Handler<E> = Box<dyn Fn(&E)>;
+
+// Calls every subscribed handler with each emitted event, in the order
+// they subscribed. Handlers are named so they can be unsubscribed later.
+pub struct EventBus<E> {
+    handlers: Vec<(String, Handler<E>)>,
+}
+
+impl<E> EventBus<E> {
+    pub fn new() -> Self {
+        Self { handlers: vec![] }
+    }
+
+    /// Calls `handler` with every event from now on. Subscribing with an id
+    /// that's already taken replaces that handler.
+    pub fn subscribe(&mut self, id: &str, handler: impl Fn(&E) + 'static) {
+        match self.handlers.iter_mut().find(|(other, _)| other == id) {
+            Some((_, existing)) => *existing = Box::new(handler),
+            None => self.handlers.push((id.to_string(), Box::new(handler))),
+        }
+    }
+
+    /// Returns false if nothing was subscribed with the id.
+    pub fn unsubscribe(&mut self, id: &str) -> bool {
+        let count = self.handlers.len();
+        self.handlers.retain(|(other, _)| other != id);
+
+        self.handlers.len() != count
+    }
+
+    pub fn emit(&self, event: &E) {
+        for (_, handler) in &self.handlers {
+            handler(event);
+        }
+    }
+
+    pub fn handler_count(&self) -> usize {
+        self.handlers.len()
+    }
+}
+
+impl<E> Default for EventBus<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> fmt::Debug for EventBus<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.handlers.iter().map(|(id, _)| id))
+            .finish()
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Window events are sent here by `handle_window_event` as `AppEvent`s,
+    /// so widgets and game systems can react to them without knowing about
+    /// each other or the event loop. Handlers that change state need to
+    /// share it, e.g. through an `Rc<RefCell<_>>`.
+    pub fn event_bus(&self) -> &EventBus<AppEvent> {
+        &self.event_bus
+    }
+
+    pub fn event_bus_mut(&mut self) -> &mut EventBus<AppEvent> {
+        &mut self.event_bus
+    }
+
+    // called by handle_window_event with every event
+    pub fn emit_window_event(&self, event: &WindowEvent) {
+        let app_event = match event {
+            WindowEvent::Resized(size) => {
+                // sent before the context handles the resize, sizes scale
+                // the same way positions do
+                let size = self.window_to_viewport(PhysicalPosition::new(
+                    size.width.into(),
+                    size.height.into(),
+                ));
+                AppEvent::Resized {
+                    width: size.x,
+                    height: size.y,
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => AppEvent::KeyPressed(*code),
+            WindowEvent::CursorMoved { position, .. } => {
+                AppEvent::MouseMoved(self.window_to_viewport(*position))
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                AppEvent::MouseButton {
+                    button: *button,
+                    pressed: *state == ElementState::Pressed,
+                }
+            }
+            _ => return,
+        };
+
+        self.event_bus.emit(&app_event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    // every handler writes its id and the event into `log`
+    fn logging_bus() -> (EventBus<u32>, Rc<RefCell<Vec<String>>>) {
+        (EventBus::new(), Rc::new(RefCell::new(vec![])))
+    }
+
+    fn log_as(
+        log: &Rc<RefCell<Vec<String>>>,
+        id: &'static str,
+    ) -> impl Fn(&u32) + 'static {
+        let log = log.clone();
+        move |event| log.borrow_mut().push(format!("{} {}", id, event))
+    }
+
+    #[test]
+    fn handlers_are_called_in_subscription_order() {
+        let (mut bus, log) = logging_bus();
+        bus.subscribe("b", log_as(&log, "b"));
+        bus.subscribe("a", log_as(&log, "a"));
+
+        bus.emit(&1);
+        bus.emit(&2);
+
+        assert_eq!(*log.borrow(), ["b 1", "a 1", "b 2", "a 2"]);
+    }
+
+    #[test]
+    fn subscribing_again_replaces_the_handler_in_place() {
+        let (mut bus, log) = logging_bus();
+        bus.subscribe("a", log_as(&log, "a"));
+        bus.subscribe("b", log_as(&log, "b"));
+        bus.subscribe("a", log_as(&log, "new a"));
+
+        bus.emit(&1);
+
+        assert_eq!(bus.handler_count(), 2);
+        assert_eq!(*log.borrow(), ["new a 1", "b 1"]);
+    }
+
+    #[test]
+    fn unsubscribe() {
+        let (mut bus, log) = logging_bus();
+        bus.subscribe("a", log_as(&log, "a"));
+        bus.subscribe("b", log_as(&log, "b"));
+
+        assert!(bus.unsubscribe("a"));
+        assert!(!bus.unsubscribe("a"));
+        bus.emit(&1);
+
+        assert_eq!(bus.handler_count(), 1);
+        assert_eq!(*log.borrow(), ["b 1"]);
+    }
+}
@@ -0,0 +1,470 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+};
+
+use crate::{
+    atlas::SpriteHandle,
+    color::Color,
+    context::{Context, RectangleDrawData, TextureError},
+};
+
+pub type FontHandle = usize;
+
+// One entry of the metrics file, in pixels of the atlas
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct GlyphMetrics {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    advance: f32,
+    // how far the top of the glyph is above the baseline, 0 if it's left
+    // out
+    baseline_offset: f32,
+}
+
+#[derive(Debug)]
+struct FontMetrics {
+    line_height: f32,
+    // distance from the top of a line to the baseline
+    baseline: f32,
+    glyphs: BTreeMap<String, GlyphMetrics>,
+}
+
+// The metrics are TOML like the texture manifest, so fonts don't need the
+// serde feature. They're read from the parsed table instead of with a
+// derive for the same reason.
+fn parse_font_metrics(source: &str) -> Result<FontMetrics, String> {
+    let table: toml::Table =
+        toml::from_str(source).map_err(|e| e.to_string())?;
+
+    let glyphs = match table.get("glyphs") {
+        Some(toml::Value::Table(glyphs)) => glyphs,
+        Some(_) => return Err("glyphs has to be a table".to_string()),
+        None => return Err("glyphs is missing".to_string()),
+    };
+
+    let glyphs = glyphs
+        .iter()
+        .map(|(key, glyph)| {
+            let Some(glyph) = glyph.as_table() else {
+                return Err(format!("glyph {:?} has to be a table", key));
+            };
+            let in_glyph = |e| format!("glyph {:?}: {}", key, e);
+
+            let metrics = GlyphMetrics {
+                x: metrics_u32(glyph, "x").map_err(in_glyph)?,
+                y: metrics_u32(glyph, "y").map_err(in_glyph)?,
+                width: metrics_u32(glyph, "width").map_err(in_glyph)?,
+                height: metrics_u32(glyph, "height").map_err(in_glyph)?,
+                advance: metrics_f32(glyph, "advance").map_err(in_glyph)?,
+                baseline_offset: match glyph.contains_key("baseline_offset") {
+                    true => metrics_f32(glyph, "baseline_offset")
+                        .map_err(in_glyph)?,
+                    false => 0.0,
+                },
+            };
+
+            Ok((key.clone(), metrics))
+        })
+        .collect::<Result<_, _>>()?;
+
+    // everything is scaled by size / line_height when it's drawn
+    let line_height = metrics_f32(&table, "line_height")?;
+    if !(line_height > 0.0 && line_height.is_finite()) {
+        return Err("line_height has to be more than 0".to_string());
+    }
+
+    Ok(FontMetrics {
+        line_height,
+        baseline: metrics_f32(&table, "baseline")?,
+        glyphs,
+    })
+}
+
+impl GlyphMetrics {
+    // whether the glyph is inside an atlas of the given size, without
+    // overflowing for huge values
+    fn fits_in(&self, width: u32, height: u32) -> bool {
+        let fits = |start: u32, size: u32, max: u32| {
+            start.checked_add(size).is_some_and(|end| end <= max)
+        };
+
+        fits(self.x, self.width, width) && fits(self.y, self.height, height)
+    }
+}
+
+fn metrics_f32(table: &toml::Table, key: &str) -> Result<f32, String> {
+    match table.get(key) {
+        Some(toml::Value::Float(value)) => Ok(*value as f32),
+        Some(toml::Value::Integer(value)) => Ok(*value as f32),
+        Some(_) => Err(format!("{} has to be a number", key)),
+        None => Err(format!("{} is missing", key)),
+    }
+}
+
+fn metrics_u32(table: &toml::Table, key: &str) -> Result<u32, String> {
+    match table.get(key) {
+        Some(toml::Value::Integer(value)) => u32::try_from(*value)
+            .map_err(|_| format!("{} can't be negative", key)),
+        Some(_) => Err(format!("{} has to be an integer", key)),
+        None => Err(format!("{} is missing", key)),
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Glyph {
+    pub sprite: SpriteHandle,
+    // everything below is in pixels of the atlas, draw_text scales it
+    pub size: [f32; 2],
+    pub advance: f32,
+    pub baseline_offset: f32,
+}
+
+// A font made of pre-rendered glyphs packed into one texture
+pub struct BitmapFont {
+    pub line_height: f32,
+    pub baseline: f32,
+    pub glyphs: HashMap<char, Glyph>,
+}
+
+impl BitmapFont {
+    // how far the pen moves when drawing the text at the given scale
+    fn text_width(&self, text: &str, scale: f32) -> f32 {
+        text.chars()
+            .filter_map(|c| self.glyphs.get(&c))
+            .map(|glyph| glyph.advance * scale)
+            .sum()
+    }
+}
+
+// The size of a line of text, in pixels
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TextMetrics {
+    pub width: f32,
+    // ascent + descent
+    pub height: f32,
+    // from the top of the line to the baseline
+    pub ascent: f32,
+    // from the baseline to the bottom of the line
+    pub descent: f32,
+}
+
+// Splits the text into lines no wider than `max_width`, breaking between
+// words. A word that's wider than `max_width` on its own gets its own line.
+// Newlines always start a new line.
+pub fn wrap_lines(
+    text: &str,
+    max_width: f32,
+    width_of: impl Fn(&str) -> f32,
+) -> Vec<&str> {
+    let mut lines = vec![];
+
+    for paragraph in text.split('\n') {
+        // byte range of the current line in the paragraph
+        let mut line: Option<(usize, usize)> = None;
+
+        for word in paragraph.split_whitespace() {
+            let word_start =
+                word.as_ptr() as usize - paragraph.as_ptr() as usize;
+            let word_end = word_start + word.len();
+
+            line = match line {
+                Some((start, _))
+                    if width_of(&paragraph[start..word_end]) <= max_width =>
+                {
+                    Some((start, word_end))
+                }
+                Some((start, end)) => {
+                    lines.push(&paragraph[start..end]);
+                    Some((word_start, word_end))
+                }
+                None => Some((word_start, word_end)),
+            };
+        }
+
+        lines.push(match line {
+            Some((start, end)) => &paragraph[start..end],
+            None => "",
+        });
+    }
+
+    lines
+}
+
+impl<'a> Context<'a> {
+    /// Loads a font from a glyph atlas and a TOML file (the same format as
+    /// the texture manifest) that says where each glyph is in it, for
+    /// example:
+    ///
+    /// ```toml
+    /// line_height = 16.0
+    /// baseline = 12.0
+    ///
+    /// [glyphs]
+    /// A = { x = 0, y = 0, width = 8, height = 12, advance = 9.0, baseline_offset = 12.0 }
+    /// "." = { x = 8, y = 0, width = 2, height = 2, advance = 4.0, baseline_offset = 2.0 }
+    /// ```
+    pub fn load_bitmap_font(
+        &mut self,
+        atlas_path: &str,
+        metrics_path: &str,
+    ) -> Result<FontHandle, TextureError> {
+        // parse the metrics first so nothing is uploaded for nothing
+        let source = fs::read_to_string(metrics_path)
+            .map_err(TextureError::CouldNotOpenFile)?;
+        let metrics = parse_font_metrics(&source)
+            .map_err(TextureError::InvalidFontMetrics)?;
+
+        let texture = self.create_texture_from_path(atlas_path)?;
+        let (width, height) = self.texture_size(texture).unwrap();
+
+        let mut glyphs = HashMap::new();
+        for (key, glyph) in metrics.glyphs {
+            let mut chars = key.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                return Err(TextureError::InvalidFontMetrics(format!(
+                    "glyph name {:?} isn't a single character",
+                    key
+                )));
+            };
+
+            if !glyph.fits_in(width, height) {
+                return Err(TextureError::InvalidFontMetrics(format!(
+                    "glyph {:?} is outside of the atlas",
+                    key
+                )));
+            }
+
+            glyphs.insert(
+                c,
+                Glyph {
+                    sprite: SpriteHandle {
+                        texture,
+                        uv_pos: [
+                            glyph.x as f32 / width as f32,
+                            glyph.y as f32 / height as f32,
+                        ],
+                        uv_size: [
+                            glyph.width as f32 / width as f32,
+                            glyph.height as f32 / height as f32,
+                        ],
+                    },
+                    size: [glyph.width as f32, glyph.height as f32],
+                    advance: glyph.advance,
+                    baseline_offset: glyph.baseline_offset,
+                },
+            );
+        }
+
+        self.fonts.push(BitmapFont {
+            line_height: metrics.line_height,
+            baseline: metrics.baseline,
+            glyphs,
+        });
+
+        Ok(self.fonts.len() - 1)
+    }
+
+    /// How big `text` is when drawn with `draw_text` at the given size, or
+    /// `None` if the handle is invalid.
+    pub fn measure_text(
+        &self,
+        text: &str,
+        font: FontHandle,
+        size: f32,
+    ) -> Option<TextMetrics> {
+        let font = self.fonts.get(font)?;
+        let scale = size / font.line_height;
+
+        Some(TextMetrics {
+            width: font.text_width(text, scale),
+            height: size,
+            ascent: font.baseline * scale,
+            descent: (font.line_height - font.baseline) * scale,
+        })
+    }
+
+    /// Splits `text` into lines that are at most `max_width` wide when drawn
+    /// with `draw_text` at the given size, by breaking between words. Each
+    /// line can be drawn with its own `draw_text`. `None` if the handle is
+    /// invalid.
+    pub fn measure_text_line_breaks<'t>(
+        &self,
+        text: &'t str,
+        font: FontHandle,
+        size: f32,
+        max_width: f32,
+    ) -> Option<Vec<&'t str>> {
+        let font = self.fonts.get(font)?;
+        let scale = size / font.line_height;
+
+        Some(wrap_lines(text, max_width, |line| {
+            font.text_width(line, scale)
+        }))
+    }
+
+    /// Draws a single line of text with its top left corner at `x`, `y`.
+    /// `size` is the height of the line in pixels. Characters the font
+    /// doesn't have are skipped. The atlas is multiplied with `color`, so
+    /// white glyphs come out in exactly that color.
+    pub fn draw_text(
+        &mut self,
+        text: &str,
+        x: f32,
+        y: f32,
+        size: f32,
+        color: impl Into<Color>,
+        font: FontHandle,
+    ) -> Result<(), TextureError> {
+        let color = color.into();
+        let font = self.fonts.get(font).ok_or(TextureError::InvalidHandle)?;
+        let scale = size / font.line_height;
+
+        let mut pen_x = x;
+        for c in text.chars() {
+            let Some(glyph) = font.glyphs.get(&c) else {
+                continue;
+            };
+
+            // spaces usually have an empty rectangle
+            if glyph.size[0] > 0.0 && glyph.size[1] > 0.0 {
+                self.rectangles_to_render.push(RectangleDrawData {
+                    pos: [
+                        pen_x,
+                        y + (font.baseline - glyph.baseline_offset) * scale,
+                    ],
+                    size: [glyph.size[0] * scale, glyph.size[1] * scale],
+                    color,
+                    texture_index: glyph.sprite.texture as i32,
+                    uv_pos: glyph.sprite.uv_pos,
+                    uv_size: glyph.sprite.uv_size,
+                    ..Default::default()
+                });
+            }
+
+            pen_x += glyph.advance * scale;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every character is 10 pixels wide
+    fn width_of(text: &str) -> f32 {
+        text.chars().count() as f32 * 10.0
+    }
+
+    #[test]
+    fn parses_font_metrics() {
+        let metrics = parse_font_metrics(
+            r#"
+            line_height = 16.0
+            baseline = 12
+
+            [glyphs]
+            A = { x = 0, y = 0, width = 8, height = 12, advance = 9.0, baseline_offset = 12.0 }
+            "." = { x = 8, y = 0, width = 2, height = 2, advance = 4 }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(metrics.line_height, 16.0);
+        assert_eq!(metrics.baseline, 12.0);
+        assert_eq!(
+            metrics.glyphs["A"],
+            GlyphMetrics {
+                x: 0,
+                y: 0,
+                width: 8,
+                height: 12,
+                advance: 9.0,
+                baseline_offset: 12.0,
+            }
+        );
+        assert_eq!(metrics.glyphs["."].advance, 4.0);
+        assert_eq!(metrics.glyphs["."].baseline_offset, 0.0);
+    }
+
+    #[test]
+    fn glyphs_have_to_fit_in_the_atlas() {
+        let glyph = |x, width| GlyphMetrics {
+            x,
+            y: 0,
+            width,
+            height: 4,
+            advance: 0.0,
+            baseline_offset: 0.0,
+        };
+
+        assert!(glyph(12, 4).fits_in(16, 4));
+        assert!(!glyph(13, 4).fits_in(16, 4));
+        assert!(!glyph(0, 4).fits_in(16, 3));
+        // would wrap around to 3 without checking
+        assert!(!glyph(u32::MAX, 4).fits_in(16, 4));
+    }
+
+    #[test]
+    fn rejects_invalid_font_metrics() {
+        let error = |source| parse_font_metrics(source).unwrap_err();
+
+        assert_eq!(
+            error("line_height = 0.0\nbaseline = 12.0\n[glyphs]"),
+            "line_height has to be more than 0"
+        );
+        assert_eq!(
+            error("baseline = 12.0\n[glyphs]"),
+            "line_height is missing"
+        );
+        assert_eq!(
+            error("line_height = 16.0\nbaseline = 12.0"),
+            "glyphs is missing"
+        );
+        assert_eq!(
+            error(
+                "line_height = 16.0\nbaseline = 12.0\n[glyphs]\n\
+                 A = { x = -1, y = 0, width = 8, height = 12, advance = 9.0 }"
+            ),
+            "glyph \"A\": x can't be negative"
+        );
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        let full_width = width_of("hello world");
+
+        assert_eq!(
+            wrap_lines("hello world", full_width - 1.0, width_of),
+            vec!["hello", "world"]
+        );
+        assert_eq!(
+            wrap_lines("hello world", full_width, width_of),
+            vec!["hello world"]
+        );
+    }
+
+    #[test]
+    fn long_word_gets_its_own_line() {
+        assert_eq!(
+            wrap_lines("a extraordinarily long word", 60.0, width_of),
+            vec!["a", "extraordinarily", "long", "word"]
+        );
+    }
+
+    #[test]
+    fn newlines_start_a_new_line() {
+        assert_eq!(
+            wrap_lines("one two\nthree\n\nfour", 1000.0, width_of),
+            vec!["one two", "three", "", "four"]
+        );
+        assert_eq!(
+            wrap_lines("one two\nthree four", 70.0, width_of),
+            vec!["one two", "three", "four"]
+        );
+    }
+}
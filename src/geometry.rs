@@ -0,0 +1,291 @@
+use std::ops::{Add, Mul, Sub};
+
+use crate::context::RectangleDrawData;
+
+// A position or size. Functions that take one also take a plain [f32; 2],
+// and the GPU structs keep using arrays.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2::new(0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn length(self) -> f32 {
+        self.x.hypot(self.y)
+    }
+}
+
+impl From<[f32; 2]> for Vec2 {
+    fn from([x, y]: [f32; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<(f32, f32)> for Vec2 {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Vec2> for [f32; 2] {
+    fn from(vec: Vec2) -> Self {
+        [vec.x, vec.y]
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, factor: f32) -> Vec2 {
+        Vec2::new(self.x * factor, self.y * factor)
+    }
+}
+
+// An axis-aligned rectangle, x and y are its top left corner.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Rect {
+    pub const fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    pub fn from_pos_size(pos: impl Into<Vec2>, size: impl Into<Vec2>) -> Self {
+        let (pos, size) = (pos.into(), size.into());
+        Self::new(pos.x, pos.y, size.x, size.y)
+    }
+
+    pub fn pos(&self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.w, self.h)
+    }
+
+    pub fn center(&self) -> Vec2 {
+        Vec2::new(self.x + self.w / 2.0, self.y + self.h / 2.0)
+    }
+
+    /// Whether the point is inside, for hit testing with the mouse. The
+    /// top and left edges count as inside, the bottom and right ones don't,
+    /// so a point is never inside two rectangles that touch.
+    pub fn contains(&self, point: impl Into<Vec2>) -> bool {
+        let point = point.into();
+
+        point.x >= self.x
+            && point.x < self.x + self.w
+            && point.y >= self.y
+            && point.y < self.y + self.h
+    }
+
+    /// Whether the two overlap. Rectangles that only touch don't.
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.x + other.w
+            && other.x < self.x + self.w
+            && self.y < other.y + other.h
+            && other.y < self.y + self.h
+    }
+}
+
+// where something smaller than the area it's put in ends up, see
+// Context::draw_image_scaled_to_fit
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Alignment {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Alignment {
+    // how much of the free space goes to the left and to the top, 0 to 1
+    fn factors(self) -> (f32, f32) {
+        match self {
+            Alignment::TopLeft => (0.0, 0.0),
+            Alignment::Top => (0.5, 0.0),
+            Alignment::TopRight => (1.0, 0.0),
+            Alignment::Left => (0.0, 0.5),
+            Alignment::Center => (0.5, 0.5),
+            Alignment::Right => (1.0, 0.5),
+            Alignment::BottomLeft => (0.0, 1.0),
+            Alignment::Bottom => (0.5, 1.0),
+            Alignment::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// The largest rectangle with the aspect ratio of `size` that fits into
+/// `bounds`, aligned inside of it. Scales up as well as down.
+pub fn fit_rect(size: impl Into<Vec2>, bounds: Rect, align: Alignment) -> Rect {
+    let size = size.into();
+    if size.x <= 0.0 || size.y <= 0.0 {
+        return Rect::from_pos_size(bounds.pos(), Vec2::ZERO);
+    }
+
+    let scale = (bounds.w / size.x).min(bounds.h / size.y);
+    let (w, h) = (size.x * scale, size.y * scale);
+    let (factor_x, factor_y) = align.factors();
+
+    Rect::new(
+        bounds.x + (bounds.w - w) * factor_x,
+        bounds.y + (bounds.h - h) * factor_y,
+        w,
+        h,
+    )
+}
+
+impl RectangleDrawData {
+    /// Where the rectangle is drawn, e.g. to check if the mouse is over it.
+    pub fn rect(&self) -> Rect {
+        Rect::from_pos_size(self.pos, self.size)
+    }
+
+    pub fn with_rect(self, rect: Rect) -> Self {
+        Self {
+            pos: rect.pos().into(),
+            size: rect.size().into(),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: Rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+
+    #[test]
+    fn rect_center() {
+        assert_eq!(
+            Rect::new(10.0, 20.0, 30.0, 40.0).center(),
+            Vec2::new(25.0, 40.0)
+        );
+        assert_eq!(
+            Rect::new(-4.0, 0.0, 0.0, 0.0).center(),
+            Vec2::new(-4.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn rect_contains_its_top_left_edges_only() {
+        let rect = Rect::new(10.0, 20.0, 30.0, 40.0);
+
+        assert!(rect.contains([10.0, 20.0]));
+        assert!(rect.contains(Vec2::new(25.0, 59.9)));
+        assert!(!rect.contains([40.0, 30.0]));
+        assert!(!rect.contains([20.0, 60.0]));
+        assert!(!rect.contains([9.9, 30.0]));
+
+        // a point on the shared edge is only in the second one
+        let right = Rect::new(40.0, 20.0, 30.0, 40.0);
+        assert!(right.contains([40.0, 30.0]));
+    }
+
+    #[test]
+    fn rect_intersects() {
+        let rect = Rect::new(0.0, 0.0, 10.0, 10.0);
+
+        assert!(rect.intersects(&Rect::new(5.0, 5.0, 10.0, 10.0)));
+        assert!(rect.intersects(&Rect::new(2.0, 2.0, 2.0, 2.0)));
+        assert!(Rect::new(2.0, 2.0, 2.0, 2.0).intersects(&rect));
+        // touching
+        assert!(!rect.intersects(&Rect::new(10.0, 0.0, 10.0, 10.0)));
+        assert!(!rect.intersects(&Rect::new(0.0, 10.0, 10.0, 10.0)));
+        assert!(!rect.intersects(&Rect::new(20.0, 20.0, 5.0, 5.0)));
+    }
+
+    #[test]
+    fn fit_rect_aligns_a_wide_image() {
+        let size = [200.0, 100.0];
+
+        assert_eq!(
+            fit_rect(size, BOUNDS, Alignment::Center),
+            Rect::new(0.0, 25.0, 100.0, 50.0)
+        );
+        assert_eq!(
+            fit_rect(size, BOUNDS, Alignment::TopLeft),
+            Rect::new(0.0, 0.0, 100.0, 50.0)
+        );
+        assert_eq!(
+            fit_rect(size, BOUNDS, Alignment::BottomRight),
+            Rect::new(0.0, 50.0, 100.0, 50.0)
+        );
+    }
+
+    #[test]
+    fn fit_rect_aligns_a_tall_image_in_offset_bounds() {
+        let bounds = Rect::new(10.0, 20.0, 100.0, 100.0);
+        let size = [25.0, 50.0];
+
+        assert_eq!(
+            fit_rect(size, bounds, Alignment::Left),
+            Rect::new(10.0, 20.0, 50.0, 100.0)
+        );
+        assert_eq!(
+            fit_rect(size, bounds, Alignment::Top),
+            Rect::new(35.0, 20.0, 50.0, 100.0)
+        );
+        assert_eq!(
+            fit_rect(size, bounds, Alignment::TopRight),
+            Rect::new(60.0, 20.0, 50.0, 100.0)
+        );
+    }
+
+    #[test]
+    fn fit_rect_with_zero_size() {
+        let bounds = Rect::new(10.0, 20.0, 100.0, 100.0);
+
+        assert_eq!(
+            fit_rect([0.0, 50.0], bounds, Alignment::Center),
+            Rect::new(10.0, 20.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            fit_rect([50.0, -1.0], bounds, Alignment::Center),
+            Rect::new(10.0, 20.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn alignment_factors() {
+        assert_eq!(Alignment::TopLeft.factors(), (0.0, 0.0));
+        assert_eq!(Alignment::Center.factors(), (0.5, 0.5));
+        assert_eq!(Alignment::Bottom.factors(), (0.5, 1.0));
+        assert_eq!(Alignment::BottomRight.factors(), (1.0, 1.0));
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::context::Context;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum GpuTimerState {
+    // the readback buffer is free, the next frame can be measured
+    Idle,
+    // the frame's timestamps are being copied into the readback buffer
+    Copying,
+    // waiting for the readback buffer to be mapped
+    Mapping,
+}
+
+// Measures how long the main render pass takes on the GPU, with a timestamp
+// at the start and the end of it. Only one frame is measured at a time,
+// frames that start while the previous result is still on its way aren't
+// measured.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+
+    // nanoseconds per timestamp tick
+    timestamp_period: f32,
+
+    state: GpuTimerState,
+    // set by the map_async callback, depending on whether it worked
+    mapped: Arc<AtomicBool>,
+    map_failed: Arc<AtomicBool>,
+
+    last_frame_ns: Option<u64>,
+}
+
+const TIMESTAMPS_SIZE: u64 = 2 * std::mem::size_of::<u64>() as u64;
+
+impl GpuTimer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("GPU Timer Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: 2,
+        });
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Timer Resolve Buffer"),
+            size: TIMESTAMPS_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Timer Readback Buffer"),
+            size: TIMESTAMPS_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            timestamp_period: queue.get_timestamp_period(),
+            state: GpuTimerState::Idle,
+            mapped: Arc::new(AtomicBool::new(false)),
+            map_failed: Arc::new(AtomicBool::new(false)),
+            last_frame_ns: None,
+        }
+    }
+
+    // whether the frame that's being encoded gets measured
+    pub fn start_measurement(&mut self) -> bool {
+        if self.state != GpuTimerState::Idle {
+            return false;
+        }
+
+        self.state = GpuTimerState::Copying;
+        true
+    }
+
+    pub fn timestamp_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1),
+        }
+    }
+
+    // after the render pass
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(
+            &self.query_set,
+            0..2,
+            &self.resolve_buffer,
+            0,
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            TIMESTAMPS_SIZE,
+        );
+    }
+
+    // after the frame was submitted
+    pub fn after_submit(&mut self) {
+        if self.state != GpuTimerState::Copying {
+            return;
+        }
+        self.state = GpuTimerState::Mapping;
+
+        let mapped = self.mapped.clone();
+        let map_failed = self.map_failed.clone();
+        self.readback_buffer.slice(..).map_async(
+            wgpu::MapMode::Read,
+            move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(_) => map_failed.store(true, Ordering::Release),
+            },
+        );
+    }
+
+    // reads the result if it's there. The device has to have been polled
+    // since the map_async.
+    pub fn read_result(&mut self) {
+        if self.state != GpuTimerState::Mapping {
+            return;
+        }
+
+        // the buffer isn't mapped, so the next frame can use it right away
+        if self.map_failed.swap(false, Ordering::Acquire) {
+            log::warn!("Could not read the GPU timestamps");
+            self.state = GpuTimerState::Idle;
+            return;
+        }
+
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return;
+        }
+
+        {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+
+            let ticks = timestamps[1].saturating_sub(timestamps[0]);
+            self.last_frame_ns =
+                Some((ticks as f64 * self.timestamp_period as f64) as u64);
+        }
+
+        self.readback_buffer.unmap();
+        self.state = GpuTimerState::Idle;
+    }
+}
+
+impl<'a> Context<'a> {
+    /// How long the main render pass of a recent frame took on the GPU, in
+    /// nanoseconds. Needs `AppConfig::enable_gpu_timestamps` and an adapter
+    /// that supports timestamp queries. The results arrive a few frames
+    /// late, so this is `None` for the first few frames.
+    pub fn last_gpu_frame_ns(&self) -> Option<u64> {
+        self.gpu_timer.as_ref()?.last_frame_ns
+    }
+
+    // picks up the timer result, if one arrived
+    pub fn poll_gpu_timer(&mut self) {
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            self.device.poll(wgpu::Maintain::Poll);
+            gpu_timer.read_result();
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+use winit::dpi::PhysicalSize;
+
+use crate::{config::AppConfig, context::Context};
+
+// what headless contexts draw into, the same format a window's surface
+// would usually have
+pub const HEADLESS_FORMAT: wgpu::TextureFormat =
+    wgpu::TextureFormat::Rgba8UnormSrgb;
+
+#[derive(Debug)]
+pub enum ContextError {
+    NoAdapter,
+    // the shader samples each texture with different samplers, which GL
+    // can't do
+    UnsupportedBackend(wgpu::Backend),
+    RequestDevice(wgpu::RequestDeviceError),
+    // the shader from AppConfig::shader doesn't compile or doesn't fit the
+    // pipelines
+    InvalidShader(wgpu::Error),
+    // Context::recreate_device needs the window's surface, contexts without
+    // one can't recreate their device
+    NoSurface,
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextError::NoAdapter => write!(f, "No suitable GPU adapter."),
+            ContextError::UnsupportedBackend(backend) => {
+                write!(f, "The {:?} backend isn't supported.", backend)
+            }
+            ContextError::RequestDevice(e) => {
+                write!(f, "Could not create the device: {}", e)
+            }
+            ContextError::InvalidShader(e) => {
+                write!(f, "The shader is invalid: {}", e)
+            }
+            ContextError::NoSurface => {
+                write!(f, "Only contexts with a window can do this.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ContextError {}
+
+// Where frames end up: the window's surface, or a texture for contexts
+// without a window.
+pub enum FrameTarget<'a> {
+    Surface(wgpu::Surface<'a>),
+    Headless(wgpu::Texture),
+}
+
+// recreated on resize, like the depth buffer. It can be copied from, so
+// the frame can be read back.
+pub fn create_headless_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Headless Texture"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+impl Context<'static> {
+    /// A context without a window, for tests and CI. Frames are drawn into
+    /// a `width` x `height` texture (see `headless_texture`) instead of a
+    /// surface, everything else works the same. Everything that would
+    /// change the window (the cursor, size constraints, redraw requests)
+    /// does nothing. Falls back to a software adapter if there's no GPU
+    /// that works, so it also runs on CI machines. `capture_frame` reads
+    /// the frame back as an image.
+    pub fn build_headless(
+        width: u32,
+        height: u32,
+    ) -> Result<Context<'static>, ContextError> {
+        Self::build_headless_with_config(width, height, &AppConfig::default())
+    }
+
+    /// Same as `build_headless`, with the settings that don't have to do
+    /// with the window taken from `config`.
+    pub fn build_headless_with_config(
+        width: u32,
+        height: u32,
+        config: &AppConfig,
+    ) -> Result<Context<'static>, ContextError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::VULKAN
+                | wgpu::Backends::METAL
+                | wgpu::Backends::DX12
+                | wgpu::Backends::GL,
+            ..Default::default()
+        });
+
+        let request_adapter = |force_fallback_adapter| {
+            pollster::block_on(instance.request_adapter(
+                &wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    compatible_surface: None,
+                    force_fallback_adapter,
+                },
+            ))
+        };
+        let is_supported = |adapter: &wgpu::Adapter| {
+            adapter.get_info().backend != wgpu::Backend::Gl
+        };
+
+        // CI machines often have no GPU, or only GL. Software renderers
+        // (lavapipe, WARP) are only handed out when asking for the fallback
+        // adapter.
+        let adapter = match request_adapter(false) {
+            Some(adapter) if is_supported(&adapter) => adapter,
+            adapter => match request_adapter(true) {
+                Some(fallback) if is_supported(&fallback) => fallback,
+                _ => match adapter {
+                    Some(adapter) => {
+                        return Err(ContextError::UnsupportedBackend(
+                            adapter.get_info().backend,
+                        ))
+                    }
+                    None => return Err(ContextError::NoAdapter),
+                },
+            },
+        };
+
+        Context::from_adapter(
+            &adapter,
+            None,
+            None,
+            PhysicalSize::new(width.max(1), height.max(1)),
+            1.0,
+            config,
+        )
+    }
+}
+
+impl<'a> Context<'a> {
+    /// The texture a headless context draws into, `None` for contexts with
+    /// a window.
+    pub fn headless_texture(&self) -> Option<&wgpu::Texture> {
+        match &self.frame_target {
+            FrameTarget::Headless(texture) => Some(texture),
+            FrameTarget::Surface(_) => None,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    path::Path,
+    sync::mpsc::{self, Receiver},
+};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::context::Context;
+
+// Watches a shader file on a background thread and sends its new contents
+// every time it's modified.
+pub struct ShaderWatcher {
+    // stops watching when dropped
+    _watcher: RecommendedWatcher,
+    receiver: Receiver<String>,
+}
+
+impl ShaderWatcher {
+    pub fn new(path: &str) -> notify::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+
+        let watched_path = path.to_string();
+        let mut watcher = notify::recommended_watcher(
+            move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+
+                if !event.kind.is_modify() && !event.kind.is_create() {
+                    return;
+                }
+
+                // editors sometimes truncate the file first, skip that
+                match std::fs::read_to_string(&watched_path) {
+                    Ok(source) if !source.is_empty() => {
+                        let _ = sender.send(source);
+                    }
+                    _ => {}
+                }
+            },
+        )?;
+
+        watcher.watch(Path::new(path), RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            receiver,
+        })
+    }
+
+    // the newest version of the shader since the last call, if it changed
+    pub fn latest_source(&self) -> Option<String> {
+        self.receiver.try_iter().last()
+    }
+}
+
+impl<'a> Context<'a> {
+    pub fn reload_shader_if_changed(&mut self) {
+        let Some(source) = self
+            .shader_watcher
+            .as_ref()
+            .and_then(|watcher| watcher.latest_source())
+        else {
+            return;
+        };
+
+        log::info!("Shader changed, rebuilding render pipeline");
+        self.rebuild_render_pipeline(&source);
+    }
+}
@@ -1,24 +1,122 @@
+use std::time::Instant;
+
 use winit::{
     application::ApplicationHandler,
+    dpi::PhysicalSize,
     event::*,
-    event_loop::EventLoop,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
     keyboard::{KeyCode, PhysicalKey},
-    window::WindowAttributes,
+    window::{Window, WindowAttributes},
 };
 
+pub mod animation;
+pub mod atlas;
+pub mod bezier;
+pub mod blend;
+pub mod camera;
+pub mod capture;
+pub mod clip;
+pub mod color;
+pub mod config;
 pub mod context;
+pub mod culling;
+#[cfg(feature = "egui")]
+pub mod debug_ui;
+pub mod depth;
+pub mod device_lost;
+pub mod effect;
+pub mod event_bus;
+pub mod font;
+pub mod geometry;
+pub mod gpu_timer;
+pub mod headless;
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+pub mod hot_reload;
+pub mod loader;
+pub mod mask;
+pub mod material;
+pub mod msaa;
+pub mod multi_window;
+pub mod nine_slice;
+pub mod particles;
+pub mod polygon;
+pub mod post_process;
+pub mod present;
+pub mod registry;
+pub mod render_graph;
+pub mod render_target;
+pub mod scene;
+#[cfg(feature = "serde")]
+pub mod scene_file;
+mod shader;
+pub mod stats;
+pub mod stencil;
+#[cfg(feature = "svg")]
+pub mod svg;
+pub mod texture_watch;
+pub mod tilemap;
+pub mod timing;
+#[cfg(feature = "ttf")]
+pub mod ttf;
+pub mod tween;
 
-use crate::context::Context;
+use crate::{
+    config::AppConfig,
+    context::{Context, RedrawMode},
+    loader::TEXTURE_LOAD_POLL_INTERVAL,
+};
 
+// Context can be handed to EventLoop::run_app directly, which is what run()
+// does. Applications that want their own ApplicationHandler can forward the
+// events to handle_new_events and handle_window_event instead, see
+// examples/custom_loop.rs.
 impl<'a> ApplicationHandler for Context<'a> {
-    fn resumed(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {}
+    fn resumed(&mut self, _event_loop: &ActiveEventLoop) {}
+
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        self.handle_new_events(cause);
+    }
 
     fn window_event(
         &mut self,
-        event_loop: &winit::event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        event_loop: &ActiveEventLoop,
+        window_id: winit::window::WindowId,
+        event: WindowEvent,
+    ) {
+        if self.is_extra_window(window_id) {
+            self.handle_extra_window_event(window_id, event);
+        } else {
+            self.handle_window_event(event_loop, event);
+        }
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Has to be called from `ApplicationHandler::new_events` when driving
+    /// the context from your own event loop, the frame rate limiter relies
+    /// on it.
+    pub fn handle_new_events(&mut self, cause: StartCause) {
+        // the wait set up by the frame rate limiter is over
+        if let StartCause::ResumeTimeReached { .. } = cause {
+            self.request_redraw();
+        }
+
+        self.request_redraw_if_changed();
+    }
+
+    /// Handles resizing, redrawing and closing the window. Has to be called
+    /// from `ApplicationHandler::window_event` when driving the context from
+    /// your own event loop.
+    pub fn handle_window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
         event: WindowEvent,
     ) {
+        #[cfg(feature = "egui")]
+        self.handle_debug_ui_event(&event);
+        self.handle_camera_event(&event);
+        self.emit_window_event(&event);
+
         match event {
             // close on escape or when it's requested
             WindowEvent::CloseRequested
@@ -34,21 +132,96 @@ impl<'a> ApplicationHandler for Context<'a> {
 
             // handle window resizing
             WindowEvent::Resized(physical_size) => {
+                self.sync_fullscreen();
                 self.resize(physical_size);
             }
 
+            // the window moved to a monitor with a different DPI, keep the
+            // same logical size
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                mut inner_size_writer,
+            } => {
+                let logical_size =
+                    self.size.to_logical::<f64>(self.scale_factor);
+                let new_size = logical_size.to_physical::<u32>(scale_factor);
+
+                self.scale_factor = scale_factor;
+
+                if inner_size_writer.request_inner_size(new_size).is_err() {
+                    log::warn!("Could not request new inner size");
+                }
+
+                self.resize(new_size);
+            }
+
             WindowEvent::RedrawRequested => {
-                self.window.request_redraw();
+                // the GPU was reset or switched, everything on it has to be
+                // created again
+                if self.device_lost() {
+                    if let Err(e) = self.recreate_device() {
+                        log::error!("Could not recreate the device: {}", e);
+                        event_loop.exit();
+                        return;
+                    }
+                }
+
+                // the surface kept failing, wait before the next try
+                // instead of spinning on it. handle_new_events asks for the
+                // frame again once it's time.
+                if let Some(retry_at) = self.surface_retry_at() {
+                    event_loop
+                        .set_control_flow(ControlFlow::WaitUntil(retry_at));
+                    return;
+                }
+
+                self.wait_for_frame_deadline();
+
+                self.last_redraw_instant = Instant::now();
+                self.frame_timer.record_frame(self.last_redraw_instant);
 
-                self.update();
+                // with a target fps, wait until the frame's time is up
+                // before asking for the next one, otherwise go as fast as
+                // possible. On demand the next frame only comes when
+                // Context::request_redraw is called.
+                match self.target_frame_duration {
+                    // textures that finished loading are only uploaded in
+                    // update(), so keep checking while some are loading
+                    _ if self.redraw_mode == RedrawMode::OnDemand => {
+                        event_loop.set_control_flow(
+                            match self.textures_loading() {
+                                true => ControlFlow::WaitUntil(
+                                    self.last_redraw_instant
+                                        + TEXTURE_LOAD_POLL_INTERVAL,
+                                ),
+                                false => ControlFlow::Wait,
+                            },
+                        );
+                    }
+                    Some(frame_duration) => {
+                        let wake_up = self.schedule_next_frame(frame_duration);
+                        event_loop
+                            .set_control_flow(ControlFlow::WaitUntil(wake_up));
+                    }
+                    None => {
+                        event_loop.set_control_flow(ControlFlow::Wait);
+                        self.request_redraw();
+                    }
+                }
+
+                let dt = self.delta_time();
+                self.update(dt);
+                self.run_fixed_updates(dt);
+                self.run_frame_callback(dt);
 
                 match self.render() {
                     Ok(_) => {}
 
-                    // Reconfigure the surface if it's lost or outdated
+                    // Reconfigure the surface if it's lost or outdated, the
+                    // size is still the same so resizing isn't needed
                     Err(
                         wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated,
-                    ) => self.resize(self.size),
+                    ) => self.reconfigure_surface(),
 
                     // The system is out of memory, should probably quit
                     Err(wgpu::SurfaceError::OutOfMemory) => {
@@ -64,23 +237,79 @@ impl<'a> ApplicationHandler for Context<'a> {
             }
             _ => {}
         }
+
+        self.request_redraw_if_changed();
     }
 }
 
 pub fn run() {
+    run_with_config(AppConfig::default());
+}
+
+/// Same as `run`, but the window is created with the given attributes, e.g.
+/// `Window::default_attributes().with_title("My game")`.
+pub fn run_with_attributes(attributes: WindowAttributes) {
+    run_with_config(AppConfig {
+        window_attributes: attributes,
+        ..Default::default()
+    });
+}
+
+pub fn run_with_config(config: AppConfig) {
     let event_loop = EventLoop::new().unwrap();
 
+    let window = create_window(&event_loop, &config);
+
+    let mut context = Context::new(&window, &config);
+
+    event_loop.run_app(&mut context).unwrap();
+}
+
+/// Opens a window and runs the event loop with your own code. `setup` is
+/// called once with the context, e.g. to load textures, and returns the
+/// state that's passed to `frame`. `frame` is called before every frame is
+/// drawn with the seconds since the last one, and draws it (the rectangles
+/// from the last frame are still there, clear them first to start over).
+pub fn run_with<S>(
+    config: AppConfig,
+    setup: impl FnOnce(&mut Context) -> S,
+    mut frame: impl FnMut(&mut Context, &mut S, f32),
+) {
+    let event_loop = EventLoop::new().unwrap();
+
+    let window = create_window(&event_loop, &config);
+
+    let mut context = Context::new(&window, &config);
+
+    let mut state = setup(&mut context);
+    context.set_frame_callback(move |context, dt| {
+        frame(context, &mut state, dt);
+    });
+
+    event_loop.run_app(&mut context).unwrap();
+}
+
+/// Creates a window with the attributes and size constraints from the
+/// config. Use this together with `Context::new` to run the event loop
+/// yourself.
+pub fn create_window<T>(
+    event_loop: &EventLoop<T>,
+    config: &AppConfig,
+) -> Window {
+    let mut window_attributes = config.window_attributes.clone();
+    if let Some((width, height)) = config.min_size {
+        window_attributes = window_attributes
+            .with_min_inner_size(PhysicalSize::new(width, height));
+    }
+    if let Some((width, height)) = config.max_size {
+        window_attributes = window_attributes
+            .with_max_inner_size(PhysicalSize::new(width, height));
+    }
+
     // this is supposed to be done in ApplicationHandler::resumed because on
     // some platforms, like Android, you need to handle suspend and resume
     // events by recreating your entire graphics context. I only care about
     // desktop platforms so that doesn't matter to me.
     #[allow(deprecated)]
-    let window = event_loop
-        .create_window(WindowAttributes::default())
-        .unwrap();
-
-    let mut context = Context::new(&window);
-    context.create_texture_from_path("res/one.png").unwrap();
-
-    event_loop.run_app(&mut context).unwrap();
+    event_loop.create_window(window_attributes).unwrap()
 }
@@ -0,0 +1,104 @@
+use std::{thread, time::Duration};
+
+use image::DynamicImage;
+
+use crate::context::{
+    create_texture_from_raw_data, Context, TextureError, TextureFormat,
+    TextureHandle,
+};
+
+// how often finished loads are checked for with RedrawMode::OnDemand, which
+// otherwise wouldn't draw a frame (and pick them up) until something else
+// happens
+pub const TEXTURE_LOAD_POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+#[derive(Debug)]
+pub enum TextureState {
+    // still being decoded, the texture is a 1x1 transparent placeholder
+    Loading,
+    Ready,
+    Failed(TextureError),
+}
+
+// sent from the decoding threads back to the Context
+pub type TextureLoadResult =
+    (TextureHandle, Result<DynamicImage, TextureError>);
+
+pub fn load_image_from_path(path: &str) -> Result<DynamicImage, TextureError> {
+    let img = image::io::Reader::open(path)
+        .map_err(TextureError::CouldNotOpenFile)?;
+
+    img.decode().map_err(TextureError::CouldNotDecode)
+}
+
+impl<'a> Context<'a> {
+    /// Returns a handle right away and decodes the image on a background
+    /// thread. Until it's uploaded (at the start of an `update()` after
+    /// decoding finishes) the texture is an empty placeholder, so it can
+    /// already be used for drawing. Use `texture_state` to check progress.
+    pub fn load_texture_async(
+        &mut self,
+        path: &str,
+    ) -> Result<TextureHandle, TextureError> {
+        let handle =
+            self.create_texture_from_raw_data(&DynamicImage::new_rgba8(1, 1))?;
+
+        let texture = &mut self.textures[handle];
+        texture.path = Some(path.to_string());
+        texture.state = TextureState::Loading;
+
+        // if the context is dropped before this finishes, sending just
+        // fails and the result is thrown away
+        let sender = self.texture_load_sender.clone();
+        let path = path.to_string();
+        thread::spawn(move || {
+            let _ = sender.send((handle, load_image_from_path(&path)));
+        });
+
+        Ok(handle)
+    }
+
+    /// Whether the texture is still loading, or `None` if the handle is
+    /// invalid.
+    pub fn texture_state(
+        &self,
+        handle: TextureHandle,
+    ) -> Option<&TextureState> {
+        self.textures.get(handle).map(|texture| &texture.state)
+    }
+
+    /// Whether any texture from `load_texture_async` is still loading.
+    pub fn textures_loading(&self) -> bool {
+        self.textures
+            .iter()
+            .any(|texture| matches!(texture.state, TextureState::Loading))
+    }
+
+    // uploads all the textures that finished decoding since the last call
+    pub fn finish_texture_loads(&mut self) {
+        while let Ok((handle, result)) = self.texture_load_receiver.try_recv() {
+            match result {
+                Ok(data) => {
+                    let path = self.textures[handle].path.take();
+
+                    let mut texture = create_texture_from_raw_data(
+                        &self.device,
+                        &self.queue,
+                        &data,
+                        TextureFormat::default(),
+                    );
+                    texture.path = path;
+
+                    self.textures[handle] = texture;
+                    self.current_frame_stats.record_texture_upload();
+                    self.textures_bind_group_dirty = true;
+                }
+                Err(e) => {
+                    log::warn!("Could not load texture {}: {}", handle, e);
+
+                    self.textures[handle].state = TextureState::Failed(e);
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,38 @@
-use anis::run;
+use anis::{
+    color::Color, config::AppConfig, context::RectangleDrawData, run_with,
+};
 
 fn main() {
     env_logger::init();
 
-    run();
+    run_with(
+        AppConfig::default(),
+        |context| {
+            let texture =
+                context.create_texture_from_path("res/one.png").unwrap();
+
+            context.set_rectangles(vec![
+                RectangleDrawData {
+                    pos: [10.0, 10.0],
+                    size: [100.0, 100.0],
+                    color: Color::WHITE,
+                    ..Default::default()
+                },
+                RectangleDrawData {
+                    pos: [120.0, 20.0],
+                    size: [100.0, 100.0],
+                    color: Color::BLACK,
+                    texture_index: texture as i32,
+                    ..Default::default()
+                },
+                RectangleDrawData {
+                    pos: [230.0, 50.0],
+                    size: [100.0, 150.0],
+                    color: Color::rgb(0.4, 0.3, 0.3),
+                    ..Default::default()
+                },
+            ]);
+        },
+        |_, _, _| {},
+    );
 }
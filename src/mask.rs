@@ -0,0 +1,50 @@
+use crate::{
+    context::{Context, RectangleDrawData},
+    geometry::Rect,
+};
+
+impl RectangleDrawData {
+    /// Cuts the rectangle to `mask` with corners of `radius`, with smooth
+    /// edges like rounded corners. Works the same for textured rectangles.
+    pub fn with_mask(self, mask: Rect, radius: f32) -> Self {
+        Self {
+            mask_pos: mask.pos().into(),
+            mask_size: mask.size().into(),
+            mask_radius: radius,
+            ..self
+        }
+    }
+
+    pub fn is_masked(&self) -> bool {
+        self.mask_size[0] > 0.0 && self.mask_size[1] > 0.0
+    }
+}
+
+impl<'a> Context<'a> {
+    /// The rectangles `draw` draws (not polygons) only show up inside
+    /// `mask` with corners of `radius`, e.g. an avatar cut to a circle by
+    /// using half its size as the radius. Nested masks don't combine, the
+    /// inner one wins.
+    ///
+    /// Clip rects are free since the GPU doesn't even run the shader outside
+    /// of them, but they're only rectangles and split the draw call. Masks
+    /// don't split anything and work with render targets, but every pixel
+    /// of a masked rectangle is still shaded and then faded out, so a big
+    /// image masked to a small circle costs as much as the whole image.
+    pub fn draw_masked(
+        &mut self,
+        mask: Rect,
+        radius: f32,
+        draw: impl FnOnce(&mut Self),
+    ) {
+        let start = self.rectangles_to_render.len();
+
+        draw(self);
+
+        for rectangle in self.rectangles_to_render.iter_mut().skip(start) {
+            if !rectangle.is_masked() {
+                *rectangle = rectangle.with_mask(mask, radius);
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::{fmt, ops::Range};
+
+use crate::{
+    context::{create_render_pipeline, Context, RectangleDrawData},
+    depth::depth_stencil_state,
+    render_target::RENDER_TARGET_FORMAT,
+    shader::build_shader_source,
+    stencil::StencilMode,
+};
+
+pub type MaterialHandle = u16;
+
+// drawn by the built-in fs_main, materials created with
+// Context::create_material start at 1
+pub const DEFAULT_MATERIAL: MaterialHandle = 0;
+
+#[derive(Debug)]
+pub enum MaterialError {
+    // every MaterialHandle is taken
+    TooManyMaterials,
+    InvalidShader(wgpu::Error),
+}
+
+impl fmt::Display for MaterialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialError::TooManyMaterials => {
+                write!(
+                    f,
+                    "No more than {} materials can be created.",
+                    MaterialHandle::MAX
+                )
+            }
+            MaterialError::InvalidShader(e) => {
+                write!(f, "The material shader is invalid: {}", e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MaterialError {}
+
+// the handle the next material gets when there are `count` already, None
+// if it doesn't fit into a MaterialHandle anymore
+fn next_material_handle(count: usize) -> Option<MaterialHandle> {
+    MaterialHandle::try_from(count + 1).ok()
+}
+
+// A fragment shader that replaces the built-in one for the rectangles that
+// use it. It's compiled together with the main shader, so it has the same
+// bindings and helpers.
+pub struct Material {
+    pub wgsl: String,
+    pub pipeline: wgpu::RenderPipeline,
+    // render targets have a different format than the surface
+    pub render_target_pipeline: wgpu::RenderPipeline,
+}
+
+// Splits the rectangles into runs that use the same material, so each run
+// can be drawn with one draw call. The ranges start at `offset`.
+pub fn material_runs(
+    rectangles: &[RectangleDrawData],
+    offset: usize,
+) -> impl Iterator<Item = (Range<usize>, MaterialHandle)> + '_ {
+    let mut start = 0;
+
+    std::iter::from_fn(move || {
+        let material = rectangles.get(start)?.material;
+        let end = rectangles[start..]
+            .iter()
+            .position(|rectangle| rectangle.material != material)
+            .map_or(rectangles.len(), |length| start + length);
+
+        let run = (offset + start..offset + end, material);
+        start = end;
+        Some(run)
+    })
+}
+
+// the pipeline for the material, or `default_pipeline` (the built-in
+// shader's) for DEFAULT_MATERIAL and invalid handles
+pub fn material_pipeline<'p>(
+    materials: &'p [Material],
+    default_pipeline: &'p wgpu::RenderPipeline,
+    material: MaterialHandle,
+    render_target: bool,
+) -> &'p wgpu::RenderPipeline {
+    let material = (material as usize)
+        .checked_sub(1)
+        .and_then(|index| materials.get(index));
+
+    match (material, render_target) {
+        (Some(material), false) => &material.pipeline,
+        (Some(material), true) => &material.render_target_pipeline,
+        (None, _) => default_pipeline,
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Compiles a fragment shader that rectangles can be drawn with instead
+    /// of the built-in one, by setting `RectangleDrawData::material` to the
+    /// returned handle. The shader is put after the main shader, so it has
+    /// the projection, textures, `sample_texture`, `time` and `VertexOutput`
+    /// available, and has to define its entry point like this:
+    ///
+    /// ```wgsl
+    /// @fragment
+    /// fn fs_material(in: VertexOutput) -> @location(0) vec4<f32> {
+    ///     return vec4<f32>(in.uv, 0.0, 1.0);
+    /// }
+    /// ```
+    ///
+    /// Rectangles drawn between `begin_stencil_test` and `end_stencil_test`
+    /// always use the built-in shader. Fails with
+    /// `MaterialError::TooManyMaterials` once every handle is taken.
+    pub fn create_material(
+        &mut self,
+        wgsl: &str,
+    ) -> Result<MaterialHandle, MaterialError> {
+        let handle = next_material_handle(self.materials.len())
+            .ok_or(MaterialError::TooManyMaterials)?;
+
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let material = self.build_material(&self.main_shader_source, wgsl);
+
+        if let Some(e) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(MaterialError::InvalidShader(e));
+        }
+
+        self.materials.push(material);
+
+        Ok(handle)
+    }
+
+    // errors end up in the device's error scope
+    pub fn build_material(&self, main_shader: &str, wgsl: &str) -> Material {
+        let shader =
+            self.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("Material Shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        format!(
+                            "{}\n{}",
+                            build_shader_source(
+                                main_shader,
+                                &self.effects,
+                                self.texture_binding_mode,
+                                self.push_constants_supported,
+                            ),
+                            wgsl
+                        )
+                        .into(),
+                    ),
+                });
+
+        Material {
+            wgsl: wgsl.to_string(),
+            pipeline: create_render_pipeline(
+                &self.device,
+                &self.render_pipeline_layout,
+                &shader,
+                "fs_material",
+                self.config.format,
+                wgpu::ColorWrites::ALL,
+                wgpu::BlendState::ALPHA_BLENDING,
+                self.depth_buffer.as_ref().map(|_| {
+                    depth_stencil_state(self.depth_test, StencilMode::None)
+                }),
+                self.sample_count,
+            ),
+            render_target_pipeline: create_render_pipeline(
+                &self.device,
+                &self.render_pipeline_layout,
+                &shader,
+                "fs_material",
+                RENDER_TARGET_FORMAT,
+                wgpu::ColorWrites::ALL,
+                wgpu::BlendState::ALPHA_BLENDING,
+                None,
+                1,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn material_handles_dont_wrap_around() {
+        assert_eq!(next_material_handle(0), Some(1));
+        assert_eq!(
+            next_material_handle(MaterialHandle::MAX as usize - 1),
+            Some(MaterialHandle::MAX)
+        );
+        // would be DEFAULT_MATERIAL again
+        assert_eq!(next_material_handle(MaterialHandle::MAX as usize), None);
+    }
+}
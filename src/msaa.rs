@@ -0,0 +1,106 @@
+use crate::{context::Context, depth::DepthBuffer};
+
+// With multisampling the main pass draws into this texture instead of the
+// frame, and it's resolved into the frame at the end of the pass. Render
+// targets are always drawn without it.
+pub struct MsaaTexture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl MsaaTexture {
+    // like the depth buffer, this has to be created again whenever the
+    // window is resized
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            label: Some("MSAA texture"),
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self { texture, view }
+    }
+}
+
+// The highest of 4 and 2 that isn't above `requested` and that `flags`
+// (the features of every format that gets multisampled, combined) allow,
+// otherwise 1.
+pub fn supported_sample_count(
+    flags: wgpu::TextureFormatFeatureFlags,
+    requested: u32,
+) -> u32 {
+    if !flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE) {
+        return 1;
+    }
+
+    [4, 2]
+        .into_iter()
+        .find(|&count| {
+            count <= requested && flags.sample_count_supported(count)
+        })
+        .unwrap_or(1)
+}
+
+impl<'a> Context<'a> {
+    /// Switches multisampling to `sample_count` samples per pixel (1 turns
+    /// it off), recreating the pipelines. Falls back to a lower count if
+    /// the adapter doesn't support it, the count that's used is returned.
+    pub fn set_sample_count(&mut self, sample_count: u32) -> u32 {
+        let sample_count =
+            supported_sample_count(self.sample_count_flags, sample_count);
+        if sample_count == self.sample_count {
+            return sample_count;
+        }
+        self.sample_count = sample_count;
+
+        if sample_count > 1 && self.pre_render_hook.is_some() {
+            log::warn!("The pre render hook isn't run with multisampling");
+        }
+
+        self.msaa_texture = (sample_count > 1).then(|| {
+            MsaaTexture::new(
+                &self.device,
+                self.config.format,
+                self.config.width,
+                self.config.height,
+                sample_count,
+            )
+        });
+
+        // the depth buffer has to have the same sample count
+        if self.depth_buffer.is_some() {
+            self.depth_buffer = Some(DepthBuffer::new(
+                &self.device,
+                self.config.width,
+                self.config.height,
+                sample_count,
+            ));
+        }
+
+        let main_shader = self.main_shader_source.clone();
+        self.rebuild_render_pipeline(&main_shader);
+
+        sample_count
+    }
+
+    /// Samples per pixel the window is drawn with, 1 without multisampling.
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+}
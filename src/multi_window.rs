@@ -0,0 +1,434 @@
+use std::fmt;
+
+use winit::{
+    dpi::PhysicalSize,
+    event::WindowEvent,
+    window::{Window, WindowId},
+};
+
+use crate::{
+    color::Color,
+    context::{
+        create_rectangles_buffer, create_render_pipeline,
+        create_uniform_bind_group, grow_rectangles_buffer, Context,
+        RectangleDrawData, INITIAL_RECTANGLES_CAPACITY,
+    },
+    shader::create_main_shader_module,
+};
+
+#[derive(Debug)]
+pub enum WindowError {
+    // only contexts from Context::new keep the instance and adapter that
+    // surfaces are created with
+    NoInstance,
+    CreateSurface(wgpu::CreateSurfaceError),
+    UnknownWindow,
+    // begin_window while a window or render target is being drawn into
+    NestedWindow,
+    NoActiveWindow,
+}
+
+impl fmt::Display for WindowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowError::NoInstance => {
+                write!(f, "The context can't create surfaces.")
+            }
+            WindowError::CreateSurface(e) => {
+                write!(f, "Could not create the surface: {}", e)
+            }
+            WindowError::UnknownWindow => {
+                write!(f, "The window wasn't added to the context.")
+            }
+            WindowError::NestedWindow => write!(
+                f,
+                "Something else is being drawn into, call end_window or \
+                 end_target first."
+            ),
+            WindowError::NoActiveWindow => {
+                write!(f, "No window is being drawn into.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WindowError {}
+
+// A window besides the one the context was created with. It shares the
+// device, textures and shader with that one and has its own surface,
+// projection and rectangles, like a render target. It's drawn with a
+// single alpha blended pipeline, see Context::begin_window for what that
+// leaves out.
+pub struct ExtraWindow<'a> {
+    pub window: &'a Window,
+    pub surface: wgpu::Surface<'a>,
+    pub config: wgpu::SurfaceConfiguration,
+    pub size: PhysicalSize<u32>,
+    pub clear_color: Color,
+
+    pub rectangles_to_render: Vec<RectangleDrawData>,
+    pub projection_buffer: wgpu::Buffer,
+    pub rectangles_buffer: wgpu::Buffer,
+    // None until the first frame and after the rectangles buffer, the
+    // samplers or the device changed
+    pub uniform_bind_group: Option<wgpu::BindGroup>,
+    // how many samplers uniform_bind_group was created with
+    pub bind_group_samplers: usize,
+}
+
+impl<'a> Context<'a> {
+    /// Adds another window that's drawn with the same device, textures and
+    /// shader, e.g. for a tool on a second monitor. Only plain rectangles,
+    /// text and textures are drawn there, see `begin_window`. Draw into it
+    /// between `begin_window` and `end_window`. Its events are passed to
+    /// `handle_extra_window_event` by the context's `ApplicationHandler`,
+    /// closing it removes it.
+    pub fn add_window(
+        &mut self,
+        window: &'a Window,
+    ) -> Result<WindowId, WindowError> {
+        let (Some(instance), Some(adapter)) = (&self.instance, &self.adapter)
+        else {
+            return Err(WindowError::NoInstance);
+        };
+
+        let surface = instance
+            .create_surface(window)
+            .map_err(WindowError::CreateSurface)?;
+        let surface_caps = surface.get_capabilities(adapter);
+
+        // same as the main window where the surface allows it
+        let format = surface_caps
+            .formats
+            .iter()
+            .copied()
+            .find(|f| *f == self.config.format)
+            .or(surface_caps.formats.iter().copied().find(|f| f.is_srgb()))
+            .unwrap_or(surface_caps.formats[0]);
+        let present_mode = match surface_caps
+            .present_modes
+            .contains(&self.config.present_mode)
+        {
+            true => self.config.present_mode,
+            false => wgpu::PresentMode::Fifo,
+        };
+
+        let size = window.inner_size();
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode,
+            alpha_mode: surface_caps.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(&self.device, &config);
+
+        let projection_buffer =
+            self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Window Projection Buffer"),
+                size: 64,
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+        let rectangles_buffer = create_rectangles_buffer(
+            &self.device,
+            "Window Rectangles Buffer",
+            INITIAL_RECTANGLES_CAPACITY,
+        );
+
+        self.extra_windows.insert(
+            window.id(),
+            ExtraWindow {
+                window,
+                surface,
+                config,
+                size,
+                clear_color: self.clear_color,
+                rectangles_to_render: vec![],
+                projection_buffer,
+                rectangles_buffer,
+                uniform_bind_group: None,
+                bind_group_samplers: 0,
+            },
+        );
+
+        Ok(window.id())
+    }
+
+    /// Stops drawing into the window. Returns false if it wasn't added.
+    pub fn remove_window(&mut self, id: WindowId) -> bool {
+        if self.active_window == Some(id) {
+            let _ = self.end_window();
+        }
+
+        self.extra_windows.remove(&id).is_some()
+    }
+
+    pub fn extra_window_ids(&self) -> impl Iterator<Item = WindowId> + '_ {
+        self.extra_windows.keys().copied()
+    }
+
+    pub fn set_window_clear_color(
+        &mut self,
+        id: WindowId,
+        color: impl Into<Color>,
+    ) -> Result<(), WindowError> {
+        let window = self
+            .extra_windows
+            .get_mut(&id)
+            .ok_or(WindowError::UnknownWindow)?;
+        window.clear_color = color.into();
+        window.window.request_redraw();
+
+        Ok(())
+    }
+
+    /// Everything drawn until `end_window` goes into the window (replacing
+    /// what was drawn into it before) instead of the main one. Rectangles
+    /// are drawn in order with alpha blending and the built-in shader and
+    /// effects. Materials, blend modes, clip rects, the stencil buffer,
+    /// MSAA, post processing and the debug UI only work in the main window
+    /// and are ignored here.
+    pub fn begin_window(&mut self, id: WindowId) -> Result<(), WindowError> {
+        if self.active_window.is_some() || self.active_render_target.is_some() {
+            return Err(WindowError::NestedWindow);
+        }
+
+        let window = self
+            .extra_windows
+            .get_mut(&id)
+            .ok_or(WindowError::UnknownWindow)?;
+
+        // same trick as begin_target
+        window.rectangles_to_render.clear();
+        std::mem::swap(
+            &mut self.rectangles_to_render,
+            &mut window.rectangles_to_render,
+        );
+
+        self.active_window = Some(id);
+
+        Ok(())
+    }
+
+    /// Goes back to drawing into the main window.
+    pub fn end_window(&mut self) -> Result<(), WindowError> {
+        let id = self
+            .active_window
+            .take()
+            .ok_or(WindowError::NoActiveWindow)?;
+
+        // removed windows end the drawing first, so it's still there
+        let window = self.extra_windows.get_mut(&id).unwrap();
+        std::mem::swap(
+            &mut self.rectangles_to_render,
+            &mut window.rectangles_to_render,
+        );
+        window.window.request_redraw();
+
+        Ok(())
+    }
+
+    /// Resizes, redraws and removes the extra windows. Has to be called
+    /// from `ApplicationHandler::window_event` for them when driving the
+    /// context from your own event loop, see `is_extra_window`.
+    pub fn handle_extra_window_event(
+        &mut self,
+        id: WindowId,
+        event: WindowEvent,
+    ) {
+        match event {
+            WindowEvent::CloseRequested => {
+                self.remove_window(id);
+            }
+
+            WindowEvent::Resized(new_size) => {
+                if let Some(window) = self.extra_windows.get_mut(&id) {
+                    if new_size.width > 0 && new_size.height > 0 {
+                        window.size = new_size;
+                        window.config.width = new_size.width;
+                        window.config.height = new_size.height;
+                        window.surface.configure(&self.device, &window.config);
+                    }
+                }
+            }
+
+            WindowEvent::RedrawRequested => match self.render_window(id) {
+                Ok(_) => {}
+
+                Err(
+                    wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated,
+                ) => {
+                    if let Some(window) = self.extra_windows.get(&id) {
+                        window.surface.configure(&self.device, &window.config);
+                    }
+                }
+
+                Err(e) => log::warn!("Could not draw window {:?}: {}", id, e),
+            },
+
+            _ => {}
+        }
+    }
+
+    pub fn is_extra_window(&self, id: WindowId) -> bool {
+        self.extra_windows.contains_key(&id)
+    }
+
+    /// Draws the window's rectangles and presents them. Called when the
+    /// window asks for a redraw, which `end_window` does.
+    pub fn render_window(
+        &mut self,
+        id: WindowId,
+    ) -> Result<(), wgpu::SurfaceError> {
+        // still being drawn into
+        if self.active_window == Some(id) {
+            return Ok(());
+        }
+        // the main window's textures bind group is used, it has every
+        // texture
+        if self.textures_bind_group_dirty {
+            self.rebuild_textures_bind_group();
+        }
+        let Some(window) = self.extra_windows.get_mut(&id) else {
+            return Ok(());
+        };
+
+        // PIPELINE
+        // ========
+
+        // built the first time a surface format is used, and again after
+        // the shader changed
+        if !self.window_pipelines.contains_key(&window.config.format) {
+            let shader = create_main_shader_module(
+                &self.device,
+                &self.main_shader_source,
+                &self.effects,
+                self.main_shader_spirv.as_deref(),
+                self.texture_binding_mode,
+                self.push_constants_supported,
+            );
+            let pipeline = create_render_pipeline(
+                &self.device,
+                &self.render_pipeline_layout,
+                &shader,
+                "fs_main",
+                window.config.format,
+                wgpu::ColorWrites::ALL,
+                wgpu::BlendState::ALPHA_BLENDING,
+                None,
+                1,
+            );
+            self.window_pipelines.insert(window.config.format, pipeline);
+        }
+        let pipeline = &self.window_pipelines[&window.config.format];
+
+        let output = window.surface.get_current_texture()?;
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        // UPLOAD
+        // ======
+
+        let (width, height) = if self.logical_coordinates {
+            let size =
+                window.size.to_logical::<f32>(window.window.scale_factor());
+            (size.width, size.height)
+        } else {
+            (window.size.width as f32, window.size.height as f32)
+        };
+        self.queue.write_buffer(
+            &window.projection_buffer,
+            0,
+            &Self::calculate_projection_matrix(width, height),
+        );
+
+        let (grown, rectangle_count) = grow_rectangles_buffer(
+            &self.device,
+            &mut window.rectangles_buffer,
+            "Window Rectangles Buffer",
+            window.rectangles_to_render.len(),
+        );
+        let rectangles: &[u8] = bytemuck::cast_slice(
+            &window.rectangles_to_render[..rectangle_count],
+        );
+        self.queue
+            .write_buffer(&window.rectangles_buffer, 0, rectangles);
+
+        // BIND GROUPS
+        // ===========
+
+        if grown || window.bind_group_samplers != self.samplers.len() {
+            window.uniform_bind_group = None;
+        }
+        let uniform_bind_group =
+            window.uniform_bind_group.get_or_insert_with(|| {
+                self.current_frame_stats.record_bind_group_rebuild();
+                window.bind_group_samplers = self.samplers.len();
+                create_uniform_bind_group(
+                    &self.device,
+                    &self.uniform_bind_group_layout,
+                    &window.projection_buffer,
+                    &window.rectangles_buffer,
+                    &self.texture_settings_buffer,
+                    &self.time_buffer,
+                    &self.samplers,
+                )
+            });
+
+        // DRAW
+        // ====
+
+        let mut encoder = self.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor {
+                label: Some("Window Encoder"),
+            },
+        );
+
+        {
+            let mut render_pass =
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Window Pass"),
+                    color_attachments: &[Some(
+                        wgpu::RenderPassColorAttachment {
+                            view: &view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(
+                                    window.clear_color.into(),
+                                ),
+                                store: wgpu::StoreOp::Store,
+                            },
+                        },
+                    )],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, uniform_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.textures_bind_group, &[]);
+
+            if self.push_constants_supported {
+                render_pass.set_push_constants(
+                    wgpu::ShaderStages::FRAGMENT,
+                    0,
+                    bytemuck::bytes_of(&self.push_constant_value),
+                );
+            }
+
+            render_pass.draw(0..6, 0..rectangle_count as u32);
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+
+        Ok(())
+    }
+}
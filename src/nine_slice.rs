@@ -0,0 +1,88 @@
+use crate::{
+    atlas::SpriteHandle,
+    context::{Context, TextureError},
+    geometry::Rect,
+};
+
+// Splits start..start + length into the start border, the middle and the
+// end border. Borders that don't fit are shrunk by the same factor, so
+// small panels still get both of them.
+fn split(
+    start: f32,
+    length: f32,
+    border_start: f32,
+    border_end: f32,
+) -> [f32; 4] {
+    let borders = border_start + border_end;
+    let scale = if borders > length.abs() && borders > 0.0 {
+        length.abs() / borders
+    } else {
+        1.0
+    };
+
+    [
+        start,
+        start + border_start * scale,
+        start + length - border_end * scale,
+        start + length,
+    ]
+}
+
+impl<'a> Context<'a> {
+    /// Draws the sprite stretched over `dest` without stretching its
+    /// borders, for panels and buttons. `border` is the width of the left,
+    /// top, right and bottom border in texture pixels. The corners keep
+    /// their size, the edges are stretched along them and the middle both
+    /// ways. Borders wider than `dest` are shrunk to fit.
+    pub fn draw_nine_slice(
+        &mut self,
+        sprite: impl Into<SpriteHandle>,
+        dest: Rect,
+        border: [f32; 4],
+    ) -> Result<(), TextureError> {
+        let sprite = sprite.into();
+        let (width, height) = self
+            .texture_size(sprite.texture)
+            .ok_or(TextureError::InvalidHandle)?;
+        let [left, top, right, bottom] = border;
+
+        let xs = split(dest.x, dest.w, left, right);
+        let ys = split(dest.y, dest.h, top, bottom);
+        let us = split(
+            sprite.uv_pos[0],
+            sprite.uv_size[0],
+            left / width as f32,
+            right / width as f32,
+        );
+        let vs = split(
+            sprite.uv_pos[1],
+            sprite.uv_size[1],
+            top / height as f32,
+            bottom / height as f32,
+        );
+
+        for row in 0..3 {
+            for column in 0..3 {
+                let size = [xs[column + 1] - xs[column], ys[row + 1] - ys[row]];
+                if size[0] == 0.0 || size[1] == 0.0 {
+                    continue;
+                }
+
+                self.draw_textured_rect(
+                    [xs[column], ys[row]],
+                    size,
+                    SpriteHandle {
+                        texture: sprite.texture,
+                        uv_pos: [us[column], vs[row]],
+                        uv_size: [
+                            us[column + 1] - us[column],
+                            vs[row + 1] - vs[row],
+                        ],
+                    },
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
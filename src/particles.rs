@@ -0,0 +1,144 @@
+use crate::context::{Context, RectangleDrawData};
+
+// A square that moves in a straight line until it dies. `pos` is its
+// center, `age` and `lifetime` are in seconds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Particle {
+    pub pos: [f32; 2],
+    pub vel: [f32; 2],
+    pub color: [f32; 3],
+    pub size: f32,
+    pub lifetime: f32,
+    pub age: f32,
+}
+
+// New particles get random values between the min and max of each range.
+// The color fades from color_start to color_end over a particle's life.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EmitterConfig {
+    // particles per second
+    pub emit_rate: f32,
+    // min and max for each component, in pixels per second
+    pub initial_velocity_range: ([f32; 2], [f32; 2]),
+    pub lifetime_range: (f32, f32),
+    pub size_range: (f32, f32),
+    pub color_start: [f32; 3],
+    pub color_end: [f32; 3],
+}
+
+impl Default for EmitterConfig {
+    fn default() -> Self {
+        Self {
+            emit_rate: 50.0,
+            initial_velocity_range: ([-50.0, -100.0], [50.0, -50.0]),
+            lifetime_range: (1.0, 2.0),
+            size_range: (2.0, 6.0),
+            color_start: [1.0, 1.0, 1.0],
+            color_end: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+pub struct ParticleEmitter {
+    pub position: [f32; 2],
+    pub particles: Vec<Particle>,
+    pub config: EmitterConfig,
+
+    // fractional particles left over from the previous ticks
+    emit_accumulator: f32,
+    // xorshift state, the randomness doesn't need to be any good
+    rng_state: u32,
+}
+
+impl ParticleEmitter {
+    pub fn new(position: [f32; 2], config: EmitterConfig) -> Self {
+        Self {
+            position,
+            particles: vec![],
+            config,
+            emit_accumulator: 0.0,
+            rng_state: 0x9e37_79b9,
+        }
+    }
+
+    /// Moves and ages the particles by `delta` seconds, removes the ones
+    /// that died and emits new ones at `position`.
+    pub fn tick(&mut self, delta: f32) {
+        // UPDATE
+        // ======
+
+        for particle in &mut self.particles {
+            particle.pos[0] += particle.vel[0] * delta;
+            particle.pos[1] += particle.vel[1] * delta;
+            particle.age += delta;
+        }
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+
+        // EMIT
+        // ====
+
+        self.emit_accumulator += self.config.emit_rate.max(0.0) * delta;
+        while self.emit_accumulator >= 1.0 {
+            self.emit_accumulator -= 1.0;
+
+            let (min_vel, max_vel) = self.config.initial_velocity_range;
+            let particle = Particle {
+                pos: self.position,
+                vel: [
+                    self.random_range(min_vel[0], max_vel[0]),
+                    self.random_range(min_vel[1], max_vel[1]),
+                ],
+                color: self.config.color_start,
+                size: self.random_range(
+                    self.config.size_range.0,
+                    self.config.size_range.1,
+                ),
+                lifetime: self.random_range(
+                    self.config.lifetime_range.0,
+                    self.config.lifetime_range.1,
+                ),
+                age: 0.0,
+            };
+            self.particles.push(particle);
+        }
+    }
+
+    fn random_range(&mut self, min: f32, max: f32) -> f32 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 17;
+        self.rng_state ^= self.rng_state << 5;
+
+        // the top 24 bits fit into an f32 exactly
+        let t = (self.rng_state >> 8) as f32 / (1 << 24) as f32;
+        min + (max - min) * t
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Draws every live particle of the emitter as a square, with its color
+    /// set according to how far into its life it is. Call
+    /// `ParticleEmitter::tick` every frame to move them.
+    pub fn draw_particle_emitter(&mut self, emitter: &mut ParticleEmitter) {
+        let EmitterConfig {
+            color_start,
+            color_end,
+            ..
+        } = emitter.config;
+
+        for particle in &mut emitter.particles {
+            let t = (particle.age / particle.lifetime.max(f32::EPSILON))
+                .clamp(0.0, 1.0);
+            particle.color = [0, 1, 2]
+                .map(|i| color_start[i] + (color_end[i] - color_start[i]) * t);
+
+            let half_size = particle.size / 2.0;
+            self.rectangles_to_render.push(RectangleDrawData {
+                pos: [particle.pos[0] - half_size, particle.pos[1] - half_size],
+                size: [particle.size, particle.size],
+                color: particle.color.into(),
+                ..Default::default()
+            });
+        }
+    }
+}
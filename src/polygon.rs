@@ -0,0 +1,339 @@
+use std::f32::consts::TAU;
+
+use crate::{color::Color, context::Context};
+
+// polygons are triangulated on the CPU and drawn from a vertex buffer,
+// since they don't fit into RectangleDrawData
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PolygonVertex {
+    pub pos: [f32; 2],
+    pub color: [f32; 4],
+}
+
+const POLYGON_VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+    wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+// how many vertices and indices the buffers start with room for
+pub const INITIAL_POLYGON_CAPACITY: u64 = 1024;
+
+// how many sides the round caps of lines have
+const LINE_CAP_SEGMENTS: u32 = 12;
+
+// Triangulates a polygon that can be concave, returns indices into `points`
+// with three per triangle
+pub fn triangulate_polygon(points: &[[f32; 2]]) -> Option<Vec<u32>> {
+    if points.len() < 3 {
+        return None;
+    }
+
+    match earcutr::earcut(points.as_flattened(), &[], 2) {
+        Ok(triangles) => {
+            Some(triangles.into_iter().map(|index| index as u32).collect())
+        }
+        Err(e) => {
+            log::warn!("Could not triangulate polygon: {:?}", e);
+            None
+        }
+    }
+}
+
+// the same for a convex polygon, every triangle starts at the first point
+pub fn triangulate_convex_polygon(points: &[[f32; 2]]) -> Vec<u32> {
+    if points.len() < 3 {
+        return vec![];
+    }
+
+    (1..points.len() as u32 - 1)
+        .flat_map(|i| [0, i, i + 1])
+        .collect()
+}
+
+pub fn create_polygon_buffer(
+    device: &wgpu::Device,
+    label: &str,
+    usage: wgpu::BufferUsages,
+    size: u64,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        size,
+        mapped_at_creation: false,
+    })
+}
+
+// recreates `buffer` at least twice as big if `size` bytes don't fit.
+// Nothing refers to these buffers except the draw calls, so there's nothing
+// else to update.
+fn grow_polygon_buffer(
+    device: &wgpu::Device,
+    buffer: &mut wgpu::Buffer,
+    label: &str,
+    usage: wgpu::BufferUsages,
+    size: u64,
+) {
+    if size > buffer.size() {
+        let new_size = (buffer.size() * 2).max(size);
+        *buffer = create_polygon_buffer(device, label, usage, new_size);
+    }
+}
+
+// Uses the main shader's fragment entry point, but its own vertex entry
+// point that reads the vertex buffer. The other arguments have to match
+// the main render pipeline so it can be used in the same pass.
+pub fn create_polygon_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Polygon Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_polygon",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<PolygonVertex>() as u64,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &POLYGON_VERTEX_ATTRIBUTES,
+            }],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        // triangles from earcut can have either winding
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    })
+}
+
+impl<'a> Context<'a> {
+    /// Fills the polygon with the outline `points`, which can be concave
+    /// but shouldn't cross itself. Polygons are drawn on top of all
+    /// rectangles with a single draw call, and only into the window. They
+    /// stay until `clear_polygons` is called, like rectangles.
+    pub fn draw_polygon(
+        &mut self,
+        points: &[[f32; 2]],
+        color: impl Into<Color>,
+    ) {
+        if let Some(indices) = triangulate_polygon(points) {
+            self.push_polygon_triangles(points, &indices, color.into());
+        }
+    }
+
+    /// Like `draw_polygon`, but the polygon has to be convex, which is
+    /// cheaper to triangulate: every triangle starts at the first point.
+    /// Concave polygons come out wrong, use `draw_polygon` for those.
+    pub fn draw_convex_polygon(
+        &mut self,
+        points: &[[f32; 2]],
+        color: impl Into<Color>,
+    ) {
+        if points.len() < 3 {
+            return;
+        }
+
+        let indices = triangulate_convex_polygon(points);
+        self.push_polygon_triangles(points, &indices, color.into());
+    }
+
+    /// Draws a straight line `width` pixels thick. Lines are polygons, so
+    /// they're drawn on top of rectangles too.
+    pub fn draw_line(
+        &mut self,
+        from: [f32; 2],
+        to: [f32; 2],
+        width: f32,
+        color: impl Into<Color>,
+    ) {
+        let color: Color = color.into();
+        let direction = [to[0] - from[0], to[1] - from[1]];
+        let length = direction[0].hypot(direction[1]);
+        if length <= 0.0 {
+            return;
+        }
+
+        // perpendicular to the line, half the width long
+        let normal = [
+            -direction[1] / length * width / 2.0,
+            direction[0] / length * width / 2.0,
+        ];
+
+        self.push_polygon_triangles(
+            &[
+                [from[0] + normal[0], from[1] + normal[1]],
+                [to[0] + normal[0], to[1] + normal[1]],
+                [to[0] - normal[0], to[1] - normal[1]],
+                [from[0] - normal[0], from[1] - normal[1]],
+            ],
+            &[0, 1, 2, 0, 2, 3],
+            color,
+        );
+    }
+
+    // a filled circle made of triangles, used to round off where lines meet
+    pub fn draw_line_cap(
+        &mut self,
+        center: [f32; 2],
+        radius: f32,
+        color: impl Into<Color>,
+    ) {
+        let color: Color = color.into();
+        let mut points = vec![center];
+        let mut indices = vec![];
+        for i in 0..LINE_CAP_SEGMENTS {
+            let angle = i as f32 / LINE_CAP_SEGMENTS as f32 * TAU;
+            points.push([
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]);
+            indices.extend([0, i + 1, (i + 1) % LINE_CAP_SEGMENTS + 1]);
+        }
+
+        self.push_polygon_triangles(&points, &indices, color);
+    }
+
+    // `indices` index into `points`
+    fn push_polygon_triangles(
+        &mut self,
+        points: &[[f32; 2]],
+        indices: &[u32],
+        color: Color,
+    ) {
+        let color = color.into();
+        let first_vertex = self.polygon_vertices.len() as u32;
+        self.polygon_vertices
+            .extend(points.iter().map(|&pos| PolygonVertex { pos, color }));
+        self.polygon_indices
+            .extend(indices.iter().map(|index| first_vertex + index));
+    }
+
+    pub fn clear_polygons(&mut self) {
+        self.polygon_vertices.clear();
+        self.polygon_indices.clear();
+    }
+
+    // makes sure the buffers are big enough and writes the polygons into
+    // them, before the render pass
+    pub fn upload_polygons(&mut self) {
+        if self.polygon_indices.is_empty() {
+            return;
+        }
+
+        let vertices: &[u8] = bytemuck::cast_slice(&self.polygon_vertices);
+        let indices: &[u8] = bytemuck::cast_slice(&self.polygon_indices);
+
+        grow_polygon_buffer(
+            &self.device,
+            &mut self.polygon_vertex_buffer,
+            "Polygon Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            vertices.len() as u64,
+        );
+        grow_polygon_buffer(
+            &self.device,
+            &mut self.polygon_index_buffer,
+            "Polygon Index Buffer",
+            wgpu::BufferUsages::INDEX,
+            indices.len() as u64,
+        );
+
+        self.queue
+            .write_buffer(&self.polygon_vertex_buffer, 0, vertices);
+        self.queue
+            .write_buffer(&self.polygon_index_buffer, 0, indices);
+        self.current_frame_stats.record_buffer_write(vertices.len());
+        self.current_frame_stats.record_buffer_write(indices.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: [[f32; 2]; 4] =
+        [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]];
+
+    // an arrowhead pointing right, with the notch at the last point
+    const ARROW: [[f32; 2]; 4] =
+        [[0.0, 0.0], [10.0, 5.0], [0.0, 10.0], [3.0, 5.0]];
+
+    // the area the triangles cover, counting overlaps twice
+    fn area(points: &[[f32; 2]], indices: &[u32]) -> f32 {
+        indices
+            .chunks(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| points[triangle[i] as usize]);
+                ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1]))
+                    .abs()
+                    / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn triangulates_a_square() {
+        let indices = triangulate_polygon(&SQUARE).unwrap();
+        assert_eq!(indices, vec![2, 3, 0, 0, 1, 2]);
+        assert_eq!(area(&SQUARE, &indices), 100.0);
+
+        let indices = triangulate_convex_polygon(&SQUARE);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(area(&SQUARE, &indices), 100.0);
+    }
+
+    #[test]
+    fn triangulates_a_concave_arrow() {
+        let indices = triangulate_polygon(&ARROW).unwrap();
+        assert_eq!(indices.len(), 6);
+        assert_eq!(area(&ARROW, &indices), 35.0);
+
+        // the fan covers the notch as well, which is why concave polygons
+        // need draw_polygon
+        let indices = triangulate_convex_polygon(&ARROW);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(area(&ARROW, &indices), 65.0);
+    }
+
+    #[test]
+    fn triangulates_an_l_shape() {
+        let points = [
+            [0.0, 0.0],
+            [10.0, 0.0],
+            [10.0, 4.0],
+            [4.0, 4.0],
+            [4.0, 10.0],
+            [0.0, 10.0],
+        ];
+
+        let indices = triangulate_polygon(&points).unwrap();
+        assert_eq!(indices, vec![4, 5, 0, 0, 1, 2, 3, 4, 0, 0, 2, 3]);
+        assert_eq!(area(&points, &indices), 64.0);
+    }
+
+    #[test]
+    fn needs_three_points() {
+        let line = [[0.0, 0.0], [1.0, 1.0]];
+
+        assert_eq!(triangulate_polygon(&line), None);
+        assert!(triangulate_convex_polygon(&line).is_empty());
+    }
+}
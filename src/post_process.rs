@@ -0,0 +1,434 @@
+use wgpu::util::DeviceExt;
+
+use crate::context::Context;
+
+// Put in front of the shader passed to Context::set_post_process_shader. The
+// shader only has to define the fragment shader:
+//
+//     @fragment
+//     fn fs_main(in: PostProcessVertexOutput) -> @location(0) vec4<f32> {
+//         let color = textureSample(frame_texture, frame_sampler, in.uv);
+//         return vec4<f32>(1.0 - color.rgb, color.a);
+//     }
+pub const POST_PROCESS_PRELUDE: &str = "
+@group(0) @binding(0)
+var frame_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var frame_sampler: sampler;
+
+// see Context::set_post_effect_params
+@group(0) @binding(2)
+var<uniform> effect_params: vec4<f32>;
+
+struct PostProcessVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // 0..1 across the window, (0, 0) is the top left
+    @location(0) uv: vec2<f32>,
+};
+
+// one triangle that covers the whole screen
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> PostProcessVertexOutput {
+    let uv = vec2<f32>(
+        f32((in_vertex_index << 1u) & 2u),
+        f32(in_vertex_index & 2u)
+    );
+
+    var out: PostProcessVertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+
+    return out;
+}
+";
+
+const GRAYSCALE_SHADER: &str = "
+@fragment
+fn fs_main(in: PostProcessVertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(frame_texture, frame_sampler, in.uv);
+    let gray = dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return vec4<f32>(mix(color.rgb, vec3<f32>(gray), effect_params.x), color.a);
+}
+";
+
+const SEPIA_SHADER: &str = "
+@fragment
+fn fs_main(in: PostProcessVertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(frame_texture, frame_sampler, in.uv);
+    let sepia = vec3<f32>(
+        dot(color.rgb, vec3<f32>(0.393, 0.769, 0.189)),
+        dot(color.rgb, vec3<f32>(0.349, 0.686, 0.168)),
+        dot(color.rgb, vec3<f32>(0.272, 0.534, 0.131)),
+    );
+    return vec4<f32>(
+        mix(color.rgb, min(sepia, vec3<f32>(1.0)), effect_params.x),
+        color.a
+    );
+}
+";
+
+const VIGNETTE_SHADER: &str = "
+@fragment
+fn fs_main(in: PostProcessVertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(frame_texture, frame_sampler, in.uv);
+    // 0 in the center, 1 in the corners
+    let distance = length(in.uv - vec2<f32>(0.5)) * sqrt(2.0);
+    let darkness = smoothstep(effect_params.y, 1.0, distance) * effect_params.x;
+    return vec4<f32>(color.rgb * (1.0 - darkness), color.a);
+}
+";
+
+const CRT_SCANLINES_SHADER: &str = "
+@fragment
+fn fs_main(in: PostProcessVertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(frame_texture, frame_sampler, in.uv);
+    let line = 0.5 + 0.5 * cos(in.uv.y * effect_params.y * 6.2831853);
+    return vec4<f32>(color.rgb * (1.0 - effect_params.x * line), color.a);
+}
+";
+
+// What Context::set_post_effect runs over the finished frame. The built-in
+// effects read their settings from the params, see
+// Context::set_post_effect_params.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum PostEffect {
+    // draw straight to the window, no extra pass
+    #[default]
+    None,
+    // params.x is how gray, 0 to 1
+    Grayscale,
+    // params.x is how brown, 0 to 1
+    Sepia,
+    // params.x is how dark the corners get, params.y is how far from the
+    // center (0) towards the corners (1) the darkening starts
+    Vignette,
+    // params.x is how dark the lines get, params.y is how many there are
+    CrtScanlines,
+    // a shader like for Context::set_post_process_shader
+    Custom(String),
+}
+
+impl PostEffect {
+    fn shader(&self) -> Option<&str> {
+        match self {
+            PostEffect::None => None,
+            PostEffect::Grayscale => Some(GRAYSCALE_SHADER),
+            PostEffect::Sepia => Some(SEPIA_SHADER),
+            PostEffect::Vignette => Some(VIGNETTE_SHADER),
+            PostEffect::CrtScanlines => Some(CRT_SCANLINES_SHADER),
+            PostEffect::Custom(wgsl) => Some(wgsl),
+        }
+    }
+
+    /// The params the effect starts with.
+    pub fn default_params(&self) -> [f32; 4] {
+        match self {
+            PostEffect::Vignette => [0.6, 0.4, 0.0, 0.0],
+            PostEffect::CrtScanlines => [0.3, 240.0, 0.0, 0.0],
+            _ => [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+// The frame is drawn into `texture` instead of the surface, then the post
+// process shader draws it onto the surface.
+pub struct PostProcess {
+    // without the prelude, kept to build the pipeline again on a new device
+    pub wgsl: String,
+    pub pipeline: wgpu::RenderPipeline,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub sampler: wgpu::Sampler,
+    // effect_params in the shader
+    pub params_buffer: wgpu::Buffer,
+    pub params: [f32; 4],
+
+    // same size and format as the surface
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl PostProcess {
+    fn create_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            label: Some("Post process texture"),
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        (texture, view)
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        params_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("post_process_bind_group"),
+        })
+    }
+
+    // has to be called when the window is resized
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) {
+        (self.texture, self.view) = Self::create_texture(device, config);
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.view,
+            &self.sampler,
+            &self.params_buffer,
+        );
+    }
+
+    // draws the frame from `texture` onto `view`
+    pub fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Post Process Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Runs the shader over the whole frame after everything else is drawn,
+    /// for things like blur, vignettes or color grading. The shader gets
+    /// `POST_PROCESS_PRELUDE` put in front of it, so it only has to define
+    /// `fs_main`. If it doesn't compile the error is returned and the
+    /// current post process shader (if any) stays.
+    pub fn set_post_process_shader(
+        &mut self,
+        wgsl: &str,
+    ) -> Result<(), wgpu::Error> {
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let shader =
+            self.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("Post Process Shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        format!("{}\n{}", POST_PROCESS_PRELUDE, wgsl).into(),
+                    ),
+                });
+
+        let bind_group_layout = self.device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("post_process_bind_group_layout"),
+            },
+        );
+
+        let pipeline_layout = self.device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Post Process Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            },
+        );
+
+        let pipeline = self.device.create_render_pipeline(
+            &wgpu::RenderPipelineDescriptor {
+                label: Some("Post Process Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: self.config.format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            },
+        );
+
+        if let Some(e) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(e);
+        }
+
+        let sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        // a shader that's swapped in keeps the params
+        let params = self
+            .post_process
+            .as_ref()
+            .map_or([1.0, 0.0, 0.0, 0.0], |post_process| post_process.params);
+        let params_buffer =
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Post Effect Params Buffer"),
+                    contents: bytemuck::cast_slice(&params),
+                    usage: wgpu::BufferUsages::UNIFORM
+                        | wgpu::BufferUsages::COPY_DST,
+                });
+        let (texture, view) =
+            PostProcess::create_texture(&self.device, &self.config);
+        let bind_group = PostProcess::create_bind_group(
+            &self.device,
+            &bind_group_layout,
+            &view,
+            &sampler,
+            &params_buffer,
+        );
+
+        self.post_process = Some(PostProcess {
+            wgsl: wgsl.to_string(),
+            pipeline,
+            bind_group_layout,
+            sampler,
+            params_buffer,
+            params,
+            texture,
+            view,
+            bind_group,
+        });
+
+        Ok(())
+    }
+
+    /// Goes back to drawing straight to the window.
+    pub fn clear_post_process_shader(&mut self) {
+        self.post_process = None;
+    }
+
+    /// Runs one of the built-in effects (or a custom shader) over the
+    /// finished frame, with its default params. `PostEffect::None` draws
+    /// straight to the window again without the extra pass.
+    pub fn set_post_effect(
+        &mut self,
+        effect: PostEffect,
+    ) -> Result<(), wgpu::Error> {
+        match effect.shader() {
+            Some(wgsl) => {
+                self.set_post_process_shader(wgsl)?;
+                self.set_post_effect_params(effect.default_params());
+            }
+            None => self.clear_post_process_shader(),
+        }
+
+        Ok(())
+    }
+
+    /// Changes `effect_params` in the post process shader, which is what
+    /// the built-in effects are adjusted with (see `PostEffect`). Cheap
+    /// enough to call every frame, e.g. to animate a vignette. Does nothing
+    /// without a post process shader.
+    pub fn set_post_effect_params(&mut self, params: [f32; 4]) {
+        if let Some(post_process) = &mut self.post_process {
+            post_process.params = params;
+            self.queue.write_buffer(
+                &post_process.params_buffer,
+                0,
+                bytemuck::cast_slice(&params),
+            );
+        }
+    }
+
+    pub fn post_effect_params(&self) -> Option<[f32; 4]> {
+        self.post_process
+            .as_ref()
+            .map(|post_process| post_process.params)
+    }
+}
@@ -0,0 +1,71 @@
+use crate::context::Context;
+
+// what AppConfig::present_mode and Context::set_present_mode ask for. The
+// surface doesn't support every present mode everywhere, see
+// choose_present_mode for what's used instead.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PresentModeConfig {
+    // waits for the display, no tearing but a frame or two of latency
+    Vsync,
+    // doesn't wait, lowest latency but tears
+    NoVsync,
+    // replaces the waiting frame with the newest one, no tearing and low
+    // latency but draws frames that are never shown
+    Mailbox,
+    // Mailbox if it's there, Vsync otherwise
+    #[default]
+    Auto,
+}
+
+// Picks the first mode for `requested` that's in `supported`:
+// - Vsync: Fifo
+// - NoVsync: Immediate, Mailbox, Fifo
+// - Mailbox: Mailbox, Fifo
+// - Auto: Mailbox, Fifo
+// Every surface should support Fifo, but if it doesn't the first supported
+// mode is used.
+pub fn choose_present_mode(
+    requested: PresentModeConfig,
+    supported: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    use wgpu::PresentMode::*;
+
+    let preferred: &[wgpu::PresentMode] = match requested {
+        PresentModeConfig::Vsync => &[Fifo],
+        PresentModeConfig::NoVsync => &[Immediate, Mailbox, Fifo],
+        PresentModeConfig::Mailbox | PresentModeConfig::Auto => {
+            &[Mailbox, Fifo]
+        }
+    };
+
+    preferred
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .or(supported.first().copied())
+        .unwrap_or(Fifo)
+}
+
+impl<'a> Context<'a> {
+    /// Switches the present mode, falling back to one the surface supports
+    /// (see `choose_present_mode`). The mode that's used is returned.
+    pub fn set_present_mode(
+        &mut self,
+        present_mode: PresentModeConfig,
+    ) -> wgpu::PresentMode {
+        let mode = choose_present_mode(present_mode, &self.present_modes);
+        log::info!("Present mode {:?} for {:?}", mode, present_mode);
+
+        self.present_mode = present_mode;
+        if mode != self.config.present_mode {
+            self.config.present_mode = mode;
+            self.reconfigure_surface();
+        }
+
+        mode
+    }
+
+    pub fn present_mode(&self) -> PresentModeConfig {
+        self.present_mode
+    }
+}
@@ -0,0 +1,97 @@
+use std::{collections::BTreeMap, fs};
+
+use crate::context::{Context, TextureError, TextureHandle};
+
+// what load_texture_manifest managed to load
+#[derive(Debug, Default)]
+pub struct ManifestReport {
+    pub loaded: Vec<(String, TextureHandle)>,
+    pub failed: Vec<(String, TextureError)>,
+}
+
+impl<'a> Context<'a> {
+    /// Loads the texture and makes it available under `name` through
+    /// `texture_by_name`. What happens if the name is taken depends on
+    /// `AppConfig::replace_duplicate_texture_names`.
+    pub fn load_texture_named(
+        &mut self,
+        name: &str,
+        path: &str,
+    ) -> Result<TextureHandle, TextureError> {
+        // check before loading so nothing is uploaded for nothing
+        self.check_texture_name(name)?;
+
+        let handle = self.create_texture_from_path(path)?;
+        self.texture_names.insert(name.to_string(), handle);
+
+        Ok(handle)
+    }
+
+    /// Gives a name to a texture that was created some other way.
+    pub fn name_texture(
+        &mut self,
+        name: &str,
+        handle: TextureHandle,
+    ) -> Result<(), TextureError> {
+        if handle >= self.textures.len() {
+            return Err(TextureError::InvalidHandle);
+        }
+        self.check_texture_name(name)?;
+
+        self.texture_names.insert(name.to_string(), handle);
+
+        Ok(())
+    }
+
+    pub fn texture_by_name(&self, name: &str) -> Option<TextureHandle> {
+        self.texture_names.get(name).copied()
+    }
+
+    /// Loads every texture listed in a TOML file of `name = "path"` lines,
+    /// for example:
+    ///
+    /// ```toml
+    /// player = "res/player.png"
+    /// tiles = "res/tiles.png"
+    /// ```
+    ///
+    /// Entries that fail don't stop the rest from loading, they're listed in
+    /// the returned report. Only a manifest that can't be read or parsed is
+    /// an error.
+    pub fn load_texture_manifest(
+        &mut self,
+        path: &str,
+    ) -> Result<ManifestReport, TextureError> {
+        let source =
+            fs::read_to_string(path).map_err(TextureError::CouldNotOpenFile)?;
+
+        // sorted, so textures get the same handles every time
+        let entries: BTreeMap<String, String> = toml::from_str(&source)
+            .map_err(|e| TextureError::InvalidManifest(e.to_string()))?;
+
+        let mut report = ManifestReport::default();
+        for (name, texture_path) in entries {
+            match self.load_texture_named(&name, &texture_path) {
+                Ok(handle) => report.loaded.push((name, handle)),
+                Err(e) => {
+                    log::warn!("Could not load texture {}: {}", name, e);
+                    report.failed.push((name, e));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn check_texture_name(&self, name: &str) -> Result<(), TextureError> {
+        // the texture the name pointed to before stays loaded, it just
+        // can't be found by name anymore
+        if !self.replace_duplicate_texture_names
+            && self.texture_names.contains_key(name)
+        {
+            return Err(TextureError::DuplicateName(name.to_string()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,416 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    context::{Context, TextureError},
+    render_target::RenderTargetHandle,
+};
+
+// the resource name of the window's frame. The pass that draws into it is
+// the main scene pass, whatever draws into targets can be drawn there.
+pub const SWAP_CHAIN: &str = "swap_chain";
+
+#[derive(Debug)]
+pub enum RenderGraphError {
+    DuplicatePass(String),
+    // an input or output that isn't SWAP_CHAIN or a target added with
+    // RenderGraph::add_target
+    UnknownResource { pass: String, resource: String },
+    // more than one pass draws into the resource
+    DuplicateOutput(String),
+    // post process passes have to draw into the swap chain
+    InvalidPostProcess(String),
+    // the passes that depend on each other
+    Cycle(Vec<String>),
+    NotCompiled,
+    Texture(TextureError),
+    Shader(wgpu::Error),
+}
+
+impl fmt::Display for RenderGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderGraphError::DuplicatePass(name) => {
+                write!(f, "There's more than one pass called {}.", name)
+            }
+            RenderGraphError::UnknownResource { pass, resource } => {
+                write!(f, "Pass {} uses unknown resource {}.", pass, resource)
+            }
+            RenderGraphError::DuplicateOutput(resource) => {
+                write!(f, "More than one pass draws into {}.", resource)
+            }
+            RenderGraphError::InvalidPostProcess(pass) => write!(
+                f,
+                "Post process pass {} has to draw into the swap chain.",
+                pass
+            ),
+            RenderGraphError::Cycle(passes) => {
+                write!(f, "These passes depend on each other: {:?}", passes)
+            }
+            RenderGraphError::NotCompiled => {
+                write!(f, "The render graph has to be compiled first.")
+            }
+            RenderGraphError::Texture(e) => write!(f, "{}", e),
+            RenderGraphError::Shader(e) => {
+                write!(f, "The post process shader is invalid: {}", e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RenderGraphError {}
+
+pub enum PassKind<'a> {
+    // draws with the context's draw functions, into a target or (for the
+    // main pass) the frame
+    Draw(Box<dyn FnMut(&mut Context<'a>) + 'a>),
+    // runs the shader over the main pass's output, see
+    // Context::set_post_process_shader
+    PostProcess(String),
+}
+
+pub struct PassDescriptor<'a> {
+    // the resources the pass samples, it runs after the passes that draw
+    // into them
+    pub inputs: Vec<String>,
+    pub output: String,
+    pub kind: PassKind<'a>,
+}
+
+impl<'a> PassDescriptor<'a> {
+    pub fn draw(
+        inputs: &[&str],
+        output: &str,
+        draw: impl FnMut(&mut Context<'a>) + 'a,
+    ) -> Self {
+        Self {
+            inputs: inputs.iter().map(|input| input.to_string()).collect(),
+            output: output.to_string(),
+            kind: PassKind::Draw(Box::new(draw)),
+        }
+    }
+
+    /// Reads the main pass's output and draws into the swap chain.
+    pub fn post_process(wgsl: &str) -> Self {
+        Self {
+            inputs: vec![SWAP_CHAIN.to_string()],
+            output: SWAP_CHAIN.to_string(),
+            kind: PassKind::PostProcess(wgsl.to_string()),
+        }
+    }
+}
+
+// Passes that draw into render targets and the frame, run in an order where
+// every pass comes after the ones drawing into what it samples. Build it
+// once, `compile` it, then `execute` it every frame before rendering.
+pub struct RenderGraph<'a> {
+    resources: HashMap<String, RenderTargetHandle>,
+    passes: Vec<(String, PassDescriptor<'a>)>,
+    // indices into passes, set by compile
+    order: Option<Vec<usize>>,
+}
+
+impl<'a> Default for RenderGraph<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self {
+            resources: HashMap::new(),
+            passes: vec![],
+            order: None,
+        }
+    }
+
+    /// Lets passes use the target as an input or output by `name`.
+    pub fn add_target(&mut self, name: &str, target: RenderTargetHandle) {
+        self.resources.insert(name.to_string(), target);
+        self.order = None;
+    }
+
+    pub fn add_pass(&mut self, name: &str, pass: PassDescriptor<'a>) {
+        self.passes.push((name.to_string(), pass));
+        self.order = None;
+    }
+
+    /// Checks the passes and sorts them by their inputs and outputs. Passes
+    /// that don't depend on each other stay in the order they were added.
+    pub fn compile(&mut self) -> Result<(), RenderGraphError> {
+        // VALIDATE
+        // ========
+
+        for (i, (name, pass)) in self.passes.iter().enumerate() {
+            if self.passes[..i].iter().any(|(other, _)| other == name) {
+                return Err(RenderGraphError::DuplicatePass(name.clone()));
+            }
+
+            for resource in pass.inputs.iter().chain([&pass.output]) {
+                if resource != SWAP_CHAIN
+                    && !self.resources.contains_key(resource)
+                {
+                    return Err(RenderGraphError::UnknownResource {
+                        pass: name.clone(),
+                        resource: resource.clone(),
+                    });
+                }
+            }
+
+            match &pass.kind {
+                PassKind::PostProcess(_) if pass.output != SWAP_CHAIN => {
+                    return Err(RenderGraphError::InvalidPostProcess(
+                        name.clone(),
+                    ));
+                }
+                // a target can't be sampled while it's drawn into
+                PassKind::Draw(_) if pass.inputs.contains(&pass.output) => {
+                    return Err(RenderGraphError::Cycle(vec![name.clone()]));
+                }
+                _ => {}
+            }
+
+            let draws_same_output = |(_, other): &(String, PassDescriptor)| {
+                other.output == pass.output
+                    && matches!(
+                        (&other.kind, &pass.kind),
+                        (PassKind::Draw(_), PassKind::Draw(_))
+                            | (
+                                PassKind::PostProcess(_),
+                                PassKind::PostProcess(_)
+                            )
+                    )
+            };
+            if self.passes[..i].iter().any(draws_same_output) {
+                return Err(RenderGraphError::DuplicateOutput(
+                    pass.output.clone(),
+                ));
+            }
+        }
+
+        // SORT
+        // ====
+
+        // the passes each pass has to wait for
+        let dependencies: Vec<Vec<usize>> = self
+            .passes
+            .iter()
+            .enumerate()
+            .map(|(i, (_, pass))| {
+                self.passes
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, (_, other))| {
+                        *j != i && pass.inputs.contains(&other.output)
+                    })
+                    .map(|(j, _)| j)
+                    .collect()
+            })
+            .collect();
+
+        // always take the first pass that's ready, so the order is stable
+        let mut done = vec![false; self.passes.len()];
+        let mut order = vec![];
+        while let Some(next) = (0..self.passes.len())
+            .find(|&i| !done[i] && dependencies[i].iter().all(|&j| done[j]))
+        {
+            done[next] = true;
+            order.push(next);
+        }
+
+        if order.len() < self.passes.len() {
+            return Err(RenderGraphError::Cycle(
+                (0..self.passes.len())
+                    .filter(|&i| !done[i])
+                    .map(|i| self.passes[i].0.clone())
+                    .collect(),
+            ));
+        }
+
+        self.order = Some(order);
+
+        Ok(())
+    }
+
+    /// The pass names in the order they run, `None` until compiled.
+    pub fn pass_order(&self) -> Option<Vec<&str>> {
+        self.order.as_ref().map(|order| {
+            order.iter().map(|&i| self.passes[i].0.as_str()).collect()
+        })
+    }
+
+    /// Runs the passes in order. The targets are drawn into one encoder
+    /// that's submitted right away, wgpu inserts the barriers between a
+    /// target being drawn into and sampled. The main pass adds to the
+    /// frame's rectangles and the post process pass sets the post process
+    /// shader, both end up on screen with the next `render`.
+    pub fn execute(
+        &mut self,
+        context: &mut Context<'a>,
+    ) -> Result<(), RenderGraphError> {
+        let order = self.order.clone().ok_or(RenderGraphError::NotCompiled)?;
+
+        let mut encoder = context.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor {
+                label: Some("Render Graph Encoder"),
+            },
+        );
+
+        for index in order {
+            let (_, pass) = &mut self.passes[index];
+
+            match (&mut pass.kind, self.resources.get(&pass.output)) {
+                (PassKind::Draw(draw), Some(&target)) => {
+                    context
+                        .begin_target(target)
+                        .map_err(RenderGraphError::Texture)?;
+                    draw(context);
+                    context.end_target().map_err(RenderGraphError::Texture)?;
+
+                    // encoded now instead of before the next frame, so the
+                    // passes after this one sample what it drew
+                    context.encode_target(&mut encoder, target.index);
+                }
+
+                // the swap chain
+                (PassKind::Draw(draw), None) => draw(context),
+
+                (PassKind::PostProcess(wgsl), _) => {
+                    let current = context.post_process.as_ref();
+                    if current.map(|post_process| &post_process.wgsl)
+                        != Some(wgsl)
+                    {
+                        context
+                            .set_post_process_shader(wgsl)
+                            .map_err(RenderGraphError::Shader)?;
+                    }
+                }
+            }
+        }
+
+        context.queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a graph with a target for each name, the passes never run
+    fn graph_with(targets: &[&str]) -> RenderGraph<'static> {
+        let mut graph = RenderGraph::new();
+        for (index, name) in targets.iter().enumerate() {
+            graph.add_target(
+                name,
+                RenderTargetHandle {
+                    index,
+                    texture: index,
+                },
+            );
+        }
+
+        graph
+    }
+
+    fn pass(inputs: &[&str], output: &str) -> PassDescriptor<'static> {
+        PassDescriptor::draw(inputs, output, |_| {})
+    }
+
+    fn cycle(graph: &mut RenderGraph) -> Vec<String> {
+        match graph.compile() {
+            Err(RenderGraphError::Cycle(passes)) => passes,
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn duplicate_pass() {
+        let mut graph = graph_with(&["a", "b"]);
+        graph.add_pass("shadow", pass(&[], "a"));
+        graph.add_pass("shadow", pass(&[], "b"));
+
+        assert!(matches!(
+            graph.compile(),
+            Err(RenderGraphError::DuplicatePass(name)) if name == "shadow"
+        ));
+    }
+
+    #[test]
+    fn unknown_resource() {
+        let mut graph = graph_with(&["a"]);
+        graph.add_pass("scene", pass(&["a", "missing"], SWAP_CHAIN));
+
+        assert!(matches!(
+            graph.compile(),
+            Err(RenderGraphError::UnknownResource { pass, resource })
+                if pass == "scene" && resource == "missing"
+        ));
+        assert_eq!(graph.pass_order(), None);
+
+        let mut graph = graph_with(&[]);
+        graph.add_pass("minimap", pass(&[], "missing"));
+        assert!(matches!(
+            graph.compile(),
+            Err(RenderGraphError::UnknownResource { .. })
+        ));
+    }
+
+    #[test]
+    fn duplicate_output() {
+        let mut graph = graph_with(&["a"]);
+        graph.add_pass("first", pass(&[], "a"));
+        graph.add_pass("second", pass(&[], "a"));
+
+        assert!(matches!(
+            graph.compile(),
+            Err(RenderGraphError::DuplicateOutput(resource)) if resource == "a"
+        ));
+    }
+
+    #[test]
+    fn cycles() {
+        // samples what it draws into
+        let mut graph = graph_with(&["a"]);
+        graph.add_pass("feedback", pass(&["a"], "a"));
+        assert_eq!(cycle(&mut graph), ["feedback"]);
+
+        let mut graph = graph_with(&["a", "b"]);
+        graph.add_pass("scene", pass(&[], SWAP_CHAIN));
+        graph.add_pass("first", pass(&["b"], "a"));
+        graph.add_pass("second", pass(&["a"], "b"));
+        assert_eq!(cycle(&mut graph), ["first", "second"]);
+    }
+
+    #[test]
+    fn passes_run_after_what_they_sample() {
+        let mut graph = graph_with(&["a", "b"]);
+        graph.add_pass("post", PassDescriptor::post_process(""));
+        graph.add_pass("scene", pass(&["a", "b"], SWAP_CHAIN));
+        graph.add_pass("ui", pass(&[], "b"));
+        graph.add_pass("shadow", pass(&[], "a"));
+        graph.compile().unwrap();
+
+        assert_eq!(
+            graph.pass_order(),
+            Some(vec!["ui", "shadow", "scene", "post"])
+        );
+    }
+
+    #[test]
+    fn independent_passes_keep_their_order() {
+        let mut graph = graph_with(&["a", "b", "c"]);
+        graph.add_pass("c", pass(&[], "c"));
+        graph.add_pass("a", pass(&[], "a"));
+        graph.add_pass("b", pass(&[], "b"));
+        graph.compile().unwrap();
+        assert_eq!(graph.pass_order(), Some(vec!["c", "a", "b"]));
+
+        // adding a pass throws the order away until it's compiled again
+        graph.add_pass("scene", pass(&["c"], SWAP_CHAIN));
+        assert_eq!(graph.pass_order(), None);
+        graph.compile().unwrap();
+        assert_eq!(graph.pass_order(), Some(vec!["c", "a", "b", "scene"]));
+    }
+}
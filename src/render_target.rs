@@ -0,0 +1,352 @@
+use wgpu::util::DeviceExt;
+
+use crate::{
+    atlas::SpriteHandle,
+    context::{
+        create_rectangles_buffer, create_textures_bind_group,
+        create_uniform_bind_group, grow_rectangles_buffer, Context,
+        RectangleDrawData, Texture, TextureError, TextureFormat, TextureHandle,
+        INITIAL_RECTANGLES_CAPACITY,
+    },
+    loader::TextureState,
+    material::{material_pipeline, material_runs},
+};
+
+// same as the textures loaded from images
+pub const RENDER_TARGET_FORMAT: wgpu::TextureFormat =
+    wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// A texture that rectangles can be drawn into. Everything drawn between
+// Context::begin_target and Context::end_target ends up in it instead of on
+// the screen, and it can be drawn like any other texture afterwards.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RenderTargetHandle {
+    pub index: usize,
+    pub texture: TextureHandle,
+}
+
+impl From<RenderTargetHandle> for SpriteHandle {
+    fn from(target: RenderTargetHandle) -> Self {
+        target.texture.into()
+    }
+}
+
+pub struct RenderTarget {
+    pub texture: TextureHandle,
+    pub size: (u32, u32),
+
+    pub rectangles_to_render: Vec<RectangleDrawData>,
+
+    // targets have their own projection (sized to the target, not the
+    // window) and rectangles, so they also need their own buffers
+    pub projection_buffer: wgpu::Buffer,
+    pub rectangles_buffer: wgpu::Buffer,
+
+    // only rendered again when something was drawn into it
+    pub needs_render: bool,
+}
+
+// the texture, projection and rectangles buffer of a target. Also used to
+// create them again on a new device.
+pub fn create_render_target_resources(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+) -> (Texture, wgpu::Buffer, wgpu::Buffer) {
+    let texture_size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        size: texture_size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: RENDER_TARGET_FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC,
+        label: Some("Render target texture"),
+        view_formats: &[],
+    });
+    let texture_view =
+        texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let projection_buffer =
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Render Target Projection Buffer"),
+            contents: &Context::calculate_projection_matrix(
+                width as f32,
+                height as f32,
+            ),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+    let rectangles_buffer = create_rectangles_buffer(
+        device,
+        "Render Target Rectangles Buffer",
+        INITIAL_RECTANGLES_CAPACITY,
+    );
+
+    (
+        Texture {
+            wgpu_texture: texture,
+            wgpu_texture_view: texture_view,
+            size: (width, height),
+            format: TextureFormat::Rgba8UnormSrgb,
+            path: None,
+            state: TextureState::Ready,
+        },
+        projection_buffer,
+        rectangles_buffer,
+    )
+}
+
+impl<'a> Context<'a> {
+    pub fn create_render_target(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) -> Result<RenderTargetHandle, TextureError> {
+        let (texture, projection_buffer, rectangles_buffer) =
+            create_render_target_resources(&self.device, width, height);
+        let texture = self.add_texture(texture)?;
+
+        self.render_targets.push(RenderTarget {
+            texture,
+            size: (width, height),
+            rectangles_to_render: vec![],
+            projection_buffer,
+            rectangles_buffer,
+            needs_render: true,
+        });
+
+        Ok(RenderTargetHandle {
+            index: self.render_targets.len() - 1,
+            texture,
+        })
+    }
+
+    /// Everything drawn until `end_target` goes into the target (replacing
+    /// what was drawn into it before) instead of the window. Targets can't
+    /// be nested.
+    pub fn begin_target(
+        &mut self,
+        handle: RenderTargetHandle,
+    ) -> Result<(), TextureError> {
+        // the main window's rectangles are swapped out while drawing into
+        // another window as well
+        if self.active_render_target.is_some() || self.active_window.is_some() {
+            return Err(TextureError::NestedRenderTarget);
+        }
+
+        let target = self
+            .render_targets
+            .get_mut(handle.index)
+            .ok_or(TextureError::InvalidHandle)?;
+
+        // the draw functions always push to rectangles_to_render, so swap
+        // the target's list in while it's active
+        target.rectangles_to_render.clear();
+        std::mem::swap(
+            &mut self.rectangles_to_render,
+            &mut target.rectangles_to_render,
+        );
+
+        self.active_render_target = Some(handle.index);
+
+        Ok(())
+    }
+
+    /// Goes back to drawing into the window.
+    pub fn end_target(&mut self) -> Result<(), TextureError> {
+        let index = self
+            .active_render_target
+            .take()
+            .ok_or(TextureError::NoActiveRenderTarget)?;
+
+        let target = &mut self.render_targets[index];
+        std::mem::swap(
+            &mut self.rectangles_to_render,
+            &mut target.rectangles_to_render,
+        );
+        target.needs_render = true;
+
+        Ok(())
+    }
+
+    // renders the targets that changed since the last frame. This goes into
+    // the same encoder before the main pass, so the main pass sees the new
+    // contents.
+    pub fn render_dirty_targets(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        for index in 0..self.render_targets.len() {
+            let target = &self.render_targets[index];
+
+            // still being drawn into
+            if !target.needs_render || self.active_render_target == Some(index)
+            {
+                continue;
+            }
+
+            self.encode_target(encoder, index);
+        }
+    }
+
+    // draws what was drawn into the target with begin_target
+    pub fn encode_target(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        index: usize,
+    ) {
+        let target = &mut self.render_targets[index];
+        target.needs_render = false;
+
+        let rectangles = std::mem::take(&mut target.rectangles_to_render);
+        self.encode_target_pass(encoder, index, &rectangles, false);
+        self.render_targets[index].rectangles_to_render = rectangles;
+    }
+
+    /// Draws what would be drawn into the window into the target instead,
+    /// scaled to its size, e.g. for a minimap or a reflection that's drawn
+    /// into the window afterwards. Works like the window's pass, except
+    /// that polygons, the stencil buffer, clip rects, blend modes and the
+    /// post process effect are left out, like for everything else drawn
+    /// into targets. Until the target is drawn into with `begin_target`
+    /// again it keeps this.
+    pub fn render_to(
+        &mut self,
+        handle: RenderTargetHandle,
+    ) -> Result<(), TextureError> {
+        // the window's rectangles are swapped out
+        if self.active_render_target.is_some() || self.active_window.is_some() {
+            return Err(TextureError::NestedRenderTarget);
+        }
+        if handle.index >= self.render_targets.len() {
+            return Err(TextureError::InvalidHandle);
+        }
+
+        self.push_constant_value =
+            self.time_override.unwrap_or_else(|| self.elapsed_time());
+        if !self.push_constants_supported {
+            self.queue.write_buffer(
+                &self.time_buffer,
+                0,
+                bytemuck::bytes_of(&self.push_constant_value),
+            );
+        }
+
+        let mut encoder = self.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor {
+                label: Some("Render Target Encoder"),
+            },
+        );
+
+        let rectangles = std::mem::take(&mut self.rectangles_to_render);
+        self.encode_target_pass(&mut encoder, handle.index, &rectangles, true);
+        self.rectangles_to_render = rectangles;
+
+        // what was drawn into it with begin_target would replace this
+        self.render_targets[handle.index].needs_render = false;
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(())
+    }
+
+    // Draws `rectangles` into the target. With `whole_frame` they're
+    // positioned like in the window and the target is cleared to the clear
+    // color, otherwise they're in the target's own pixels on transparent.
+    fn encode_target_pass(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        index: usize,
+        rectangles: &[RectangleDrawData],
+        whole_frame: bool,
+    ) {
+        // the bind group is created below anyway, so it doesn't matter
+        // whether the buffer was recreated
+        let target = &mut self.render_targets[index];
+        let (_, rectangle_count) = grow_rectangles_buffer(
+            &self.device,
+            &mut target.rectangles_buffer,
+            "Render Target Rectangles Buffer",
+            rectangles.len(),
+        );
+
+        let bytes: &[u8] = bytemuck::cast_slice(&rectangles[..rectangle_count]);
+        self.queue.write_buffer(&target.rectangles_buffer, 0, bytes);
+        self.current_frame_stats.record_buffer_write(bytes.len());
+
+        // both bind groups are created for every render
+        self.current_frame_stats.record_bind_group_rebuild();
+        self.current_frame_stats.record_bind_group_rebuild();
+
+        let target = &self.render_targets[index];
+        let (projection_buffer, clear_color) = match whole_frame {
+            true => (&self.projection_buffer, self.clear_color.into()),
+            false => (&target.projection_buffer, wgpu::Color::TRANSPARENT),
+        };
+
+        let uniform_bind_group = create_uniform_bind_group(
+            &self.device,
+            &self.uniform_bind_group_layout,
+            projection_buffer,
+            &target.rectangles_buffer,
+            &self.texture_settings_buffer,
+            &self.time_buffer,
+            &self.samplers,
+        );
+
+        // a texture can't be sampled while it's being rendered to, so the
+        // target's own slot is left empty
+        let textures_bind_group = create_textures_bind_group(
+            &self.device,
+            &self.textures_bind_group_layout,
+            self.texture_binding_mode,
+            &self.textures,
+            &self.empty_texture,
+            Some(target.texture),
+        );
+
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Target Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.textures[target.texture].wgpu_texture_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+        render_pass.set_bind_group(0, &uniform_bind_group, &[]);
+        render_pass.set_bind_group(1, &textures_bind_group, &[]);
+
+        if self.push_constants_supported {
+            render_pass.set_push_constants(
+                wgpu::ShaderStages::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&self.push_constant_value),
+            );
+        }
+
+        for (run, material) in material_runs(&rectangles[..rectangle_count], 0)
+        {
+            render_pass.set_pipeline(material_pipeline(
+                &self.materials,
+                &self.render_target_pipeline,
+                material,
+                true,
+            ));
+            render_pass.draw(0..6, run.start as u32..run.end as u32);
+            self.current_frame_stats.record_draw_call(run.len());
+        }
+    }
+}
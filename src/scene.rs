@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use crate::{
+    atlas::SpriteHandle,
+    context::{Context, RectangleDrawData},
+};
+
+// Something that gets drawn as part of a Scene. Nodes with a higher z are
+// drawn on top of the ones with a lower z.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SceneNode {
+    pub pos: [f32; 2],
+    pub size: [f32; 2],
+    // only used when there's no texture
+    pub color: [f32; 3],
+    pub texture: Option<SpriteHandle>,
+
+    pub z: i32,
+    pub visible: bool,
+}
+
+impl Default for SceneNode {
+    fn default() -> Self {
+        Self {
+            pos: [0.0, 0.0],
+            size: [0.0, 0.0],
+            color: [1.0, 1.0, 1.0],
+            texture: None,
+            z: 0,
+            visible: true,
+        }
+    }
+}
+
+// A set of named nodes that can be changed one at a time instead of building
+// the whole list of rectangles again. Draw it with Context::draw_scene.
+#[derive(Clone, Debug, Default)]
+pub struct Scene {
+    pub(crate) nodes: HashMap<String, SceneNode>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the node, replacing (and returning) the one that had the same
+    /// name.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        node: SceneNode,
+    ) -> Option<SceneNode> {
+        self.nodes.insert(name.into(), node)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SceneNode> {
+        self.nodes.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut SceneNode> {
+        self.nodes.get_mut(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<SceneNode> {
+        self.nodes.remove(name)
+    }
+
+    /// Removes every node whose name starts with `prefix`, which is handy
+    /// when the nodes of a component are named like "menu/button_1".
+    pub fn remove_all_with_prefix(&mut self, prefix: &str) {
+        self.nodes.retain(|name, _| !name.starts_with(prefix));
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+// RectangleDrawData::z has to be between -1 and 1. Node z values between
+// -SCENE_Z_RANGE and SCENE_Z_RANGE each get their own depth, the rest are
+// clamped.
+pub const SCENE_Z_RANGE: i32 = 1 << 16;
+
+fn scene_depth(z: i32) -> f32 {
+    z.clamp(-SCENE_Z_RANGE, SCENE_Z_RANGE) as f32 / SCENE_Z_RANGE as f32
+}
+
+impl<'a> Context<'a> {
+    /// Replaces everything that's drawn with the visible nodes of the scene,
+    /// ordered by z.
+    pub fn draw_scene(&mut self, scene: &Scene) {
+        let mut nodes: Vec<(&String, &SceneNode)> = scene
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.visible)
+            .collect();
+
+        // hashmap order changes between runs, so nodes with the same z are
+        // ordered by name to keep them from flickering
+        nodes.sort_by(|(name_a, a), (name_b, b)| {
+            a.z.cmp(&b.z).then_with(|| name_a.cmp(name_b))
+        });
+
+        self.rectangles_to_render.clear();
+        self.stencil_changes.clear();
+        self.clip_changes.clear();
+        self.rectangles_to_render
+            .extend(nodes.into_iter().map(|(_, node)| match node.texture {
+                Some(sprite) => RectangleDrawData {
+                    pos: node.pos,
+                    size: node.size,
+                    color: node.color.into(),
+                    texture_index: sprite.texture as i32,
+                    uv_pos: sprite.uv_pos,
+                    uv_size: sprite.uv_size,
+                    z: scene_depth(node.z),
+                    ..Default::default()
+                },
+                None => RectangleDrawData {
+                    pos: node.pos,
+                    size: node.size,
+                    color: node.color.into(),
+                    texture_index: -1,
+                    uv_pos: [0.0, 0.0],
+                    uv_size: [1.0, 1.0],
+                    z: scene_depth(node.z),
+                    ..Default::default()
+                },
+            }));
+    }
+}
@@ -0,0 +1,399 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    atlas::SpriteHandle,
+    color::Color,
+    context::{Context, RectangleDrawData, TextureHandle},
+    scene::{Scene, SceneNode},
+};
+
+#[derive(Debug)]
+pub enum SceneError {
+    CouldNotAccessFile(std::io::Error),
+    InvalidJson(serde_json::Error),
+    // a node uses a texture that isn't in the TextureAssetMap
+    UnknownTexture(TextureHandle),
+    UnknownTexturePath(String),
+    // an imported rectangle uses a texture without a path that isn't loaded
+    MissingTexture(TextureHandle),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::CouldNotAccessFile(e) => {
+                write!(f, "Could not access scene file: {}", e)
+            }
+            SceneError::InvalidJson(e) => {
+                write!(f, "Could not parse scene file: {}", e)
+            }
+            SceneError::UnknownTexture(handle) => {
+                write!(f, "Texture {} has no asset path.", handle)
+            }
+            SceneError::UnknownTexturePath(path) => {
+                write!(f, "No texture was loaded from {}.", path)
+            }
+            SceneError::MissingTexture(handle) => {
+                write!(f, "Texture {} doesn't exist.", handle)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+// Texture handles depend on the order textures were loaded in, so scene
+// files refer to textures by the path they were loaded from instead. This
+// goes from one to the other.
+#[derive(Clone, Debug, Default)]
+pub struct TextureAssetMap {
+    paths: HashMap<TextureHandle, String>,
+    handles: HashMap<String, TextureHandle>,
+}
+
+impl TextureAssetMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the texture, replacing whatever `handle` or `path` were mapped
+    /// to before.
+    pub fn insert(&mut self, handle: TextureHandle, path: impl Into<String>) {
+        let path = path.into();
+        if let Some(old_path) = self.paths.insert(handle, path.clone()) {
+            self.handles.remove(&old_path);
+        }
+        if let Some(old_handle) = self.handles.insert(path, handle) {
+            if old_handle != handle {
+                self.paths.remove(&old_handle);
+            }
+        }
+    }
+
+    pub fn path(&self, handle: TextureHandle) -> Option<&str> {
+        self.paths.get(&handle).map(String::as_str)
+    }
+
+    pub fn handle(&self, path: &str) -> Option<TextureHandle> {
+        self.handles.get(path).copied()
+    }
+}
+
+impl<'a> Context<'a> {
+    /// A map of every texture that was loaded from a file, for saving and
+    /// loading scenes.
+    pub fn texture_asset_map(&self) -> TextureAssetMap {
+        let mut assets = TextureAssetMap::new();
+        for (handle, texture) in self.textures.iter().enumerate() {
+            if let Some(path) = &texture.path {
+                assets.insert(handle, path.as_str());
+            }
+        }
+
+        assets
+    }
+}
+
+// what's actually written to the file, SceneNode with the texture's path
+// instead of its handle
+
+#[derive(Serialize, Deserialize)]
+struct SpriteEntry {
+    path: String,
+    uv_pos: [f32; 2],
+    uv_size: [f32; 2],
+}
+
+#[derive(Serialize, Deserialize)]
+struct NodeEntry {
+    pos: [f32; 2],
+    size: [f32; 2],
+    color: [f32; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    texture: Option<SpriteEntry>,
+    #[serde(default)]
+    z: i32,
+    #[serde(default = "default_visible")]
+    visible: bool,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize)]
+struct SceneEntry {
+    // sorted, so saving the same scene twice gives the same file
+    nodes: BTreeMap<String, NodeEntry>,
+}
+
+impl Scene {
+    /// Writes the scene to a JSON file. Textures are stored as their path
+    /// in `assets`, nodes with a texture that isn't in it can't be saved.
+    pub fn save_to_file(
+        &self,
+        path: &str,
+        assets: &TextureAssetMap,
+    ) -> Result<(), SceneError> {
+        let mut nodes = BTreeMap::new();
+        for (name, node) in &self.nodes {
+            let texture = match node.texture {
+                Some(sprite) => Some(SpriteEntry {
+                    path: assets
+                        .path(sprite.texture)
+                        .ok_or(SceneError::UnknownTexture(sprite.texture))?
+                        .to_string(),
+                    uv_pos: sprite.uv_pos,
+                    uv_size: sprite.uv_size,
+                }),
+                None => None,
+            };
+
+            nodes.insert(
+                name.clone(),
+                NodeEntry {
+                    pos: node.pos,
+                    size: node.size,
+                    color: node.color,
+                    texture,
+                    z: node.z,
+                    visible: node.visible,
+                },
+            );
+        }
+
+        let json = serde_json::to_string_pretty(&SceneEntry { nodes })
+            .map_err(SceneError::InvalidJson)?;
+        std::fs::write(path, json).map_err(SceneError::CouldNotAccessFile)
+    }
+
+    /// Reads a scene written by `save_to_file`. The textures have to be
+    /// loaded already, `assets` turns their paths back into handles.
+    pub fn load_from_file(
+        path: &str,
+        assets: &TextureAssetMap,
+    ) -> Result<Scene, SceneError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(SceneError::CouldNotAccessFile)?;
+        let entry: SceneEntry =
+            serde_json::from_str(&json).map_err(SceneError::InvalidJson)?;
+
+        let mut scene = Scene::new();
+        for (name, node) in entry.nodes {
+            let texture = match node.texture {
+                Some(sprite) => Some(SpriteHandle {
+                    texture: assets
+                        .handle(&sprite.path)
+                        .ok_or(SceneError::UnknownTexturePath(sprite.path))?,
+                    uv_pos: sprite.uv_pos,
+                    uv_size: sprite.uv_size,
+                }),
+                None => None,
+            };
+
+            scene.insert(
+                name,
+                SceneNode {
+                    pos: node.pos,
+                    size: node.size,
+                    color: node.color,
+                    texture,
+                    z: node.z,
+                    visible: node.visible,
+                },
+            );
+        }
+
+        Ok(scene)
+    }
+}
+
+// Everything that's drawn into the window, see Context::export_scene. Unlike
+// a Scene this is the draw list itself, so it also keeps materials, effects
+// and blend modes. Those are stored as their handles, textures that were
+// loaded from a file as their path.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneSnapshot {
+    pub rectangles: Vec<RectangleDrawData>,
+    // texture_index -> path for the textures the rectangles use that have
+    // one, sorted so exporting the same scene twice gives the same file
+    pub texture_paths: BTreeMap<i32, String>,
+    pub clear_color: Color,
+}
+
+impl SceneSnapshot {
+    pub fn save_to_file(&self, path: &str) -> Result<(), SceneError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(SceneError::InvalidJson)?;
+        std::fs::write(path, json).map_err(SceneError::CouldNotAccessFile)
+    }
+
+    pub fn load_from_file(path: &str) -> Result<SceneSnapshot, SceneError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(SceneError::CouldNotAccessFile)?;
+        serde_json::from_str(&json).map_err(SceneError::InvalidJson)
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Everything that's drawn into the window right now, to save it as a
+    /// document or compare it with an expected one in tests.
+    pub fn export_scene(&self) -> SceneSnapshot {
+        let assets = self.texture_asset_map();
+
+        let texture_paths = self
+            .rectangles_to_render
+            .iter()
+            .filter(|rectangle| rectangle.texture_index >= 0)
+            .filter_map(|rectangle| {
+                let path = assets.path(rectangle.texture_index as usize)?;
+                Some((rectangle.texture_index, path.to_string()))
+            })
+            .collect();
+
+        SceneSnapshot {
+            rectangles: self.rectangles_to_render.clone(),
+            texture_paths,
+            clear_color: self.clear_color,
+        }
+    }
+
+    /// Replaces what's drawn into the window with an exported scene, like
+    /// `set_rectangles` does. The textures with a path have to be loaded
+    /// already (in any order), the others are used as the handles they were
+    /// exported with and have to exist.
+    pub fn import_scene(
+        &mut self,
+        scene: &SceneSnapshot,
+    ) -> Result<(), SceneError> {
+        let rectangles = remap_textures(
+            scene,
+            &self.texture_asset_map(),
+            self.textures.len(),
+        )?;
+
+        self.set_rectangles(rectangles);
+        self.set_clear_color(scene.clear_color);
+
+        Ok(())
+    }
+}
+
+// The scene's rectangles with the texture handles they were exported with
+// turned into the current ones. Everything is checked before anything is
+// changed, so a failed import doesn't change anything.
+fn remap_textures(
+    scene: &SceneSnapshot,
+    assets: &TextureAssetMap,
+    texture_count: usize,
+) -> Result<Vec<RectangleDrawData>, SceneError> {
+    let mut handles = HashMap::new();
+    for (&index, path) in &scene.texture_paths {
+        let handle = assets
+            .handle(path)
+            .ok_or_else(|| SceneError::UnknownTexturePath(path.clone()))?;
+        handles.insert(index, handle as i32);
+    }
+
+    scene
+        .rectangles
+        .iter()
+        .map(|rectangle| {
+            let texture_index = match handles.get(&rectangle.texture_index) {
+                Some(&handle) => handle,
+                // colored
+                None if rectangle.texture_index < 0 => rectangle.texture_index,
+                None if (rectangle.texture_index as usize) < texture_count => {
+                    rectangle.texture_index
+                }
+                None => {
+                    return Err(SceneError::MissingTexture(
+                        rectangle.texture_index as usize,
+                    ))
+                }
+            };
+
+            Ok(RectangleDrawData {
+                texture_index,
+                ..*rectangle
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> SceneSnapshot {
+        let textured = RectangleDrawData {
+            pos: [10.0, 20.0],
+            size: [30.0, 40.0],
+            texture_index: 2,
+            corner_radius: 4.0,
+            ..Default::default()
+        };
+        let colored = RectangleDrawData {
+            pos: [0.5, 0.25],
+            color: Color::from_u8_rgb(200, 40, 40),
+            ..Default::default()
+        };
+        let unnamed = RectangleDrawData {
+            texture_index: 1,
+            ..Default::default()
+        };
+
+        SceneSnapshot {
+            rectangles: vec![textured, colored, unnamed],
+            texture_paths: BTreeMap::from([(2, "a.png".to_string())]),
+            clear_color: Color::from_u8_rgb(20, 30, 40),
+        }
+    }
+
+    fn bytes(rectangles: &[RectangleDrawData]) -> &[u8] {
+        bytemuck::cast_slice(rectangles)
+    }
+
+    #[test]
+    fn snapshots_survive_a_round_trip() {
+        let scene = snapshot();
+        let json = serde_json::to_string(&scene).unwrap();
+        let loaded: SceneSnapshot = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(bytes(&loaded.rectangles), bytes(&scene.rectangles));
+        assert_eq!(loaded.texture_paths, scene.texture_paths);
+        assert_eq!(loaded.clear_color, scene.clear_color);
+    }
+
+    #[test]
+    fn textures_are_looked_up_by_path() {
+        let mut assets = TextureAssetMap::new();
+        assets.insert(0, "a.png");
+
+        let rectangles = remap_textures(&snapshot(), &assets, 2).unwrap();
+        let indices: Vec<i32> =
+            rectangles.iter().map(|r| r.texture_index).collect();
+        assert_eq!(indices, [0, -1, 1]);
+    }
+
+    #[test]
+    fn missing_textures_fail_the_import() {
+        let mut assets = TextureAssetMap::new();
+        assert!(matches!(
+            remap_textures(&snapshot(), &assets, 2),
+            Err(SceneError::UnknownTexturePath(path)) if path == "a.png"
+        ));
+
+        // texture 1 has no path and only texture 0 is loaded
+        assets.insert(0, "a.png");
+        assert!(matches!(
+            remap_textures(&snapshot(), &assets, 1),
+            Err(SceneError::MissingTexture(1))
+        ));
+    }
+}
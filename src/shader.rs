@@ -0,0 +1,245 @@
+use crate::{
+    context::{TextureBindingMode, MAX_SAMPLERS, TIME_BINDING},
+    effect::build_effects_source,
+};
+
+pub const MAIN_SHADER: &str = include_str!("shader.wgsl");
+
+// where shader.wgsl is read from when hot reloading is enabled
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+pub const MAIN_SHADER_PATH: &str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl");
+
+// shader.wgsl doesn't declare the samplers or texture bindings itself, it
+// only calls sample_texture(). How the textures are bound depends on what
+// the adapter supports, so the declarations are generated here and put in
+// front of it. The same goes for `time`, which is a push constant if push
+// constants are supported and a uniform otherwise. The effects from
+// Context::register_effect go after it.
+pub fn build_shader_source(
+    main_shader: &str,
+    effects: &[String],
+    mode: TextureBindingMode,
+    push_constants_supported: bool,
+) -> String {
+    let mut source = String::new();
+
+    // PUSH CONSTANTS
+    // ==============
+
+    if push_constants_supported {
+        source.push_str("var<push_constant> time: f32;\n\n");
+    } else {
+        source.push_str(&format!(
+            "@group(0) @binding({TIME_BINDING})\nvar<uniform> time: f32;\n\n"
+        ));
+    }
+
+    // SAMPLERS
+    // ========
+
+    for i in 0..MAX_SAMPLERS {
+        source.push_str(&format!(
+            "@group(0) @binding({})\nvar sampler_{i}: sampler;\n",
+            3 + i
+        ));
+    }
+    source.push('\n');
+
+    // TEXTURES
+    // ========
+
+    match mode {
+        TextureBindingMode::BindingArray => {
+            source.push_str(
+                "@group(1) @binding(0)
+var texture_array: binding_array<texture_2d<f32>>;
+
+fn sample_texture_with_sampler(
+    index: i32, s: sampler, uv: vec2<f32>
+) -> vec4<f32> {
+    return textureSample(texture_array[index], s, uv);
+}
+",
+            );
+        }
+
+        TextureBindingMode::Bounded(count) => {
+            for i in 0..count {
+                source.push_str(&format!(
+                    "@group(1) @binding({i})\nvar texture_{i}: texture_2d<f32>;\n"
+                ));
+            }
+
+            // textureSample needs uniform control flow, which a switch on a
+            // per-rectangle index isn't. There are no mipmaps anyway so
+            // sampling level 0 explicitly gives the same result.
+            source.push_str(
+                "
+fn sample_texture_with_sampler(
+    index: i32, s: sampler, uv: vec2<f32>
+) -> vec4<f32> {
+    switch (index) {
+",
+            );
+            for i in 0..count {
+                source.push_str(&format!(
+                    "        case {i}: {{
+            return textureSampleLevel(texture_{i}, s, uv, 0.0);
+        }}
+"
+                ));
+            }
+            source.push_str(
+                "        default: {
+            return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        }
+    }
+}
+",
+            );
+        }
+    }
+
+    // pick the sampler the texture was created with, then rearrange the
+    // channels the way Swizzle says
+    source.push_str(
+        "
+fn sample_texture(index: i32, uv: vec2<f32>) -> vec4<f32> {
+    let settings = s_texture_settings[index];
+
+    var color: vec4<f32>;
+    switch (settings & 0xffu) {
+",
+    );
+    for i in 1..MAX_SAMPLERS {
+        source.push_str(&format!(
+            "        case {i}u: {{
+            color = sample_texture_with_sampler(index, sampler_{i}, uv);
+        }}
+"
+        ));
+    }
+    source.push_str(
+        "        default: {
+            color = sample_texture_with_sampler(index, sampler_0, uv);
+        }
+    }
+
+    switch (settings >> 8u) {
+        case 1u: {
+            return vec4<f32>(1.0, 1.0, 1.0, color.r);
+        }
+        case 2u: {
+            return vec4<f32>(color.r, color.r, color.r, 1.0);
+        }
+        case 3u: {
+            // a signed distance field with the edge at 0.5, anti-aliased
+            // over about a pixel at any scale
+            let edge_width = fwidth(color.r) * 0.5;
+            let alpha = smoothstep(0.5 - edge_width, 0.5 + edge_width, color.r);
+            return vec4<f32>(1.0, 1.0, 1.0, alpha);
+        }
+        default: {
+            return color;
+        }
+    }
+}
+",
+    );
+
+    source.push('\n');
+    source.push_str(main_shader);
+
+    source.push('\n');
+    source.push_str(&build_effects_source(effects));
+
+    source
+}
+
+// The module every built-in pipeline is created from: the SPIR-V one from
+// AppConfig::shader if there is one, otherwise the WGSL main shader with
+// the generated declarations in front of it.
+pub fn create_main_shader_module(
+    device: &wgpu::Device,
+    main_shader: &str,
+    effects: &[String],
+    spirv: Option<&[u32]>,
+    mode: TextureBindingMode,
+    push_constants_supported: bool,
+) -> wgpu::ShaderModule {
+    // naga translates it to whatever the backend needs, so this doesn't
+    // need SPIRV_SHADER_PASSTHROUGH
+    #[cfg(feature = "spirv")]
+    if let Some(spirv) = spirv {
+        return device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shader"),
+            source: wgpu::ShaderSource::SpirV(spirv.into()),
+        });
+    }
+    #[cfg(not(feature = "spirv"))]
+    debug_assert!(spirv.is_none());
+
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            build_shader_source(
+                main_shader,
+                effects,
+                mode,
+                push_constants_supported,
+            )
+            .into(),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what AppConfig::shader with SPIR-V expects: the generated source
+    // compiled offline. This doesn't need a GPU, so every combination of
+    // texture bindings and `time` is translated.
+    #[test]
+    fn generated_source_compiles_to_spirv() {
+        let effects = ["fn effect_1(in: VertexOutput) -> vec4<f32> {
+    return in.color * in.params;
+}"
+        .to_string()];
+
+        for mode in [
+            TextureBindingMode::BindingArray,
+            TextureBindingMode::Bounded(8),
+        ] {
+            for push_constants_supported in [true, false] {
+                let source = build_shader_source(
+                    MAIN_SHADER,
+                    &effects,
+                    mode,
+                    push_constants_supported,
+                );
+
+                let module = naga::front::wgsl::parse_str(&source)
+                    .unwrap_or_else(|e| {
+                        panic!("{}", e.emit_to_string(&source))
+                    });
+                let info = naga::valid::Validator::new(
+                    naga::valid::ValidationFlags::all(),
+                    naga::valid::Capabilities::all(),
+                )
+                .validate(&module)
+                .unwrap();
+                let spirv = naga::back::spv::write_vec(
+                    &module,
+                    &info,
+                    &naga::back::spv::Options::default(),
+                    None,
+                )
+                .unwrap();
+
+                assert_eq!(spirv[0], 0x07230203, "SPIR-V magic number");
+            }
+        }
+    }
+}
@@ -4,8 +4,19 @@
 struct RectangleDrawData {
     pos: vec2<f32>,
     size: vec2<f32>,
-    color: vec3<f32>,
-    texture_index: i32
+    color: vec4<f32>,
+    uv_pos: vec2<f32>,
+    uv_size: vec2<f32>,
+    texture_index: i32,
+    z: f32,
+    corner_radius: f32,
+    // only used on the CPU
+    material_and_flags: u32,
+    params: vec4<f32>,
+    effect: u32,
+    mask_radius: f32,
+    mask_pos: vec2<f32>,
+    mask_size: vec2<f32>,
 }
 
 @group(0) @binding(0)
@@ -14,17 +25,31 @@ var<uniform> u_projection: mat4x4<f32>;
 @group(0) @binding(1)
 var<storage, read> s_rectangles: array<RectangleDrawData>;
 
+// settings for each texture: the index into the samplers in the lowest 8
+// bits, the swizzle above that
 @group(0) @binding(2)
-var texture_sampler: sampler;
+var<storage, read> s_texture_settings: array<u32>;
 
-@group(1) @binding(0)
-var texture_array: binding_array<texture_2d<f32>>;
+// the samplers (bindings 3 and up), the texture bindings and
+// sample_texture() are generated in shader.rs, depending on whether binding
+// arrays are supported
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) uv: vec2<f32>,
     @location(1) texture_index: i32,
-    @location(2) color: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    // position inside the rectangle, from (0, 0) to size
+    @location(3) local_pos: vec2<f32>,
+    @location(4) @interpolate(flat) size: vec2<f32>,
+    @location(5) @interpolate(flat) corner_radius: f32,
+    // see Context::register_effect
+    @location(6) @interpolate(flat) params: vec4<f32>,
+    @location(7) @interpolate(flat) effect: u32,
+    // the mask's position relative to the rectangle and its size, see
+    // Context::draw_masked
+    @location(8) @interpolate(flat) mask: vec4<f32>,
+    @location(9) @interpolate(flat) mask_radius: f32,
 };
 
 fn choose_vertex_corner(
@@ -83,11 +108,13 @@ fn get_vertex_uv_coordinates(
     }
 }
 
+// every rectangle is an instance with 6 vertices
 @vertex
 fn vs_main(
     @builtin(vertex_index) in_vertex_index: u32,
+    @builtin(instance_index) in_instance_index: u32,
 ) -> VertexOutput {
-    let rectangle = s_rectangles[in_vertex_index / 6];
+    let rectangle = s_rectangles[in_instance_index];
 
     let x = rectangle.pos.x;
     let y = rectangle.pos.y;
@@ -99,32 +126,143 @@ fn vs_main(
 	let bl = vec2<f32>(x, y + h);
 	let br = vec2<f32>(x + w, y + h);
     
-    let coords = choose_vertex_corner(in_vertex_index % 6, tl, tr, bl, br);
+    let coords = choose_vertex_corner(in_vertex_index, tl, tr, bl, br);
 
     var out: VertexOutput;
 
+    // the projection maps z from -1..1 to a depth of 1..0, so a higher z
+    // ends up in front
     out.position = u_projection * vec4<f32>(
         coords.x,
         coords.y,
-        0.0, 1.0
+        rectangle.z, 1.0
     );
 
-    out.uv = get_vertex_uv_coordinates(in_vertex_index % 6);
+    // the corner's uv coordinates mapped into the rectangle's uv region
+    out.uv = rectangle.uv_pos
+        + get_vertex_uv_coordinates(in_vertex_index) * rectangle.uv_size;
 
     out.texture_index = rectangle.texture_index;
     out.color = rectangle.color;
 
+    out.local_pos = get_vertex_uv_coordinates(in_vertex_index)
+        * rectangle.size;
+    out.size = rectangle.size;
+    out.corner_radius = rectangle.corner_radius;
+    out.params = rectangle.params;
+    out.effect = rectangle.effect;
+    out.mask = vec4<f32>(
+        rectangle.mask_pos - rectangle.pos, rectangle.mask_size
+    );
+    out.mask_radius = rectangle.mask_radius;
+
+    return out;
+}
+
+// polygons are already triangulated and come from a vertex buffer. They're
+// drawn like untextured rectangles without rounded corners.
+struct PolygonVertex {
+    @location(0) pos: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_polygon(vertex: PolygonVertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.position = u_projection * vec4<f32>(vertex.pos, 0.0, 1.0);
+    out.uv = vec2<f32>(0.0, 0.0);
+    out.texture_index = -1;
+    out.color = vertex.color;
+    out.local_pos = vec2<f32>(0.0, 0.0);
+    out.size = vec2<f32>(0.0, 0.0);
+    out.corner_radius = 0.0;
+    out.params = vec4<f32>(0.0);
+    out.effect = 0u;
+    out.mask = vec4<f32>(0.0);
+    out.mask_radius = 0.0;
+
     return out;
 }
 
 /// FRAGMENT SHADER
 /// ===============
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+// signed distance from the edge of a rectangle with rounded corners,
+// negative inside
+fn rounded_rect_sdf(
+    local_pos: vec2<f32>, size: vec2<f32>, radius: f32
+) -> f32 {
+    let r = min(radius, min(size.x, size.y) * 0.5);
+    let q = abs(local_pos - size * 0.5) - size * 0.5 + r;
+
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - r;
+}
+
+// the color of a rectangle's pixel, before blending
+fn rectangle_color(in: VertexOutput) -> vec4<f32> {
+    // fwidth has to be called outside of the branches below
+    let distance = rounded_rect_sdf(in.local_pos, in.size, in.corner_radius);
+    let edge_width = fwidth(distance) * 0.5;
+    let mask_distance = rounded_rect_sdf(
+        in.local_pos - in.mask.xy, in.mask.zw, in.mask_radius
+    );
+    let mask_edge_width = fwidth(mask_distance) * 0.5;
+
+    // textures are tinted by the rectangle's color
+    var color: vec4<f32>;
     if in.texture_index == -1 {
-        return vec4<f32>(in.color, 1.0);
+        color = in.color;
     } else {
-        return textureSample(texture_array[0], texture_sampler, in.uv);
+        color = sample_texture(in.texture_index, in.uv) * in.color;
+    }
+
+    // fade out over about a pixel around the edge of rounded corners
+    if in.corner_radius > 0.0 {
+        color.a *= 1.0 - smoothstep(-edge_width, edge_width, distance);
     }
-}
\ No newline at end of file
+
+    // the same for the mask
+    if in.mask.z > 0.0 && in.mask.w > 0.0 {
+        color.a *= 1.0 - smoothstep(
+            -mask_edge_width, mask_edge_width, mask_distance
+        );
+    }
+
+    // so invisible parts don't end up in the depth or stencil buffer
+    if color.a <= 0.0 {
+        discard;
+    }
+
+    return color;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return apply_effect(in);
+}
+
+// BlendMode::Multiply multiplies what's already there with this, which is
+// the color where the rectangle is opaque and white (no change) where it's
+// transparent
+@fragment
+fn fs_multiply(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = apply_effect(in);
+    return vec4<f32>(mix(vec3<f32>(1.0), color.rgb, color.a), color.a);
+}
+
+// BlendMode::Screen adds this to what's there, scaled down by what's
+// already bright
+@fragment
+fn fs_screen(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = apply_effect(in);
+    return vec4<f32>(color.rgb * color.a, color.a);
+}
+
+// BlendMode::Overlay doubles what's there where this is 1 and changes
+// nothing where it's 0.5 gray
+@fragment
+fn fs_overlay(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = apply_effect(in);
+    return vec4<f32>(mix(vec3<f32>(0.5), color.rgb, color.a), color.a);
+}
@@ -0,0 +1,189 @@
+use std::time::Instant;
+
+use crate::context::Context;
+
+// how many frames average_frame_stats is averaged over
+pub const FRAME_STATS_SAMPLES: usize = 60;
+
+// What the renderer did during a frame, counted from the end of one
+// render() to the end of the next, so uploads made before drawing count
+// towards the frame they're drawn in. Only counted with the "stats"
+// feature, without it the record_ functions are empty and everything stays
+// at 0.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub rectangles_drawn: u32,
+    // left out by culling, see Context::set_culling
+    pub rectangles_culled: u32,
+    pub draw_calls: u32,
+    // writes to textures, including the initial upload of new ones
+    pub texture_uploads: u32,
+    pub buffer_writes: u32,
+    pub buffer_bytes_written: u64,
+    pub bind_group_rebuilds: u32,
+
+    // seconds spent in render(), and how much of that was waiting for the
+    // surface texture and presenting it (mostly waiting for vsync)
+    pub cpu_frame_time: f32,
+    pub present_wait_time: f32,
+    // seconds from the start of the previous frame to the start of this
+    // one, and how much of that the frame rate limiter spent waiting out
+    // the end of it (see Context::set_target_fps)
+    pub frame_time: f32,
+    pub limiter_wait_time: f32,
+}
+
+// Instant::now() is cheap but not free, so frame times are only measured
+// with the feature
+#[inline(always)]
+pub fn stats_instant() -> Option<Instant> {
+    #[cfg(feature = "stats")]
+    return Some(Instant::now());
+
+    #[cfg(not(feature = "stats"))]
+    None
+}
+
+#[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+impl FrameStats {
+    /// The frame rate that was actually reached, with `set_target_fps`
+    /// that's the target or less. Use it on `average_frame_stats` to get a
+    /// steady number.
+    pub fn fps(&self) -> f32 {
+        if self.frame_time <= 0.0 {
+            return 0.0;
+        }
+
+        1.0 / self.frame_time
+    }
+
+    #[inline(always)]
+    pub fn record_draw_call(&mut self, rectangles: usize) {
+        #[cfg(feature = "stats")]
+        {
+            self.draw_calls += 1;
+            self.rectangles_drawn += rectangles as u32;
+        }
+    }
+
+    #[inline(always)]
+    pub fn record_culled(&mut self, rectangles: usize) {
+        #[cfg(feature = "stats")]
+        {
+            self.rectangles_culled += rectangles as u32;
+        }
+    }
+
+    #[inline(always)]
+    pub fn record_texture_upload(&mut self) {
+        #[cfg(feature = "stats")]
+        {
+            self.texture_uploads += 1;
+        }
+    }
+
+    #[inline(always)]
+    pub fn record_buffer_write(&mut self, bytes: usize) {
+        #[cfg(feature = "stats")]
+        {
+            self.buffer_writes += 1;
+            self.buffer_bytes_written += bytes as u64;
+        }
+    }
+
+    #[inline(always)]
+    pub fn record_bind_group_rebuild(&mut self) {
+        #[cfg(feature = "stats")]
+        {
+            self.bind_group_rebuilds += 1;
+        }
+    }
+
+    // `start` comes from stats_instant
+    #[inline(always)]
+    pub fn record_cpu_time(&mut self, start: Option<Instant>) {
+        if let Some(start) = start {
+            self.cpu_frame_time += start.elapsed().as_secs_f32();
+        }
+    }
+
+    #[inline(always)]
+    pub fn record_present_wait(&mut self, start: Option<Instant>) {
+        if let Some(start) = start {
+            self.present_wait_time += start.elapsed().as_secs_f32();
+        }
+    }
+
+    #[inline(always)]
+    pub fn record_limiter_wait(&mut self, start: Option<Instant>) {
+        if let Some(start) = start {
+            self.limiter_wait_time += start.elapsed().as_secs_f32();
+        }
+    }
+
+    #[inline(always)]
+    pub fn record_frame_time(&mut self, seconds: f32) {
+        #[cfg(feature = "stats")]
+        {
+            self.frame_time = seconds;
+        }
+    }
+}
+
+impl<'a> Context<'a> {
+    /// What was drawn and uploaded during the last frame, useful for
+    /// finding out why a frame is slow or whether culling and the other
+    /// optimizations kick in. Everything is 0 unless the "stats" feature is
+    /// enabled.
+    pub fn last_frame_stats(&self) -> &FrameStats {
+        &self.frame_stats
+    }
+
+    /// `last_frame_stats` averaged over the last `FRAME_STATS_SAMPLES`
+    /// frames, counts are rounded down.
+    pub fn average_frame_stats(&self) -> FrameStats {
+        let history = &self.frame_stats_history;
+        if history.is_empty() {
+            return FrameStats::default();
+        }
+
+        let frames = history.len();
+        let average = |field: fn(&FrameStats) -> u64| {
+            history.iter().map(field).sum::<u64>() / frames as u64
+        };
+        let average_time = |field: fn(&FrameStats) -> f32| {
+            history.iter().map(field).sum::<f32>() / frames as f32
+        };
+
+        FrameStats {
+            rectangles_drawn: average(|s| s.rectangles_drawn as u64) as u32,
+            rectangles_culled: average(|s| s.rectangles_culled as u64) as u32,
+            draw_calls: average(|s| s.draw_calls as u64) as u32,
+            texture_uploads: average(|s| s.texture_uploads as u64) as u32,
+            buffer_writes: average(|s| s.buffer_writes as u64) as u32,
+            buffer_bytes_written: average(|s| s.buffer_bytes_written),
+            bind_group_rebuilds: average(|s| s.bind_group_rebuilds as u64)
+                as u32,
+            cpu_frame_time: average_time(|s| s.cpu_frame_time),
+            present_wait_time: average_time(|s| s.present_wait_time),
+            frame_time: average_time(|s| s.frame_time),
+            limiter_wait_time: average_time(|s| s.limiter_wait_time),
+        }
+    }
+
+    // called at the end of render(), starts counting the next frame
+    pub fn finish_frame_stats(&mut self) {
+        self.current_frame_stats
+            .record_frame_time(self.frame_timer.delta_time());
+        self.frame_stats = std::mem::take(&mut self.current_frame_stats);
+
+        // nothing is counted without the feature, so there's no point in
+        // keeping a history of zeros
+        if cfg!(feature = "stats") {
+            if self.frame_stats_history.len() == FRAME_STATS_SAMPLES {
+                self.frame_stats_history.pop_front();
+            }
+            self.frame_stats_history.push_back(self.frame_stats);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{
+    context::{create_render_pipeline, Context},
+    depth::depth_stencil_state,
+};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum StencilMode {
+    // rectangles are drawn normally
+    #[default]
+    None,
+
+    // rectangles only mark their area in the stencil buffer, they aren't
+    // visible themselves
+    Write,
+
+    // rectangles only show up where the stencil buffer was marked
+    Test,
+}
+
+// what Test compares the stencil buffer against. Write increments it, so
+// this is an area that was written to exactly once.
+pub const STENCIL_REFERENCE: u32 = 1;
+
+// the variants of the window's pipeline used for the stencil modes (the
+// normal render_pipeline is used for StencilMode::None)
+pub struct StencilPipelines {
+    pub write: wgpu::RenderPipeline,
+    pub test: wgpu::RenderPipeline,
+}
+
+impl StencilPipelines {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        depth_test: bool,
+        sample_count: u32,
+    ) -> Self {
+        Self {
+            write: create_render_pipeline(
+                device,
+                layout,
+                shader,
+                "fs_main",
+                format,
+                wgpu::ColorWrites::empty(),
+                wgpu::BlendState::ALPHA_BLENDING,
+                Some(depth_stencil_state(depth_test, StencilMode::Write)),
+                sample_count,
+            ),
+            test: create_render_pipeline(
+                device,
+                layout,
+                shader,
+                "fs_main",
+                format,
+                wgpu::ColorWrites::ALL,
+                wgpu::BlendState::ALPHA_BLENDING,
+                Some(depth_stencil_state(depth_test, StencilMode::Test)),
+                sample_count,
+            ),
+        }
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Everything drawn until `end_stencil_write` isn't visible, it only
+    /// marks the area it covers as a mask. Needs
+    /// `AppConfig::enable_stencil_buffer`, and only works when drawing to
+    /// the window (not to render targets).
+    pub fn begin_stencil_write(&mut self) {
+        self.set_stencil_mode(StencilMode::Write);
+    }
+
+    pub fn end_stencil_write(&mut self) {
+        self.set_stencil_mode(StencilMode::None);
+    }
+
+    /// Everything drawn until `end_stencil_test` only shows up inside the
+    /// mask drawn between `begin_stencil_write` and `end_stencil_write`.
+    pub fn begin_stencil_test(&mut self) {
+        self.set_stencil_mode(StencilMode::Test);
+    }
+
+    pub fn end_stencil_test(&mut self) {
+        self.set_stencil_mode(StencilMode::None);
+    }
+
+    fn set_stencil_mode(&mut self, mode: StencilMode) {
+        if self.stencil_pipelines.is_none() {
+            log::warn!("The stencil buffer isn't enabled");
+            return;
+        }
+        if self.active_render_target.is_some() {
+            log::warn!("Render targets don't have a stencil buffer");
+            return;
+        }
+
+        // the rectangles are drawn in one go, so the mode is remembered
+        // along with the rectangle it starts at and the draw is split there
+        self.stencil_changes
+            .push((self.rectangles_to_render.len(), mode));
+    }
+}
@@ -0,0 +1,142 @@
+use image::{DynamicImage, RgbaImage};
+use resvg::{tiny_skia, usvg};
+
+use crate::context::{Context, TextureError, TextureHandle};
+
+// Rasterizes the SVG at the given size. If only one of width and height is
+// given the other one keeps the SVG's aspect ratio, if neither is given the
+// SVG's own size is used.
+pub fn rasterize_svg(
+    data: &[u8],
+    width: Option<u32>,
+    height: Option<u32>,
+) -> Result<DynamicImage, TextureError> {
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default())
+        .map_err(|e| TextureError::InvalidSvg(e.to_string()))?;
+
+    let svg_size = tree.size();
+    let aspect_ratio = svg_size.width() / svg_size.height();
+
+    let (width, height) = match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => {
+            (width, (width as f32 / aspect_ratio).round() as u32)
+        }
+        (None, Some(height)) => {
+            ((height as f32 * aspect_ratio).round() as u32, height)
+        }
+        (None, None) => (
+            svg_size.width().ceil() as u32,
+            svg_size.height().ceil() as u32,
+        ),
+    };
+
+    // tiny_skia can't make an empty pixmap
+    let mut pixmap = tiny_skia::Pixmap::new(width.max(1), height.max(1))
+        .ok_or_else(|| {
+            TextureError::InvalidSvg(format!(
+                "Can't rasterize at {}x{}",
+                width, height
+            ))
+        })?;
+
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(
+            pixmap.width() as f32 / svg_size.width(),
+            pixmap.height() as f32 / svg_size.height(),
+        ),
+        &mut pixmap.as_mut(),
+    );
+
+    // tiny_skia uses premultiplied alpha, textures don't
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+
+    let image =
+        RgbaImage::from_raw(pixmap.width(), pixmap.height(), pixels).unwrap();
+
+    Ok(DynamicImage::from(image))
+}
+
+impl<'a> Context<'a> {
+    /// Rasterizes an SVG file into a texture, see `rasterize_svg` for how
+    /// the size is picked.
+    pub fn create_texture_from_svg_path(
+        &mut self,
+        path: &str,
+        width: Option<u32>,
+        height: Option<u32>,
+    ) -> Result<TextureHandle, TextureError> {
+        let data =
+            std::fs::read(path).map_err(TextureError::CouldNotOpenFile)?;
+
+        let handle = self.create_texture_from_raw_data(&rasterize_svg(
+            &data, width, height,
+        )?)?;
+        self.textures[handle].path = Some(path.to_string());
+
+        Ok(handle)
+    }
+
+    pub fn create_texture_from_svg_bytes(
+        &mut self,
+        data: &[u8],
+        width: Option<u32>,
+        height: Option<u32>,
+    ) -> Result<TextureHandle, TextureError> {
+        self.create_texture_from_raw_data(&rasterize_svg(data, width, height)?)
+    }
+
+    /// Same as `create_texture_from_svg_path`, but the size is in logical
+    /// pixels, so it stays sharp on HiDPI displays.
+    pub fn create_texture_from_svg_path_scaled(
+        &mut self,
+        path: &str,
+        logical_width: Option<f32>,
+        logical_height: Option<f32>,
+    ) -> Result<TextureHandle, TextureError> {
+        let (width, height) =
+            self.to_physical_svg_size(logical_width, logical_height);
+
+        self.create_texture_from_svg_path(path, width, height)
+    }
+
+    /// Rasterizes the SVG file the texture was created from again at a new
+    /// size, e.g. after the scale factor changed. The handle stays the
+    /// same.
+    pub fn rerasterize_svg(
+        &mut self,
+        handle: TextureHandle,
+        width: Option<u32>,
+        height: Option<u32>,
+    ) -> Result<(), TextureError> {
+        let path = self
+            .textures
+            .get(handle)
+            .and_then(|texture| texture.path.clone())
+            .ok_or(TextureError::InvalidHandle)?;
+
+        let data =
+            std::fs::read(&path).map_err(TextureError::CouldNotOpenFile)?;
+
+        self.replace_texture(handle, &rasterize_svg(&data, width, height)?)
+    }
+
+    fn to_physical_svg_size(
+        &self,
+        logical_width: Option<f32>,
+        logical_height: Option<f32>,
+    ) -> (Option<u32>, Option<u32>) {
+        let scale =
+            |size: f32| (size * self.scale_factor as f32).round() as u32;
+
+        (logical_width.map(scale), logical_height.map(scale))
+    }
+}
@@ -0,0 +1,103 @@
+use std::{fs, time::SystemTime};
+
+use crate::{
+    context::{
+        create_texture_from_raw_data, write_to_texture, Context, TextureError,
+        TextureHandle,
+    },
+    loader::load_image_from_path,
+};
+
+// how many calls to update() there are between checking watched files
+pub const TEXTURE_WATCH_INTERVAL: u32 = 30;
+
+pub struct WatchedTexture {
+    pub handle: TextureHandle,
+    pub path: String,
+
+    // None if the file couldn't be read, it's picked up once it's back
+    pub modified: Option<SystemTime>,
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl<'a> Context<'a> {
+    /// Same as `create_texture_from_path`, but the file is checked for
+    /// changes every few frames and loaded again into the same handle, so
+    /// everything that draws it picks up the new version.
+    pub fn create_texture_from_path_watched(
+        &mut self,
+        path: &str,
+    ) -> Result<TextureHandle, TextureError> {
+        let modified = modified_time(path);
+        let handle = self.create_texture_from_path(path)?;
+
+        self.watched_textures.push(WatchedTexture {
+            handle,
+            path: path.to_string(),
+            modified,
+        });
+
+        Ok(handle)
+    }
+
+    /// Loads every watched texture whose file changed since it was last
+    /// loaded. This is called from `update()` every `TEXTURE_WATCH_INTERVAL`
+    /// frames, but it can also be called directly.
+    pub fn poll_texture_reloads(&mut self) {
+        for watched in self.watched_textures.iter_mut() {
+            let modified = modified_time(&watched.path);
+            if modified.is_none() || modified == watched.modified {
+                continue;
+            }
+            watched.modified = modified;
+
+            // the file might still be in the middle of being written, keep
+            // the old texture until it decodes (it will change again once
+            // it's done)
+            let data = match load_image_from_path(&watched.path) {
+                Ok(data) => data,
+                Err(e) => {
+                    log::warn!("Could not reload {}: {}", watched.path, e);
+                    continue;
+                }
+            };
+
+            let texture = &mut self.textures[watched.handle];
+            if let Err(e) = texture.format.check_image(&data) {
+                log::warn!("Could not reload {}: {}", watched.path, e);
+                continue;
+            }
+
+            if texture.size == (data.width(), data.height()) {
+                write_to_texture(
+                    &self.queue,
+                    &texture.wgpu_texture,
+                    texture.format,
+                    (0, 0),
+                    &data,
+                );
+                self.current_frame_stats.record_texture_upload();
+            } else {
+                // REALLOCATE
+                // ==========
+
+                let mut new_texture = create_texture_from_raw_data(
+                    &self.device,
+                    &self.queue,
+                    &data,
+                    texture.format,
+                );
+                new_texture.path = texture.path.take();
+
+                *texture = new_texture;
+                self.current_frame_stats.record_texture_upload();
+                self.textures_bind_group_dirty = true;
+            }
+
+            log::info!("Reloaded {}", watched.path);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::fmt;
+
+use crate::{
+    context::{Context, RectangleDrawData, TextureError, TextureHandle},
+    geometry::Vec2,
+};
+
+// tiles with this id are left empty
+pub const EMPTY_TILE: u16 = u16::MAX;
+
+#[derive(Debug)]
+pub enum TilemapError {
+    // columns * rows doesn't fit in a u32
+    TooManyTiles,
+}
+
+impl fmt::Display for TilemapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TilemapError::TooManyTiles => {
+                write!(f, "The tilemap has more than {} tiles.", u32::MAX)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TilemapError {}
+
+// atlas size, tile_size and atlas_columns, everything the tile uvs depend on
+type UvKey = ((u32, u32), (u32, u32), u32);
+
+// A grid of tiles from one atlas texture. The atlas is split into
+// `tile_size` cells, read row by row from the top left, and a tile's id is
+// the index of its cell. `tiles` is the grid row by row as well. Ids past
+// the last cell in the atlas aren't drawn.
+#[derive(Clone, Debug)]
+pub struct Tilemap {
+    // in texture pixels, also how big the tiles are drawn
+    pub tile_size: (u32, u32),
+    pub columns: u32,
+    pub rows: u32,
+    pub tiles: Vec<u16>,
+    pub atlas: TextureHandle,
+    pub atlas_columns: u32,
+
+    // uv_pos and uv_size of every tile id in the atlas, computed when the
+    // map is drawn for the first time and again when anything they depend
+    // on changed
+    uvs: Vec<([f32; 2], [f32; 2])>,
+    uvs_key: Option<UvKey>,
+}
+
+impl Tilemap {
+    /// A map where every tile is `EMPTY_TILE`.
+    pub fn new(
+        atlas: TextureHandle,
+        atlas_columns: u32,
+        tile_size: (u32, u32),
+        columns: u32,
+        rows: u32,
+    ) -> Result<Self, TilemapError> {
+        let count = columns
+            .checked_mul(rows)
+            .ok_or(TilemapError::TooManyTiles)?;
+
+        Ok(Self {
+            tile_size,
+            columns,
+            rows,
+            tiles: vec![EMPTY_TILE; count as usize],
+            atlas,
+            atlas_columns,
+            uvs: vec![],
+            uvs_key: None,
+        })
+    }
+
+    pub fn tile(&self, column: u32, row: u32) -> Option<u16> {
+        if column >= self.columns || row >= self.rows {
+            return None;
+        }
+
+        self.tiles
+            .get((row * self.columns + column) as usize)
+            .copied()
+    }
+
+    /// Does nothing outside of the map.
+    pub fn set_tile(&mut self, column: u32, row: u32, tile: u16) {
+        if column < self.columns && row < self.rows {
+            if let Some(slot) =
+                self.tiles.get_mut((row * self.columns + column) as usize)
+            {
+                *slot = tile;
+            }
+        }
+    }
+
+    // uv_pos and uv_size of a tile in an atlas of `atlas_size` pixels, None
+    // if the atlas doesn't have that many tiles
+    fn tile_uv(
+        &mut self,
+        tile: u16,
+        atlas_size: (u32, u32),
+    ) -> Option<([f32; 2], [f32; 2])> {
+        let key = (atlas_size, self.tile_size, self.atlas_columns);
+        if self.uvs_key != Some(key) {
+            self.uvs_key = Some(key);
+
+            let columns = self.atlas_columns.max(1);
+            let rows = atlas_size.1 / self.tile_size.1.max(1);
+            // EMPTY_TILE isn't a tile
+            let count = (columns as u64 * rows as u64).min(EMPTY_TILE as u64);
+
+            let uv_size = [
+                self.tile_size.0 as f32 / atlas_size.0 as f32,
+                self.tile_size.1 as f32 / atlas_size.1 as f32,
+            ];
+            self.uvs = (0..count as u32)
+                .map(|tile| {
+                    let (column, row) = (tile % columns, tile / columns);
+                    (
+                        [column as f32 * uv_size[0], row as f32 * uv_size[1]],
+                        uv_size,
+                    )
+                })
+                .collect();
+        }
+
+        self.uvs.get(tile as usize).copied()
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Draws the map with its top left corner at the offset. Only the tiles
+    /// the camera can see are drawn, so big maps cost as much as the part
+    /// of them that's on screen. The map keeps the tiles' uvs between
+    /// frames, which is why it's borrowed mutably.
+    pub fn draw_tilemap(
+        &mut self,
+        map: &mut Tilemap,
+        offset_x: f32,
+        offset_y: f32,
+    ) -> Result<(), TextureError> {
+        let atlas_size = self
+            .texture_size(map.atlas)
+            .ok_or(TextureError::InvalidHandle)?;
+
+        // VISIBLE TILES
+        // =============
+
+        let (tile_width, tile_height) =
+            (map.tile_size.0 as f32, map.tile_size.1 as f32);
+        if tile_width <= 0.0 || tile_height <= 0.0 {
+            return Ok(());
+        }
+
+        let visible = self.camera.visible_area(self.viewport_size());
+        let start = visible.pos() - Vec2::new(offset_x, offset_y);
+        let end = start + visible.size();
+
+        // tiles that are only partly visible are drawn as well
+        let range = |start: f32, end: f32, size: f32, count: u32| {
+            let first = (start / size).floor().clamp(0.0, count as f32);
+            let last = (end / size).ceil().clamp(0.0, count as f32);
+            first as u32..last as u32
+        };
+        let columns = range(start.x, end.x, tile_width, map.columns);
+        let rows = range(start.y, end.y, tile_height, map.rows);
+
+        // DRAW
+        // ====
+
+        for row in rows {
+            for column in columns.clone() {
+                let tile = match map.tile(column, row) {
+                    Some(EMPTY_TILE) | None => continue,
+                    Some(tile) => tile,
+                };
+                let Some((uv_pos, uv_size)) = map.tile_uv(tile, atlas_size)
+                else {
+                    continue;
+                };
+
+                self.rectangles_to_render.push(RectangleDrawData {
+                    pos: [
+                        offset_x + column as f32 * tile_width,
+                        offset_y + row as f32 * tile_height,
+                    ],
+                    size: [tile_width, tile_height],
+                    uv_pos,
+                    uv_size,
+                    texture_index: map.atlas as i32,
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn too_many_tiles() {
+        assert!(matches!(
+            Tilemap::new(0, 4, (16, 16), u32::MAX, 2),
+            Err(TilemapError::TooManyTiles)
+        ));
+
+        let map = Tilemap::new(0, 4, (16, 16), 3, 2).unwrap();
+        assert_eq!(map.tiles.len(), 6);
+        assert_eq!(map.tile(2, 1), Some(EMPTY_TILE));
+        assert_eq!(map.tile(3, 1), None);
+    }
+
+    #[test]
+    fn tile_uvs() {
+        // 4 columns and 2 rows of 16x8 tiles
+        let mut map = Tilemap::new(0, 4, (16, 8), 1, 1).unwrap();
+
+        assert_eq!(map.tile_uv(0, (64, 16)), Some(([0.0, 0.0], [0.25, 0.5])));
+        assert_eq!(map.tile_uv(6, (64, 16)), Some(([0.5, 0.5], [0.25, 0.5])));
+        // past the last row
+        assert_eq!(map.tile_uv(8, (64, 16)), None);
+
+        // recomputed for a bigger atlas
+        assert_eq!(map.tile_uv(8, (64, 32)), Some(([0.0, 0.5], [0.25, 0.25])));
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    collections::VecDeque,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    context::{Context, RedrawMode},
+    stats::stats_instant,
+};
+
+// how many frames the fps is averaged over
+pub const FRAME_TIME_SAMPLES: usize = 60;
+
+// delta_time never gets longer than this, so a frame after the window was
+// dragged or the program sat at a breakpoint doesn't move everything by
+// seconds at once
+pub const DEFAULT_MAX_DELTA_TIME: f32 = 0.25;
+
+// The event loop's timers can be late by a few milliseconds, so with a
+// target fps it wakes up this long before the frame is due and the rest is
+// waited out with wait_precisely_until.
+pub const LIMITER_WAKE_MARGIN: Duration = Duration::from_millis(2);
+
+// sleeping is only precise to about a millisecond, so the end of the wait
+// is spent spinning
+pub const LIMITER_SPIN_TIME: Duration = Duration::from_micros(500);
+
+// Sleeps for most of the time left and spins for the rest, which gets
+// within a few microseconds of the deadline.
+pub fn wait_precisely_until(deadline: Instant) {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return;
+        }
+
+        if remaining > LIMITER_SPIN_TIME {
+            thread::sleep(remaining - LIMITER_SPIN_TIME);
+        } else {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+// steps per second of Context::set_fixed_update
+pub const DEFAULT_FIXED_UPDATE_RATE: f32 = 60.0;
+
+// After a stall there's more time to catch up on than the steps take, which
+// would make the next frame even longer. Time left over after this many
+// steps in one frame is dropped.
+pub const MAX_FIXED_STEPS_PER_FRAME: u32 = 8;
+
+// called with the context and the fixed step or the interpolation alpha, see
+// Context::set_fixed_update and Context::set_frame_update
+pub type UpdateCallback<'a> = Box<dyn FnMut(&mut Context<'a>, f32) + 'a>;
+
+// The accumulator for Context::set_fixed_update. Frames add their
+// delta_time to it and every whole step in it runs the callback once.
+pub struct FixedTimestep<'a> {
+    pub step: f32,
+    pub accumulator: f32,
+    // None runs everything with the variable delta_time only
+    pub fixed_update: Option<UpdateCallback<'a>>,
+    pub frame_update: Option<UpdateCallback<'a>>,
+    // whether there's a fixed update, also while it's taken out of
+    // fixed_update to be called
+    pub enabled: bool,
+    // set when the fixed update is replaced or cleared, so the one that's
+    // running isn't put back after it returns
+    pub changed: bool,
+}
+
+impl<'a> FixedTimestep<'a> {
+    pub fn new() -> Self {
+        Self {
+            step: 1.0 / DEFAULT_FIXED_UPDATE_RATE,
+            accumulator: 0.0,
+            fixed_update: None,
+            frame_update: None,
+            enabled: false,
+            changed: false,
+        }
+    }
+
+    // how far between the last step and the next one the current frame is,
+    // 0 to 1
+    pub fn alpha(&self) -> f32 {
+        if !self.enabled {
+            return 1.0;
+        }
+
+        (self.accumulator / self.step).clamp(0.0, 1.0)
+    }
+}
+
+impl<'a> Default for FixedTimestep<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Keeps track of how long frames take, measured from one RedrawRequested to
+// the next, and how much was uploaded to the GPU during them.
+pub struct FrameTimer {
+    last_frame: Option<Instant>,
+    delta_time: f32,
+    frame_times: VecDeque<f32>,
+    frame_times_sum: f32,
+
+    // bytes uploaded in each of the frames in frame_times
+    frame_uploads: VecDeque<u64>,
+    frame_uploads_sum: u64,
+    // bytes uploaded since the current frame started
+    current_upload: u64,
+}
+
+impl FrameTimer {
+    pub fn new() -> Self {
+        Self {
+            last_frame: None,
+            delta_time: 0.0,
+            frame_times: VecDeque::with_capacity(FRAME_TIME_SAMPLES),
+            frame_times_sum: 0.0,
+            frame_uploads: VecDeque::with_capacity(FRAME_TIME_SAMPLES),
+            frame_uploads_sum: 0,
+            current_upload: 0,
+        }
+    }
+
+    pub fn record_frame(&mut self, now: Instant) {
+        // the first frame has nothing to be compared to
+        if let Some(last_frame) = self.last_frame {
+            self.delta_time = (now - last_frame).as_secs_f32();
+
+            if self.frame_times.len() == FRAME_TIME_SAMPLES {
+                self.frame_times_sum -= self.frame_times.pop_front().unwrap();
+                self.frame_uploads_sum -=
+                    self.frame_uploads.pop_front().unwrap();
+            }
+            self.frame_times.push_back(self.delta_time);
+            self.frame_times_sum += self.delta_time;
+            self.frame_uploads.push_back(self.current_upload);
+            self.frame_uploads_sum += self.current_upload;
+        }
+
+        self.last_frame = Some(now);
+        self.current_upload = 0;
+    }
+
+    pub fn record_upload(&mut self, bytes: u64) {
+        self.current_upload += bytes;
+    }
+
+    pub fn delta_time(&self) -> f32 {
+        self.delta_time
+    }
+
+    pub fn average_frame_time(&self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+
+        self.frame_times_sum / self.frame_times.len() as f32
+    }
+
+    pub fn uploaded_bytes_per_second(&self) -> f32 {
+        if self.frame_times_sum <= 0.0 {
+            return 0.0;
+        }
+
+        self.frame_uploads_sum as f32 / self.frame_times_sum
+    }
+}
+
+impl Default for FrameTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Frames per second, averaged over the last `FRAME_TIME_SAMPLES`
+    /// frames. 0 until two frames were drawn.
+    pub fn fps(&self) -> f32 {
+        let frame_time = self.frame_timer.average_frame_time();
+        if frame_time <= 0.0 {
+            return 0.0;
+        }
+
+        1.0 / frame_time
+    }
+
+    /// Seconds between the start of the previous frame and the current one,
+    /// at most `max_delta_time`. Multiply movement by this so it doesn't
+    /// depend on the frame rate.
+    pub fn delta_time(&self) -> f32 {
+        self.frame_timer.delta_time().min(self.max_delta_time)
+    }
+
+    /// Limits `delta_time`, `DEFAULT_MAX_DELTA_TIME` by default. Frames
+    /// that took longer still count for `fps`.
+    pub fn set_max_delta_time(&mut self, seconds: f32) {
+        self.max_delta_time = seconds.max(0.0);
+    }
+
+    pub fn max_delta_time(&self) -> f32 {
+        self.max_delta_time
+    }
+
+    /// How many bytes of rectangles were uploaded to the GPU per second,
+    /// averaged over the last `FRAME_TIME_SAMPLES` frames. Stays at 0 while
+    /// the rectangles don't change.
+    pub fn uploaded_bytes_per_second(&self) -> f32 {
+        self.frame_timer.uploaded_bytes_per_second()
+    }
+
+    /// Calls `fixed_update` with the same step every time, 60 times per
+    /// second by default (see `set_fixed_update_rate`), for simulations
+    /// that should behave the same at any frame rate. A frame runs it as
+    /// often as whole steps passed since the last one, which can be zero
+    /// times. Draw between the last two states with `interpolation_alpha`,
+    /// e.g. from `set_frame_update`.
+    pub fn set_fixed_update(
+        &mut self,
+        fixed_update: impl FnMut(&mut Context<'a>, f32) + 'a,
+    ) {
+        self.fixed_timestep.fixed_update = Some(Box::new(fixed_update));
+        self.fixed_timestep.accumulator = 0.0;
+        self.fixed_timestep.enabled = true;
+        self.fixed_timestep.changed = true;
+    }
+
+    /// Goes back to only the variable `delta_time`.
+    pub fn clear_fixed_update(&mut self) {
+        self.fixed_timestep.fixed_update = None;
+        self.fixed_timestep.accumulator = 0.0;
+        self.fixed_timestep.enabled = false;
+        self.fixed_timestep.changed = true;
+    }
+
+    /// Fixed steps per second, `DEFAULT_FIXED_UPDATE_RATE` by default.
+    pub fn set_fixed_update_rate(&mut self, steps_per_second: f32) {
+        if steps_per_second > 0.0 {
+            self.fixed_timestep.step = 1.0 / steps_per_second;
+        }
+    }
+
+    /// The `dt` that `fixed_update` is called with.
+    pub fn fixed_delta_time(&self) -> f32 {
+        self.fixed_timestep.step
+    }
+
+    /// How far the current frame is between the last fixed step and the
+    /// next one, 0 to 1. Draw things at
+    /// `previous + (current - previous) * alpha` so they move smoothly
+    /// when the frame rate doesn't match the fixed rate. Always 1 without
+    /// `set_fixed_update`.
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.fixed_timestep.alpha()
+    }
+
+    /// Calls `frame_update` once per frame after the fixed steps, right
+    /// before drawing, with `interpolation_alpha`.
+    pub fn set_frame_update(
+        &mut self,
+        frame_update: impl FnMut(&mut Context<'a>, f32) + 'a,
+    ) {
+        self.fixed_timestep.frame_update = Some(Box::new(frame_update));
+    }
+
+    // Called by the event loop after update(), runs the fixed steps the
+    // frame's `dt` adds up to and then the frame update.
+    pub fn run_fixed_updates(&mut self, dt: f32) {
+        if let Some(mut fixed_update) = self.fixed_timestep.fixed_update.take()
+        {
+            self.fixed_timestep.changed = false;
+            let step = self.fixed_timestep.step;
+            self.fixed_timestep.accumulator += dt.max(0.0);
+
+            let mut steps = 0;
+            while self.fixed_timestep.accumulator >= step {
+                if steps == MAX_FIXED_STEPS_PER_FRAME {
+                    // give up on catching up, keep the fraction so alpha
+                    // doesn't jump
+                    self.fixed_timestep.accumulator %= step;
+                    break;
+                }
+
+                fixed_update(self, step);
+
+                // it replaced or cleared itself, which also reset the
+                // accumulator
+                if self.fixed_timestep.changed {
+                    break;
+                }
+
+                self.fixed_timestep.accumulator -= step;
+                steps += 1;
+            }
+
+            if !self.fixed_timestep.changed {
+                self.fixed_timestep.fixed_update = Some(fixed_update);
+            }
+        }
+
+        if let Some(mut frame_update) = self.fixed_timestep.frame_update.take()
+        {
+            frame_update(self, self.interpolation_alpha());
+
+            if self.fixed_timestep.frame_update.is_none() {
+                self.fixed_timestep.frame_update = Some(frame_update);
+            }
+        }
+    }
+
+    /// Calls `frame` once per frame with the frame's `dt`, after `update`
+    /// and the fixed steps and right before drawing. This is where a game's
+    /// per-frame logic and drawing go, see `run_with`.
+    pub fn set_frame_callback(
+        &mut self,
+        frame: impl FnMut(&mut Context<'a>, f32) + 'a,
+    ) {
+        self.frame_callback = Some(Box::new(frame));
+    }
+
+    pub fn clear_frame_callback(&mut self) {
+        self.frame_callback = None;
+    }
+
+    // called by the event loop after run_fixed_updates
+    pub fn run_frame_callback(&mut self, dt: f32) {
+        if let Some(mut frame) = self.frame_callback.take() {
+            frame(self, dt);
+
+            // unless it replaced itself
+            if self.frame_callback.is_none() {
+                self.frame_callback = Some(frame);
+            }
+        }
+    }
+
+    // Called when a frame starts. With a target fps the event loop woke up
+    // LIMITER_WAKE_MARGIN before the frame is due, this waits out the rest.
+    pub fn wait_for_frame_deadline(&mut self) {
+        if self.target_frame_duration.is_none()
+            || self.redraw_mode == RedrawMode::OnDemand
+        {
+            return;
+        }
+
+        if let Some(deadline) = self.next_frame_deadline {
+            // redraws the limiter didn't ask for (resizes, request_redraw)
+            // can come at any time and aren't held back
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining <= LIMITER_WAKE_MARGIN {
+                let wait_start = stats_instant();
+                wait_precisely_until(deadline);
+                self.current_frame_stats.record_limiter_wait(wait_start);
+            }
+        }
+    }
+
+    // Picks when the next frame is due and returns when the event loop
+    // should wake up for it. Deadlines follow each other, so the frame
+    // rate doesn't drift when waking up is a bit late, unless a frame was
+    // so late that the next one would come right away.
+    pub fn schedule_next_frame(&mut self, frame_duration: Duration) -> Instant {
+        let deadline = match self.next_frame_deadline {
+            Some(deadline)
+                if deadline + frame_duration > self.last_redraw_instant =>
+            {
+                deadline + frame_duration
+            }
+            _ => self.last_redraw_instant + frame_duration,
+        };
+        self.next_frame_deadline = Some(deadline);
+
+        deadline
+            .checked_sub(LIMITER_WAKE_MARGIN)
+            .unwrap_or(deadline)
+    }
+}
@@ -0,0 +1,506 @@
+use std::collections::HashMap;
+
+use fontdue::{Font, FontSettings, Metrics};
+use image::{DynamicImage, GrayImage};
+
+use crate::{
+    color::Color,
+    context::{
+        write_to_texture, Context, RectangleDrawData, SamplerOptions, Swizzle,
+        Texture, TextureError, TextureFormat, TextureHandle,
+    },
+    font::{wrap_lines, TextMetrics},
+    stats::FrameStats,
+};
+
+pub type TtfFontHandle = usize;
+
+// width and height of every font's glyph cache texture
+pub const GLYPH_CACHE_SIZE: u32 = 1024;
+
+// SDF glyphs are generated at this size, whatever size they're drawn at
+pub const SDF_REFERENCE_SIZE: f32 = 64.0;
+// how far the distance field reaches outside of (and into) the glyphs, in
+// pixels at the reference size
+pub const SDF_SPREAD: u32 = 8;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FontLoadMode {
+    // the glyphs are rasterized at the font's size, they get blurry when
+    // drawn bigger than that
+    #[default]
+    Rasterized,
+
+    // the glyphs are stored as signed distance fields, which stay sharp at
+    // any size
+    Sdf,
+}
+
+// 8SSEDT: the offset from every pixel to the closest pixel that's set in
+// `inside`, found with two passes over the grid
+fn distance_offsets(
+    inside: &[bool],
+    width: usize,
+    height: usize,
+) -> Vec<(i32, i32)> {
+    const FAR: (i32, i32) = (9999, 9999);
+    let length = |(dx, dy): (i32, i32)| dx * dx + dy * dy;
+
+    let mut grid: Vec<(i32, i32)> = inside
+        .iter()
+        .map(|&inside| if inside { (0, 0) } else { FAR })
+        .collect();
+
+    let compare =
+        |grid: &mut Vec<(i32, i32)>, x: usize, y: usize, ox: i32, oy: i32| {
+            let (nx, ny) = (x as i32 + ox, y as i32 + oy);
+            if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                return;
+            }
+
+            let other = grid[ny as usize * width + nx as usize];
+            let offset = (other.0 + ox, other.1 + oy);
+            if length(offset) < length(grid[y * width + x]) {
+                grid[y * width + x] = offset;
+            }
+        };
+
+    for y in 0..height {
+        for x in 0..width {
+            compare(&mut grid, x, y, -1, 0);
+            compare(&mut grid, x, y, 0, -1);
+            compare(&mut grid, x, y, -1, -1);
+            compare(&mut grid, x, y, 1, -1);
+        }
+        for x in (0..width).rev() {
+            compare(&mut grid, x, y, 1, 0);
+        }
+    }
+
+    for y in (0..height).rev() {
+        for x in (0..width).rev() {
+            compare(&mut grid, x, y, 1, 0);
+            compare(&mut grid, x, y, 0, 1);
+            compare(&mut grid, x, y, -1, 1);
+            compare(&mut grid, x, y, 1, 1);
+        }
+        for x in 0..width {
+            compare(&mut grid, x, y, -1, 0);
+        }
+    }
+
+    grid
+}
+
+// Turns a glyph's coverage into a distance field with `spread` pixels of
+// padding on every side. 0.5 is the edge, higher is inside.
+pub fn signed_distance_field(
+    coverage: &[u8],
+    width: usize,
+    height: usize,
+    spread: u32,
+) -> GrayImage {
+    let padding = spread as usize;
+    let (padded_width, padded_height) =
+        (width + 2 * padding, height + 2 * padding);
+
+    let mut inside = vec![false; padded_width * padded_height];
+    for y in 0..height {
+        for x in 0..width {
+            inside[(y + padding) * padded_width + x + padding] =
+                coverage[y * width + x] >= 128;
+        }
+    }
+    let outside: Vec<bool> = inside.iter().map(|inside| !inside).collect();
+
+    let to_inside = distance_offsets(&inside, padded_width, padded_height);
+    let to_outside = distance_offsets(&outside, padded_width, padded_height);
+
+    let distance = |(dx, dy): (i32, i32)| ((dx * dx + dy * dy) as f32).sqrt();
+    let pixels = to_inside
+        .iter()
+        .zip(to_outside.iter())
+        .map(|(&to_inside, &to_outside)| {
+            // positive inside the glyph
+            let signed = distance(to_outside) - distance(to_inside);
+            let value = 0.5 + signed / (2.0 * spread as f32);
+            (value.clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect();
+
+    GrayImage::from_raw(padded_width as u32, padded_height as u32, pixels)
+        .unwrap()
+}
+
+#[derive(Copy, Clone, Debug)]
+struct CachedGlyph {
+    // at raster_px
+    metrics: Metrics,
+    // None for glyphs without pixels, like spaces
+    cell: Option<usize>,
+}
+
+// A font rasterized at one size. Glyphs are rasterized the first time
+// they're drawn and kept in a texture made of equally sized cells. When all
+// cells are taken, the one that was drawn the longest time ago is reused.
+pub struct TtfFont {
+    font: Font,
+    pub size_px: f32,
+    pub mode: FontLoadMode,
+    // at size_px
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_height: f32,
+
+    // the size the glyphs in the cache are rasterized at, and the padding
+    // around them (the distance field's spread for SDF fonts)
+    raster_px: f32,
+    padding: u32,
+
+    pub texture: TextureHandle,
+    // cells are square, with a pixel of space between them so linear
+    // filtering doesn't pick up the neighbors
+    cell_size: u32,
+    columns: u32,
+    // which glyph is in each cell, and when it was last drawn
+    cells: Vec<Option<(char, u64)>>,
+    glyphs: HashMap<char, CachedGlyph>,
+    // goes up with every draw_text_ttf call
+    tick: u64,
+}
+
+impl TtfFont {
+    // the cache texture was emptied, every glyph is rasterized again the
+    // next time it's drawn
+    pub fn clear_glyph_cache(&mut self) {
+        self.cells.fill(None);
+        self.glyphs.clear();
+    }
+
+    fn text_width(&self, text: &str) -> f32 {
+        text.chars()
+            .map(|c| self.font.metrics(c, self.size_px).advance_width)
+            .sum()
+    }
+
+    fn cell_origin(&self, cell: usize) -> (u32, u32) {
+        let stride = self.cell_size + 1;
+        (
+            cell as u32 % self.columns * stride,
+            cell as u32 / self.columns * stride,
+        )
+    }
+
+    // rasterizes the glyph into the cache if it isn't there yet
+    fn cache_glyph(
+        &mut self,
+        queue: &wgpu::Queue,
+        texture: &Texture,
+        stats: &mut FrameStats,
+        c: char,
+    ) -> CachedGlyph {
+        if let Some(glyph) = self.glyphs.get(&c).copied() {
+            if let Some(cell) = glyph.cell {
+                self.cells[cell] = Some((c, self.tick));
+            }
+            return glyph;
+        }
+
+        let (metrics, coverage) = self.font.rasterize(c, self.raster_px);
+
+        let empty = metrics.width == 0 || metrics.height == 0;
+        let too_big = metrics.width as u32 + 2 * self.padding > self.cell_size
+            || metrics.height as u32 + 2 * self.padding > self.cell_size;
+        if too_big {
+            log::warn!("Glyph {:?} doesn't fit in a glyph cache cell", c);
+        }
+        if empty || too_big {
+            let glyph = CachedGlyph {
+                metrics,
+                cell: None,
+            };
+            self.glyphs.insert(c, glyph);
+            return glyph;
+        }
+
+        // EVICT
+        // =====
+
+        let cell = match self.cells.iter().position(|cell| cell.is_none()) {
+            Some(cell) => cell,
+            None => {
+                let (cell, (evicted, _)) = self
+                    .cells
+                    .iter()
+                    .enumerate()
+                    .map(|(i, cell)| (i, cell.unwrap()))
+                    .min_by_key(|(_, (_, last_used))| *last_used)
+                    .unwrap();
+
+                self.glyphs.remove(&evicted);
+                cell
+            }
+        };
+
+        // UPLOAD
+        // ======
+
+        let glyph_pixels = match self.mode {
+            FontLoadMode::Rasterized => GrayImage::from_raw(
+                metrics.width as u32,
+                metrics.height as u32,
+                coverage,
+            )
+            .unwrap(),
+            FontLoadMode::Sdf => signed_distance_field(
+                &coverage,
+                metrics.width,
+                metrics.height,
+                SDF_SPREAD,
+            ),
+        };
+
+        // the whole cell is written so nothing of the previous glyph is left
+        let mut pixels = GrayImage::new(self.cell_size, self.cell_size);
+        image::imageops::replace(&mut pixels, &glyph_pixels, 0, 0);
+
+        write_to_texture(
+            queue,
+            &texture.wgpu_texture,
+            TextureFormat::R8Unorm,
+            self.cell_origin(cell),
+            &DynamicImage::ImageLuma8(pixels),
+        );
+        stats.record_texture_upload();
+
+        self.cells[cell] = Some((c, self.tick));
+        let glyph = CachedGlyph {
+            metrics,
+            cell: Some(cell),
+        };
+        self.glyphs.insert(c, glyph);
+
+        glyph
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Loads a TrueType or OpenType font that's drawn at `size_px` pixels.
+    /// Every font gets its own glyph cache texture.
+    pub fn load_ttf_font(
+        &mut self,
+        data: &[u8],
+        size_px: f32,
+    ) -> Result<TtfFontHandle, TextureError> {
+        self.load_ttf_font_with_mode(data, size_px, FontLoadMode::Rasterized)
+    }
+
+    /// Same as `load_ttf_font`, but the glyphs can be stored as signed
+    /// distance fields, so they can be drawn at any size with
+    /// `draw_text_ttf_sized` without getting blurry.
+    pub fn load_ttf_font_with_mode(
+        &mut self,
+        data: &[u8],
+        size_px: f32,
+        mode: FontLoadMode,
+    ) -> Result<TtfFontHandle, TextureError> {
+        let font = Font::from_bytes(
+            data,
+            FontSettings {
+                scale: size_px,
+                ..Default::default()
+            },
+        )
+        .map_err(|e| TextureError::InvalidFont(e.to_string()))?;
+
+        // fontdue's descent is negative, going down from the baseline
+        let (ascent, descent, line_height) =
+            match font.horizontal_line_metrics(size_px) {
+                Some(line) => (line.ascent, -line.descent, line.new_line_size),
+                None => (size_px, 0.0, size_px),
+            };
+
+        let (raster_px, padding) = match mode {
+            FontLoadMode::Rasterized => (size_px, 0),
+            FontLoadMode::Sdf => (SDF_REFERENCE_SIZE, SDF_SPREAD),
+        };
+
+        let raster_line_height = font
+            .horizontal_line_metrics(raster_px)
+            .map_or(raster_px, |line| line.new_line_size);
+        let cell_size = raster_line_height.ceil() as u32 + 2 * padding;
+        let columns = GLYPH_CACHE_SIZE / (cell_size + 1);
+        if columns == 0 {
+            return Err(TextureError::InvalidFont(format!(
+                "{}px is too big for the glyph cache",
+                size_px
+            )));
+        }
+
+        // empty, glyphs are written into it as they're needed
+        let texture = self.create_texture_from_raw_data_with_format(
+            &DynamicImage::ImageLuma8(GrayImage::new(
+                GLYPH_CACHE_SIZE,
+                GLYPH_CACHE_SIZE,
+            )),
+            TextureFormat::R8Unorm,
+        )?;
+
+        // distance fields have to be interpolated to get smooth edges
+        if mode == FontLoadMode::Sdf {
+            self.set_texture_swizzle(texture, Swizzle::Sdf)?;
+            self.set_texture_sampler(
+                texture,
+                SamplerOptions {
+                    min_filter: wgpu::FilterMode::Linear,
+                    ..Default::default()
+                },
+            )?;
+        }
+
+        self.ttf_fonts.push(TtfFont {
+            font,
+            size_px,
+            mode,
+            ascent,
+            descent,
+            line_height,
+            raster_px,
+            padding,
+            texture,
+            cell_size,
+            columns,
+            cells: vec![None; (columns * columns) as usize],
+            glyphs: HashMap::new(),
+            tick: 0,
+        });
+
+        Ok(self.ttf_fonts.len() - 1)
+    }
+
+    /// How big `text` is when drawn with `draw_text_ttf`, or `None` if the
+    /// handle is invalid.
+    pub fn measure_text_ttf(
+        &self,
+        text: &str,
+        font: TtfFontHandle,
+    ) -> Option<TextMetrics> {
+        let font = self.ttf_fonts.get(font)?;
+
+        Some(TextMetrics {
+            width: font.text_width(text),
+            height: font.ascent + font.descent,
+            ascent: font.ascent,
+            descent: font.descent,
+        })
+    }
+
+    /// Same as `measure_text_line_breaks`, for TrueType fonts.
+    pub fn measure_text_ttf_line_breaks<'t>(
+        &self,
+        text: &'t str,
+        font: TtfFontHandle,
+        max_width: f32,
+    ) -> Option<Vec<&'t str>> {
+        let font = self.ttf_fonts.get(font)?;
+
+        Some(wrap_lines(text, max_width, |line| font.text_width(line)))
+    }
+
+    /// Draws a single line of text with its top left corner at `x`, `y`.
+    /// When the font's glyph cache is full, the glyphs that weren't drawn
+    /// for the longest time are replaced, so text that's still in
+    /// `rectangles_to_render` from long ago can show the wrong characters
+    /// until it's drawn again.
+    pub fn draw_text_ttf(
+        &mut self,
+        text: &str,
+        x: f32,
+        y: f32,
+        color: impl Into<Color>,
+        font: TtfFontHandle,
+    ) -> Result<(), TextureError> {
+        let size = self
+            .ttf_fonts
+            .get(font)
+            .ok_or(TextureError::InvalidHandle)?
+            .size_px;
+
+        self.draw_text_ttf_sized(text, x, y, size, color, font)
+    }
+
+    /// Same as `draw_text_ttf`, but the text is scaled to `size` pixels.
+    /// Fonts loaded with `FontLoadMode::Sdf` stay sharp at any size, the
+    /// others get blurry when they're drawn bigger than they were loaded.
+    pub fn draw_text_ttf_sized(
+        &mut self,
+        text: &str,
+        x: f32,
+        y: f32,
+        size: f32,
+        color: impl Into<Color>,
+        font: TtfFontHandle,
+    ) -> Result<(), TextureError> {
+        let color = color.into();
+        let font = self
+            .ttf_fonts
+            .get_mut(font)
+            .ok_or(TextureError::InvalidHandle)?;
+        let texture = &self.textures[font.texture];
+        font.tick += 1;
+
+        // from the cached glyphs to the size they're drawn at
+        let scale = size / font.raster_px;
+        let padding = font.padding as f32;
+
+        let baseline = y + font.ascent * size / font.size_px;
+        let mut pen_x = x;
+        for c in text.chars() {
+            let glyph = font.cache_glyph(
+                &self.queue,
+                texture,
+                &mut self.current_frame_stats,
+                c,
+            );
+            let metrics = glyph.metrics;
+
+            if let Some(cell) = glyph.cell {
+                let (cell_x, cell_y) = font.cell_origin(cell);
+                // in the cache, with the padding
+                let cached_size = [
+                    metrics.width as f32 + 2.0 * padding,
+                    metrics.height as f32 + 2.0 * padding,
+                ];
+
+                // ymin is where the bottom of the glyph is relative to the
+                // baseline, going up
+                self.rectangles_to_render.push(RectangleDrawData {
+                    pos: [
+                        pen_x + (metrics.xmin as f32 - padding) * scale,
+                        baseline
+                            - (metrics.ymin as f32
+                                + metrics.height as f32
+                                + padding)
+                                * scale,
+                    ],
+                    size: [cached_size[0] * scale, cached_size[1] * scale],
+                    color,
+                    texture_index: font.texture as i32,
+                    uv_pos: [
+                        cell_x as f32 / GLYPH_CACHE_SIZE as f32,
+                        cell_y as f32 / GLYPH_CACHE_SIZE as f32,
+                    ],
+                    uv_size: [
+                        cached_size[0] / GLYPH_CACHE_SIZE as f32,
+                        cached_size[1] / GLYPH_CACHE_SIZE as f32,
+                    ],
+                    ..Default::default()
+                });
+            }
+
+            pen_x += metrics.advance_width * scale;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::color::Color;
+
+// Maps how far along a tween is (0 to 1) to how far between start and end
+// its value is. That's usually 0 to 1 as well, but can overshoot.
+pub type EasingFn = fn(f32) -> f32;
+
+pub const LINEAR: EasingFn = linear;
+pub const EASE_IN_QUAD: EasingFn = ease_in_quad;
+pub const EASE_OUT_QUAD: EasingFn = ease_out_quad;
+pub const EASE_IN_OUT_CUBIC: EasingFn = ease_in_out_cubic;
+pub const BOUNCE: EasingFn = bounce;
+
+fn linear(t: f32) -> f32 {
+    t
+}
+
+fn ease_in_quad(t: f32) -> f32 {
+    t * t
+}
+
+fn ease_out_quad(t: f32) -> f32 {
+    1.0 - (1.0 - t) * (1.0 - t)
+}
+
+fn ease_in_out_cubic(t: f32) -> f32 {
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+    }
+}
+
+// bounces off the end a few times, each bounce smaller than the last
+fn bounce(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+// values that can be blended between, `t` of 0 gives self and 1 gives end
+pub trait Lerp: Copy {
+    fn lerp(self, end: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, end: Self, t: f32) -> Self {
+        self + (end - self) * t
+    }
+}
+
+impl<const N: usize> Lerp for [f32; N] {
+    fn lerp(self, end: Self, t: f32) -> Self {
+        std::array::from_fn(|i| self[i].lerp(end[i], t))
+    }
+}
+
+impl Lerp for Color {
+    fn lerp(self, end: Self, t: f32) -> Self {
+        Color::lerp(self, end, t)
+    }
+}
+
+// Goes from start to end over duration. The value stays at end once it's
+// done.
+#[derive(Copy, Clone, Debug)]
+pub struct Tween<T: Lerp> {
+    pub start: T,
+    pub end: T,
+    pub duration: Duration,
+    pub elapsed: Duration,
+    pub easing: EasingFn,
+}
+
+impl<T: Lerp> Tween<T> {
+    pub fn new(start: T, end: T, duration: Duration, easing: EasingFn) -> Self {
+        Self {
+            start,
+            end,
+            duration,
+            elapsed: Duration::ZERO,
+            easing,
+        }
+    }
+
+    /// Advances the tween by `delta` seconds and returns its new value.
+    pub fn tick(&mut self, delta: f32) -> T {
+        // from_secs_f32 panics on infinity and NaN, and more than the
+        // duration doesn't make a difference anyway
+        let delta = match delta.is_nan() {
+            true => 0.0,
+            false => delta.clamp(0.0, self.duration.as_secs_f32()),
+        };
+
+        self.elapsed =
+            (self.elapsed + Duration::from_secs_f32(delta)).min(self.duration);
+        self.value()
+    }
+
+    pub fn value(&self) -> T {
+        self.start.lerp(self.end, (self.easing)(self.progress()))
+    }
+
+    // how much of the duration has passed, 0 to 1
+    pub fn progress(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+
+        (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Starts over from `start`.
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::ZERO;
+    }
+}
+
+// Tweens by name, so they can all be stepped with one call in the update
+// callback.
+#[derive(Clone, Debug)]
+pub struct TweenManager<T: Lerp> {
+    pub tweens: HashMap<String, Tween<T>>,
+}
+
+impl<T: Lerp> Default for TweenManager<T> {
+    fn default() -> Self {
+        Self {
+            tweens: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Lerp> TweenManager<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the tween, replacing the one with the same name if there is
+    /// one.
+    pub fn insert(&mut self, name: &str, tween: Tween<T>) {
+        self.tweens.insert(name.to_string(), tween);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Tween<T>> {
+        self.tweens.remove(name)
+    }
+
+    /// The current value of the tween called `name`.
+    pub fn value(&self, name: &str) -> Option<T> {
+        self.tweens.get(name).map(Tween::value)
+    }
+
+    /// Advances every tween by `delta` seconds.
+    pub fn tick(&mut self, delta: f32) {
+        for tween in self.tweens.values_mut() {
+            tween.tick(delta);
+        }
+    }
+
+    /// Removes the tweens that reached their end.
+    pub fn remove_finished(&mut self) {
+        self.tweens.retain(|_, tween| !tween.is_finished());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_tween_is_halfway_after_half_the_duration() {
+        let mut tween = Tween::new(0.0, 1.0, Duration::from_secs(1), LINEAR);
+
+        assert_eq!(tween.tick(0.5), 0.5);
+        assert!(!tween.is_finished());
+    }
+
+    #[test]
+    fn tween_stays_at_the_end() {
+        let mut tween = Tween::new(0.0, 10.0, Duration::from_secs(1), LINEAR);
+
+        assert_eq!(tween.tick(3.0), 10.0);
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn tick_handles_infinity_and_nan() {
+        let mut tween = Tween::new(0.0, 1.0, Duration::from_secs(1), LINEAR);
+
+        assert_eq!(tween.tick(f32::NAN), 0.0);
+        assert_eq!(tween.tick(f32::NEG_INFINITY), 0.0);
+        assert_eq!(tween.tick(f32::INFINITY), 1.0);
+    }
+
+    #[test]
+    fn manager_steps_every_tween() {
+        let mut manager = TweenManager::new();
+        manager.insert(
+            "short",
+            Tween::new(0.0, 1.0, Duration::from_millis(500), LINEAR),
+        );
+        manager.insert(
+            "long",
+            Tween::new(0.0, 4.0, Duration::from_secs(2), LINEAR),
+        );
+
+        manager.tick(0.5);
+
+        assert_eq!(manager.value("short"), Some(1.0));
+        assert_eq!(manager.value("long"), Some(1.0));
+        assert_eq!(manager.value("missing"), None);
+
+        manager.remove_finished();
+
+        assert_eq!(manager.value("short"), None);
+        assert_eq!(manager.value("long"), Some(1.0));
+    }
+}
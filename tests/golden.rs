@@ -0,0 +1,231 @@
+use anis::{color::Color, context::Context, headless::ContextError};
+use image::RgbaImage;
+
+// Renders small scenes with a headless context and compares the captured
+// frames with the PNGs in tests/golden. These need a Vulkan, Metal or DX12
+// adapter (lavapipe and WARP work too), so they're ignored by default. Run
+// them with `cargo test --test golden -- --ignored`.
+//
+// Set ANIS_UPDATE_GOLDEN=1 to write the captured frames over the PNGs
+// instead, and check that they look right before committing them. The PNGs
+// have to come from a real render, not be drawn by hand.
+
+const SIZE: u32 = 64;
+
+// how far a channel can be off before a pixel counts as different. sRGB
+// encoding rounds differently on some GPUs.
+const CHANNEL_TOLERANCE: u8 = 1;
+
+// how far an antialiased edge pixel can be off, edges are computed with
+// screen space derivatives which differ a little between GPUs
+const EDGE_TOLERANCE: u8 = 32;
+
+fn golden_path(name: &str) -> String {
+    format!("{}/tests/golden/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+// the tests only run when asked for, so not having an adapter is a failure
+// and not a skip
+fn headless() -> Context<'static> {
+    match Context::build_headless(SIZE, SIZE) {
+        Ok(mut context) => {
+            context.set_clear_color(Color::from_u8_rgb(20, 30, 40));
+            context
+        }
+        Err(
+            e @ (ContextError::NoAdapter | ContextError::UnsupportedBackend(_)),
+        ) => panic!("Golden image tests need a GPU adapter: {}", e),
+        Err(e) => panic!("Could not create a headless context: {}", e),
+    }
+}
+
+fn capture(context: &mut Context) -> RgbaImage {
+    context.update(0.0);
+    context.capture_frame()
+}
+
+// `edge_pixels` is how many pixels can be off by up to EDGE_TOLERANCE
+fn assert_matches_golden(name: &str, frame: &RgbaImage, edge_pixels: usize) {
+    let path = golden_path(&format!("{}.png", name));
+
+    if std::env::var_os("ANIS_UPDATE_GOLDEN").is_some() {
+        frame.save(&path).unwrap();
+        return;
+    }
+
+    let golden = match image::open(&path) {
+        Ok(golden) => golden.to_rgba8(),
+        Err(e) => panic!(
+            "Could not open {}: {}. Render it with ANIS_UPDATE_GOLDEN=1",
+            path, e
+        ),
+    };
+    assert_eq!(frame.dimensions(), golden.dimensions(), "{}", name);
+
+    let mut different = vec![];
+    for ((x, y, pixel), golden) in frame.enumerate_pixels().zip(golden.pixels())
+    {
+        let difference = pixel
+            .0
+            .iter()
+            .zip(golden.0)
+            .map(|(a, b)| a.abs_diff(b))
+            .max()
+            .unwrap();
+
+        assert!(
+            difference <= EDGE_TOLERANCE,
+            "{}: pixel ({}, {}) is {:?}, expected {:?}",
+            name,
+            x,
+            y,
+            pixel.0,
+            golden.0
+        );
+        if difference > CHANNEL_TOLERANCE {
+            different.push((x, y));
+        }
+    }
+
+    assert!(
+        different.len() <= edge_pixels,
+        "{}: {} pixels are different: {:?}",
+        name,
+        different.len(),
+        different
+    );
+}
+
+#[test]
+#[ignore = "needs a GPU adapter, run with --ignored"]
+fn rectangles() {
+    let mut context = headless();
+
+    // overlapping, later ones are drawn on top
+    context.draw_rounded_rect(
+        8.0,
+        8.0,
+        24.0,
+        16.0,
+        0.0,
+        Color::from_u8_rgb(200, 40, 40),
+    );
+    context.draw_rounded_rect(
+        20.0,
+        16.0,
+        32.0,
+        32.0,
+        0.0,
+        Color::from_u8_rgb(40, 180, 90),
+    );
+    context.draw_rounded_rect(
+        4.0,
+        40.0,
+        56.0,
+        8.0,
+        0.0,
+        Color::from_u8_rgb(60, 90, 220),
+    );
+    context.draw_rounded_rect(30.0, 52.0, 10.0, 10.0, 0.0, Color::WHITE);
+
+    assert_matches_golden("rectangles", &capture(&mut context), 0);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter, run with --ignored"]
+fn rounded_corners() {
+    let mut context = headless();
+
+    context.draw_rounded_rect(
+        8.0,
+        6.0,
+        48.0,
+        32.0,
+        10.0,
+        Color::from_u8_rgb(230, 200, 60),
+    );
+    // the radius is clamped to half the height, which makes a pill
+    context.draw_rounded_rect(
+        12.0,
+        44.0,
+        40.0,
+        12.0,
+        6.0,
+        Color::from_u8_rgb(200, 60, 160),
+    );
+    context.draw_rounded_rect(
+        24.0,
+        14.0,
+        16.0,
+        16.0,
+        4.0,
+        Color::from_u8_rgb(40, 40, 40),
+    );
+
+    assert_matches_golden("rounded_corners", &capture(&mut context), 64);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter, run with --ignored"]
+fn text() {
+    let mut context = headless();
+
+    // 5x7 glyphs, drawn at their size in the atlas so every pixel is one
+    // texel
+    let font = context
+        .load_bitmap_font(&golden_path("font.png"), &golden_path("font.toml"))
+        .unwrap();
+
+    context
+        .draw_text("ANIS", 4.0, 4.0, 9.0, Color::WHITE, font)
+        .unwrap();
+    context
+        .draw_text(
+            "SAIN IS",
+            4.0,
+            20.0,
+            9.0,
+            Color::from_u8_rgb(255, 128, 0),
+            font,
+        )
+        .unwrap();
+    context
+        .draw_text(
+            "NA",
+            40.0,
+            50.0,
+            9.0,
+            Color::from_u8_rgb(90, 200, 255),
+            font,
+        )
+        .unwrap();
+
+    assert_matches_golden("text", &capture(&mut context), 0);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter, run with --ignored"]
+fn instanced_rectangles() {
+    let mut context = headless();
+
+    // one instance per rectangle in a single draw call. They overlap by two
+    // pixels, so this also checks that instances are drawn in order. Edges
+    // are on pixel boundaries, so every pixel has to match.
+    for i in 0..49 {
+        let (column, row) = (i % 7, i / 7);
+        context.draw_rounded_rect(
+            2.0 + column as f32 * 8.0,
+            2.0 + row as f32 * 8.0,
+            10.0,
+            10.0,
+            0.0,
+            Color::from_u8_rgb(
+                (column * 36) as u8,
+                (row * 36) as u8,
+                (i * 5) as u8,
+            ),
+        );
+    }
+
+    assert_matches_golden("instanced", &capture(&mut context), 0);
+}
@@ -0,0 +1,104 @@
+#![cfg(feature = "spirv")]
+
+use anis::{
+    color::Color,
+    config::{AppConfig, ShaderSource},
+    context::Context,
+    headless::ContextError,
+};
+use image::RgbaImage;
+
+// The built-in shader compiled to SPIR-V offline has to draw exactly what
+// the WGSL one does. That needs a GPU adapter like the golden image tests,
+// so it's ignored by default, run it with `--ignored`. Compiling the
+// generated source to SPIR-V is checked without a GPU in src/shader.rs.
+
+const SIZE: u32 = 64;
+
+fn headless(
+    context: Result<Context<'static>, ContextError>,
+) -> Context<'static> {
+    match context {
+        Ok(context) => context,
+        Err(
+            e @ (ContextError::NoAdapter | ContextError::UnsupportedBackend(_)),
+        ) => panic!("The SPIR-V test needs a GPU adapter: {}", e),
+        Err(e) => panic!("Could not create a headless context: {}", e),
+    }
+}
+
+// what Context::generated_shader_source gives, compiled the way naga or
+// glslang would offline
+fn compile_to_spirv(wgsl: &str) -> Vec<u32> {
+    let module = naga::front::wgsl::parse_str(wgsl).unwrap();
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    naga::back::spv::write_vec(
+        &module,
+        &info,
+        &naga::back::spv::Options::default(),
+        None,
+    )
+    .unwrap()
+}
+
+fn draw_scene(context: &mut Context) -> RgbaImage {
+    let font_path =
+        |name| format!("{}/tests/golden/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let font = context
+        .load_bitmap_font(&font_path("font.png"), &font_path("font.toml"))
+        .unwrap();
+
+    context.set_clear_color(Color::from_u8_rgb(20, 30, 40));
+    context.draw_rounded_rect(
+        4.0,
+        4.0,
+        56.0,
+        24.0,
+        0.0,
+        Color::from_u8_rgb(200, 40, 40),
+    );
+    context.draw_rounded_rect(
+        8.0,
+        32.0,
+        48.0,
+        28.0,
+        9.0,
+        Color::from_u8_rgb(230, 200, 60),
+    );
+    context
+        .draw_text("ANIS", 8.0, 12.0, 9.0, Color::WHITE, font)
+        .unwrap();
+
+    context.update(0.0);
+    context.capture_frame()
+}
+
+#[test]
+#[ignore = "needs a GPU adapter, run with --ignored"]
+fn spirv_renders_like_wgsl() {
+    let mut wgsl_context = headless(Context::build_headless(SIZE, SIZE));
+    let spirv = compile_to_spirv(&wgsl_context.generated_shader_source());
+
+    let mut spirv_context = headless(Context::build_headless_with_config(
+        SIZE,
+        SIZE,
+        &AppConfig {
+            shader: ShaderSource::SpirV(spirv),
+            ..Default::default()
+        },
+    ));
+
+    let expected = draw_scene(&mut wgsl_context);
+    let frame = draw_scene(&mut spirv_context);
+
+    assert!(
+        frame == expected,
+        "the SPIR-V shader draws something different"
+    );
+}